@@ -0,0 +1,213 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use tracing::info;
+
+use crate::service::command_service::CommandService;
+use crate::storage::command_storage::db_file_path;
+
+/// Name of a backup of `db_url` taken at `timestamp` seconds since the epoch
+pub fn backup_file_name(db_url: &str, timestamp: u64) -> String {
+    let db_file = db_file_path(db_url);
+    let stem = db_file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("commands");
+
+    format!("{stem}-backup-{timestamp}.db")
+}
+
+/// The most recent backup of `db_url` sitting next to it, going by the timestamp in its name
+pub fn latest_backup(db_url: &str) -> Option<PathBuf> {
+    let db_file = db_file_path(db_url);
+    let stem = db_file.file_stem()?.to_str()?;
+    let prefix = format!("{stem}-backup-");
+    let dir = match db_file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    std::fs::read_dir(&dir)
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let timestamp = path
+                .file_name()?
+                .to_str()?
+                .strip_prefix(&prefix)?
+                .strip_suffix(".db")?
+                .parse::<u64>()
+                .ok()?;
+            Some((timestamp, path))
+        })
+        .max_by_key(|(timestamp, _)| *timestamp)
+        .map(|(_, path)| path)
+}
+
+/// Offers to restore the latest backup of a corrupted database, returns whether it was
+pub async fn offer_restore(db_url: &str) -> Result<bool> {
+    let Some(backup_path) = latest_backup(db_url) else {
+        println!("No backup of {db_url} was found, it can be moved away to start afresh");
+        return Ok(false);
+    };
+
+    let backup_path = backup_path
+        .to_str()
+        .ok_or_else(|| anyhow!("The backup path {backup_path:?} is not valid UTF-8"))?;
+
+    let question = format!("{db_url} is corrupted, restore it from {backup_path}?");
+    if !super::confirm(&question)? {
+        return Ok(false);
+    }
+
+    restore(db_url, backup_path).await?;
+    Ok(true)
+}
+
+/// Snapshots the database. When `target` is absent or a directory, the backup gets a
+/// timestamped name inside it, otherwise it is written to exactly `target`.
+pub async fn backup(
+    command_service: &CommandService,
+    db_url: &str,
+    target: Option<&str>,
+) -> Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .with_context(|| "System clock is set before the epoch")?
+        .as_secs();
+
+    let target = match target.map(Path::new) {
+        Some(path) if !path.is_dir() => path.to_path_buf(),
+        Some(dir) => dir.join(backup_file_name(db_url, timestamp)),
+        None => PathBuf::from(backup_file_name(db_url, timestamp)),
+    };
+
+    if target.exists() {
+        return Err(anyhow!("The backup file {target:?} already exists"));
+    }
+
+    let target_str = target
+        .to_str()
+        .ok_or_else(|| anyhow!("The backup path {target:?} is not valid UTF-8"))?;
+
+    info!("Backing up {db_url} to {target_str}");
+    command_service
+        .backup_to(target_str)
+        .await
+        .with_context(|| format!("Failed to back up the database to {target_str}"))?;
+
+    Ok(target)
+}
+
+/// Replaces the database with `backup_path` once the backup is known to be loadable.
+/// Leftover WAL files of the replaced database are removed so they are not replayed.
+pub async fn restore(db_url: &str, backup_path: &str) -> Result<()> {
+    if !Path::new(backup_path).is_file() {
+        return Err(anyhow!("The backup {backup_path} is not a file"));
+    }
+
+    let backup_service = CommandService::open_read_only(backup_path)
+        .await
+        .with_context(|| format!("The backup {backup_path} can not be opened"))?;
+    let commands = backup_service
+        .get_all_commands_including_disabled()
+        .await
+        .with_context(|| format!("The backup {backup_path} can not be read"))?;
+    drop(backup_service);
+
+    let db_file = db_file_path(db_url);
+    info!(
+        "Restoring {} commands from {backup_path} into {db_file:?}",
+        commands.len()
+    );
+
+    std::fs::copy(backup_path, &db_file)
+        .with_context(|| format!("Failed to copy {backup_path} over {db_file:?}"))?;
+
+    for suffix in ["-wal", "-shm"] {
+        let mut leftover = db_file.clone().into_os_string();
+        leftover.push(suffix);
+        let _ = std::fs::remove_file(leftover);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use super::{backup, backup_file_name, latest_backup, restore};
+    use crate::service::command_service::CommandService;
+    use crate::storage::command_storage::db_file_path;
+
+    #[test]
+    fn test_backup_file_name() {
+        assert_eq!(
+            backup_file_name("sqlite://commands.db", 42),
+            "commands-backup-42.db".to_string()
+        );
+        assert_eq!(
+            db_file_path("sqlite://commands.db"),
+            db_file_path("commands.db")
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_backup_and_restore() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        let backup_path = backup(&service, "test.sqlite", Some("test_backup.sqlite"))
+            .await
+            .unwrap();
+
+        let _ = service
+            .insert_command("ls -a", "ls_all", None)
+            .await
+            .unwrap();
+        drop(service);
+
+        restore("test.sqlite", backup_path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let commands = service.get_all_commands().await.unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].alias, "git_pull".to_string());
+
+        std::fs::remove_file("test.sqlite").unwrap();
+        std::fs::remove_file("test_backup.sqlite").unwrap();
+    }
+
+    #[test]
+    fn test_latest_backup() {
+        let dir = std::env::temp_dir().join("command_organiser_latest_backup");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let db_url = dir.join("commands.db");
+        let db_url = db_url.to_str().unwrap();
+        assert_eq!(latest_backup(db_url), None);
+
+        for name in [
+            "commands-backup-9.db",
+            "commands-backup-10.db",
+            "other-backup-99.db",
+        ] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        assert_eq!(
+            latest_backup(db_url),
+            Some(dir.join("commands-backup-10.db"))
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+use std::ffi::OsString;
+
+use crossterm::style::{Color, Stylize};
+
+/// Whether output may be styled, honouring `--no-color` and the `NO_COLOR` convention
+pub fn should_colorize(no_color: bool) -> bool {
+    colorize_with(no_color, std::env::var_os("NO_COLOR"))
+}
+
+/// `NO_COLOR` disables styling whenever it is set to a non-empty value
+fn colorize_with(no_color: bool, no_color_env: Option<OsString>) -> bool {
+    !no_color && no_color_env.is_none_or(|value| value.is_empty())
+}
+
+pub fn paint(text: &str, color: Color, colorize: bool) -> String {
+    if colorize {
+        text.with(color).bold().to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use crossterm::style::Color;
+
+    use super::{colorize_with, paint};
+
+    #[test]
+    fn test_no_color_env() {
+        assert!(colorize_with(false, None));
+        assert!(colorize_with(false, Some(OsString::new())));
+        assert!(!colorize_with(false, Some(OsString::from("1"))));
+
+        let colorize = colorize_with(false, Some(OsString::from("1")));
+        assert_eq!(paint("git_pull", Color::Cyan, colorize), "git_pull");
+    }
+
+    #[test]
+    fn test_flag_overrides_env() {
+        assert!(!colorize_with(true, None));
+        assert!(!colorize_with(true, Some(OsString::new())));
+
+        assert_ne!(paint("git_pull", Color::Cyan, true), "git_pull");
+        assert!(paint("git_pull", Color::Cyan, true).contains("\u{1b}["));
+    }
+}
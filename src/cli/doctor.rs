@@ -0,0 +1,192 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crossterm::style::Color;
+
+use super::color::paint;
+use crate::config::app_config::AppConfig;
+use crate::model::command::Command;
+use crate::service::command_service::CommandService;
+use crate::storage::command_storage::db_file_path;
+use crate::ui::clipboard::check_clipboard;
+
+/// The outcome of one of the checks of `doctor`
+#[derive(Debug, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub passed: bool,
+    pub details: String,
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, details: impl Into<String>) -> CheckResult {
+        CheckResult {
+            name,
+            passed: true,
+            details: details.into(),
+        }
+    }
+
+    fn fail(name: &'static str, details: impl Into<String>) -> CheckResult {
+        CheckResult {
+            name,
+            passed: false,
+            details: details.into(),
+        }
+    }
+
+    /// The checklist line of the check, e.g. `[PASS] Config: ...`
+    pub fn line(&self, colorize: bool) -> String {
+        let mark = if self.passed {
+            paint("[PASS]", Color::Green, colorize)
+        } else {
+            paint("[FAIL]", Color::Red, colorize)
+        };
+
+        format!("{mark} {}: {}", self.name, self.details)
+    }
+}
+
+/// The config file parses, a missing one meaning the defaults are used
+pub fn check_config(path: &str) -> CheckResult {
+    const NAME: &str = "Config";
+
+    if !Path::new(path).is_file() {
+        return CheckResult::pass(NAME, format!("no file at {path}, using the defaults"));
+    }
+
+    match AppConfig::load(path) {
+        Ok(_) => CheckResult::pass(NAME, format!("{path} is valid")),
+        Err(e) => CheckResult::fail(NAME, format!("{path}: {e}")),
+    }
+}
+
+/// The db file exists and can be read
+pub fn check_db_file(db_url: &str) -> CheckResult {
+    const NAME: &str = "Database file";
+    let path = db_file_path(db_url);
+
+    if !path.is_file() {
+        return CheckResult::fail(NAME, format!("there is no database at {}", path.display()));
+    }
+
+    match std::fs::File::open(&path) {
+        Ok(_) => CheckResult::pass(NAME, format!("{} is readable", path.display())),
+        Err(e) => CheckResult::fail(NAME, format!("{} can not be read: {e}", path.display())),
+    }
+}
+
+/// The clipboard the config picks can be initialised, without replacing what it holds
+pub fn check_clipboard_provider(config: &AppConfig) -> CheckResult {
+    const NAME: &str = "Clipboard";
+
+    match check_clipboard(
+        config.clipboard,
+        config.clipboard_file.as_deref().map(Path::new),
+    ) {
+        Ok(provider) => CheckResult::pass(NAME, format!("copying with the {provider:?} provider")),
+        Err(e) => CheckResult::fail(NAME, format!("the clipboard is unavailable: {e}")),
+    }
+}
+
+/// Every stored executable is a shell builtin or found on the PATH
+pub fn check_executables(commands: &[Command]) -> CheckResult {
+    const NAME: &str = "Executables";
+
+    let missing: BTreeSet<&str> = commands
+        .iter()
+        .filter(|command| command.validate_executable().is_err())
+        .map(|command| command.executable.as_str())
+        .collect();
+
+    if missing.is_empty() {
+        CheckResult::pass(NAME, "every stored executable is available")
+    } else {
+        CheckResult::fail(
+            NAME,
+            format!(
+                "not found on the PATH: {}",
+                missing.into_iter().collect::<Vec<_>>().join(", ")
+            ),
+        )
+    }
+}
+
+/// Runs every check, those needing the db only once it could be opened
+pub async fn run_checks(db_url: &str, config_path: &str, config: &AppConfig) -> Vec<CheckResult> {
+    let mut results = vec![check_config(config_path), check_db_file(db_url)];
+
+    if results[1].passed {
+        // Opening the db runs `PRAGMA integrity_check`
+        match CommandService::with_options(db_url, &config.storage_options()).await {
+            Ok(command_service) => {
+                results.push(CheckResult::pass("Integrity", "the database is sound"));
+                match command_service.get_all_commands_including_disabled().await {
+                    Ok(commands) => results.push(check_executables(&commands)),
+                    Err(e) => results.push(CheckResult::fail(
+                        "Executables",
+                        format!("the commands could not be read: {e}"),
+                    )),
+                }
+                command_service.close().await;
+            }
+            Err(e) => results.push(CheckResult::fail("Integrity", e.to_string())),
+        }
+    }
+
+    results.push(check_clipboard_provider(config));
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_config, check_db_file, check_executables};
+    use crate::model::command::Command;
+
+    #[test]
+    fn test_check_config() {
+        assert!(check_config("missing_doctor_config.toml").passed);
+
+        std::fs::write("test_doctor_config.toml", "exit_on_copy = \"sometimes\"").unwrap();
+        let result = check_config("test_doctor_config.toml");
+        assert!(!result.passed);
+        assert!(result.details.contains("test_doctor_config.toml"));
+
+        std::fs::write("test_doctor_config.toml", "exit_on_copy = false").unwrap();
+        assert!(check_config("test_doctor_config.toml").passed);
+
+        std::fs::remove_file("test_doctor_config.toml").unwrap();
+    }
+
+    #[test]
+    fn test_check_db_file() {
+        let result = check_db_file("sqlite://missing_doctor.sqlite");
+        assert!(!result.passed);
+        assert!(result.details.contains("missing_doctor.sqlite"));
+    }
+
+    #[test]
+    fn test_check_executables() {
+        let command = |executable: &str, command: &str| {
+            Command::new(
+                executable.to_string(),
+                command.to_string(),
+                executable.to_string(),
+                None,
+            )
+        };
+
+        assert!(check_executables(&[command("cd", "cd /tmp"), command("sh", "sh")]).passed);
+
+        let result = check_executables(&[
+            command("cd", "cd /tmp"),
+            command("definitely_not_a_command", "definitely_not_a_command -v"),
+        ]);
+        assert!(!result.passed);
+        assert_eq!(
+            result.details,
+            "not found on the PATH: definitely_not_a_command"
+        );
+    }
+}
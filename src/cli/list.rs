@@ -0,0 +1,134 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use crossterm::style::Color;
+
+use super::color::paint;
+use crate::model::command::Command;
+use crate::service::export::{render, ExportFormat};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Toml,
+}
+
+/// Renders the commands in `format`, the toml output can be imported back with `--file`.
+/// Only the text format gets colored.
+pub fn format_commands(
+    commands: &[Command],
+    format: OutputFormat,
+    colorize: bool,
+) -> Result<String> {
+    match format {
+        OutputFormat::Text => Ok(commands
+            .iter()
+            .map(|command| {
+                let alias = paint(&command.alias, Color::Cyan, colorize);
+                format!("{alias}: {}\n", command.command)
+            })
+            .collect()),
+        OutputFormat::Json => render(commands, ExportFormat::Json)
+            .with_context(|| "Failed to serialise the commands to json"),
+        OutputFormat::Toml => render(commands, ExportFormat::Toml)
+            .with_context(|| "Failed to serialise the commands to toml"),
+    }
+}
+
+/// Writes the formatted commands to `output`, creating its parent directories, or to stdout
+pub fn write_commands(
+    commands: &[Command],
+    format: OutputFormat,
+    colorize: bool,
+    output: Option<&Path>,
+) -> Result<()> {
+    let formatted = format_commands(commands, format, colorize)?;
+
+    match output {
+        Some(path) => {
+            if let Some(parent) = path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+            {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+
+            std::fs::write(path, formatted)
+                .with_context(|| format!("Failed to write the commands to {}", path.display()))
+        }
+        None => {
+            print!("{formatted}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{format_commands, write_commands, OutputFormat};
+    use crate::model::command::{Command, Tags};
+
+    fn commands() -> Vec<Command> {
+        vec![
+            Command {
+                tags: Tags(vec!["vcs".to_string()]),
+                favorite: true,
+                ..Command::new(
+                    "git".to_string(),
+                    "git pull".to_string(),
+                    "git_pull".to_string(),
+                    Some("Pull the changes".to_string()),
+                )
+            },
+            Command {
+                environment: Some("prod".to_string()),
+                ..Command::new(
+                    "kubectl".to_string(),
+                    "kubectl get pods".to_string(),
+                    "pods".to_string(),
+                    None,
+                )
+            },
+        ]
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let json = format_commands(&commands(), OutputFormat::Json, true).unwrap();
+        let parsed: Vec<Command> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, commands());
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let toml = format_commands(&commands(), OutputFormat::Toml, true).unwrap();
+        let parsed: HashMap<String, Vec<Command>> = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed["commands"], commands());
+    }
+
+    #[test]
+    fn test_text_output() {
+        let text = format_commands(&commands(), OutputFormat::Text, false).unwrap();
+        assert_eq!(text, "git_pull: git pull\npods: kubectl get pods\n");
+
+        let text = format_commands(&commands(), OutputFormat::Text, true).unwrap();
+        assert!(text.contains("\u{1b}["));
+    }
+
+    #[test]
+    fn test_output_creates_parent_dirs() {
+        let path = std::env::temp_dir().join("command_organiser_list/nested/out.json");
+        let _ = std::fs::remove_dir_all(std::env::temp_dir().join("command_organiser_list"));
+
+        write_commands(&commands(), OutputFormat::Json, false, Some(&path)).unwrap();
+        assert!(path.is_file());
+
+        std::fs::remove_dir_all(std::env::temp_dir().join("command_organiser_list")).unwrap();
+    }
+}
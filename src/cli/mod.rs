@@ -1,77 +1,1646 @@
-use std::collections::HashMap;
-use std::path::Path;
+pub mod backup;
+pub mod color;
+pub mod doctor;
+pub mod list;
+pub mod serve;
+pub mod watch;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
-use clap::Parser;
-use tracing::{info, trace, warn, Level};
+use clap::{Parser, Subcommand};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::Color;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use directories::ProjectDirs;
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::{info, trace, warn};
 
-use crate::model::command::Command;
-use crate::service::command_service::CommandService;
+use crate::config::app_config::AppConfig;
+use crate::model::command::{
+    generate_unique_alias, strip_trailing_comment, Command, ValidationError,
+};
+use crate::model::danger::is_dangerous;
+use crate::model::placeholder::{expand_automatic, extract_positionals, substitute_positionals};
+use crate::service::command_service::{
+    normalized_executable, CommandService, ConflictPolicy, ImportOutcome, ImportReport,
+};
+use crate::service::crypto::PASSPHRASE_VAR;
+use crate::service::export::ExportFormat;
+use crate::service::runner::{check_exit, resolve_working_dir, run_shell, ExitCheck, RunOutcome};
+use crate::storage::command_storage::{db_file_path, OrderBy};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-struct Args {
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+
     /// Name of the person to greet
     #[arg(short, long)]
     file: Option<String>,
 
-    /// Specify the db file name
-    #[arg(short, long, default_value = "commands.db")]
-    db_file: String,
+    /// Specify the db file, `commands.db` in the data directory of the user by default
+    #[arg(short, long, default_value_t = default_db_file().display().to_string())]
+    pub db_file: String,
+
+    /// Specify the config file
+    #[arg(long, default_value = "config.toml")]
+    pub config: String,
 
-    /// Populate the db in termianl, interactive mode
+    /// Populate the db in termianl, interactive mode: ask what to do with the imported
+    /// commands whose alias is already taken
     #[arg(short, long, default_value_t = false)]
     interactive: bool,
 
+    /// Disable colors in the output, also done by setting NO_COLOR
+    #[arg(long, global = true, default_value_t = false)]
+    pub no_color: bool,
+
+    /// Skip imported commands that are not valid shell syntax
+    #[arg(long, default_value_t = false)]
+    pub check: bool,
+
+    /// Remove the trailing `# comment` of imported commands
+    #[arg(long, default_value_t = false)]
+    pub strip_comments: bool,
+
+    /// Only print how many commands of the import file would be inserted, asked about, skipped
+    /// or fail, honouring `--interactive` and `--check`
+    #[arg(long, default_value_t = false)]
+    pub preview: bool,
+
     /// Number of times to greet
     #[arg(short, long, default_value_t = 1)]
     count: u8,
 }
 
-pub async fn read_commands_from_file(file: String) -> Result<Vec<Command>> {
+#[derive(Subcommand, Debug)]
+pub enum CliCommand {
+    /// Read newline-delimited JSON requests from stdin and answer on stdout
+    Serve,
+
+    /// Print every stored command
+    List {
+        /// Format of the listed commands
+        #[arg(long, value_enum, default_value_t = list::OutputFormat::Text)]
+        format: list::OutputFormat,
+
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Order of the listed commands
+        #[arg(long, value_enum, default_value_t = OrderBy::Alias)]
+        sort: OrderBy,
+
+        /// List the disabled commands too
+        #[arg(long)]
+        all: bool,
+
+        /// Only the commands whose executable starts with this, e.g. `kube` for `kubectl` and
+        /// `kubectx`. They are ordered by executable.
+        #[arg(long, conflicts_with = "sort")]
+        executable_prefix: Option<String>,
+    },
+
+    /// Print the commands whose alias, command or description contain the query
+    Search {
+        query: String,
+
+        /// Print at most this many commands, all of them by default
+        #[arg(long)]
+        limit: Option<u32>,
+
+        /// Skip this many commands first, to page through the results with --limit
+        #[arg(long)]
+        offset: Option<u32>,
+
+        /// Format of the printed commands
+        #[arg(long, value_enum, default_value_t = list::OutputFormat::Text)]
+        format: list::OutputFormat,
+    },
+
+    /// Write the commands to a file, only those matching the given tag and executable
+    Export {
+        /// The file to write, named after the format by default, e.g. `commands.toml`
+        path: Option<String>,
+
+        /// Format of the exported commands
+        #[arg(long, value_enum, default_value_t = ExportFormat::Toml)]
+        format: ExportFormat,
+
+        /// Only export the commands with this tag
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Only export the commands of this executable
+        #[arg(long)]
+        executable: Option<String>,
+    },
+
+    /// Write every command as a recipe of a justfile
+    Justfile {
+        /// The justfile to write
+        #[arg(default_value = "justfile")]
+        path: String,
+    },
+
+    /// Write a markdown cheatsheet of every command, with a section per executable
+    Markdown {
+        /// The markdown file to write
+        #[arg(default_value = "commands.md")]
+        path: String,
+    },
+
+    /// Print how many commands are stored, per executable
+    Stats,
+
+    /// Check the database, the config, the clipboard and the stored executables
+    Doctor,
+
+    /// Recompute the executable of every stored command and fix the misfiled ones
+    Reclassify,
+
+    /// Tag every command with its executable, skipping those tagged so already
+    AutoTag {
+        /// Tag with the group of the executable from `executable_groups` instead
+        #[arg(long)]
+        by_group: bool,
+    },
+
+    /// Snapshot the database, to a timestamped file unless a file path is given
+    Backup {
+        /// Directory or file to write the backup to
+        path: Option<String>,
+    },
+
+    /// Import the aliases of a shell rc file, e.g. `~/.bash_aliases`
+    Aliases {
+        /// The file defining the aliases
+        path: PathBuf,
+    },
+
+    /// Import the commands of a cheat sheet of `# description` and command blocks
+    Cheat {
+        /// The cheat sheet to import
+        path: PathBuf,
+    },
+
+    /// Shrink the database file after many commands were deleted
+    Vacuum,
+
+    /// Hide every variant of an alias in the archive, purged after `archive_retention_days`
+    Archive {
+        /// The alias to archive
+        alias: String,
+    },
+
+    /// Take every variant of an alias out of the archive and show it again
+    Unarchive {
+        /// The alias to restore
+        alias: String,
+    },
+
+    /// Add the commands of another database to this one
+    Merge {
+        /// The database to take the commands from
+        path: String,
+
+        /// What to do with a command whose alias or command string is already stored
+        #[arg(long, value_enum, default_value_t = ConflictPolicy::Skip)]
+        on_conflict: ConflictPolicy,
+    },
+
+    /// Run a stored command, prompting for the positional arguments it references
+    Run {
+        /// Alias of the command to run
+        alias: String,
+
+        /// Run the variant of the alias for this environment
+        #[arg(short, long)]
+        environment: Option<String>,
+
+        /// Kill the command after this many seconds, overriding `run_timeout_secs`
+        #[arg(short, long)]
+        timeout: Option<u64>,
+    },
+
+    /// Record the exit code a command is expected to end with, checked whenever it is run
+    Expect {
+        /// Alias of the command
+        alias: String,
+
+        /// The expected exit code, the expectation is removed when left out
+        #[arg(allow_negative_numbers = true)]
+        exit_code: Option<i32>,
+
+        /// Set it on the variant of the alias for this environment
+        #[arg(short, long)]
+        environment: Option<String>,
+    },
+
+    /// Store a command, or every `alias<TAB>command` line piped in with --stdin
+    Add {
+        /// The alias the command is looked up by
+        #[arg(required_unless_present = "stdin")]
+        alias: Option<String>,
+
+        /// The command itself, quoted when it has spaces
+        #[arg(required_unless_present = "stdin")]
+        command: Option<String>,
+
+        /// Read `alias<TAB>command` lines from stdin instead
+        #[arg(long, conflicts_with_all = ["alias", "command"])]
+        stdin: bool,
+
+        /// Store the commands encrypted, asking for the passphrase
+        #[arg(long)]
+        sensitive: bool,
+
+        /// Shell the commands are run with, e.g. `bash` or `fish`
+        #[arg(long)]
+        shell: Option<String>,
+    },
+
+    /// Store a command unless its alias or command string is stored already, for scripts
+    /// that have to run more than once
+    Ensure {
+        /// The alias the command is looked up by
+        alias: String,
+
+        /// The command itself, quoted when it has spaces
+        command: String,
+
+        /// What the command does
+        #[arg(long)]
+        description: Option<String>,
+    },
+
+    /// Import a TOML file, then keep the database in sync with it whenever it is saved
+    Watch {
+        /// The import file to watch
+        file: PathBuf,
+    },
+
+    /// Replace the database with a previously taken backup
+    Restore {
+        /// The backup file to restore
+        path: String,
+
+        /// Do not ask for confirmation
+        #[arg(short, long, default_value_t = false)]
+        yes: bool,
+    },
+}
+
+/// `commands.db` in the data directory of the user, e.g.
+/// `$XDG_DATA_HOME/command_organiser/commands.db`, or in the working directory when there is
+/// no home directory to find it from
+pub fn default_db_file() -> PathBuf {
+    match ProjectDirs::from("", "", env!("CARGO_PKG_NAME")) {
+        Some(dirs) => dirs.data_dir().join("commands.db"),
+        None => PathBuf::from("commands.db"),
+    }
+}
+
+/// Creates the directory the db file goes in when it does not exist yet
+pub fn create_db_dir(db_file: &str) -> Result<()> {
+    match db_file_path(db_file).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() && !dir.exists() => {
+            info!("Creating the db directory {dir:?}");
+            std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {dir:?}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Layout of the import file
+#[derive(Debug, Deserialize)]
+struct ImportFile {
+    commands: Vec<Command>,
+    /// Description given to the commands of an executable that have none of their own
+    #[serde(default)]
+    executable_defaults: HashMap<String, String>,
+}
+
+/// An entry of the import file that can not be stored
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Entry {index} ({alias}): {source}")]
+pub struct ImportError {
+    /// Position of the entry in the file, starting at 1
+    pub index: usize,
+    pub alias: String,
+    pub source: ValidationError,
+}
+
+/// Checks every entry has the fields the db needs, with aliases of up to `max_alias_len`
+/// characters, collecting the errors of all the entries
+pub fn validate_import(parsed: &[Command], max_alias_len: usize) -> Result<(), Vec<ImportError>> {
+    let errors: Vec<ImportError> = parsed
+        .iter()
+        .enumerate()
+        .filter_map(|(index, command)| {
+            command
+                .validate(max_alias_len)
+                .err()
+                .map(|source| ImportError {
+                    index: index + 1,
+                    alias: if command.alias.is_empty() {
+                        "no alias".to_string()
+                    } else {
+                        command.alias.clone()
+                    },
+                    source,
+                })
+        })
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// How the commands of an import file are laid out, shown when the file looks different
+const IMPORT_EXAMPLE: &str =
+    "[[commands]]\ncommand = \"git pull\"\nexecutable = \"git\"\nalias = \"git_pull\"";
+
+/// Catches a missing or misshapen `commands` key, which serde only reports as a type mismatch
+fn check_import_shape(toml_string: &str) -> Result<()> {
+    let table: toml::Table =
+        toml::from_str(toml_string).with_context(|| "The import file is not valid TOML")?;
+
+    match table.get("commands") {
+        Some(toml::Value::Array(_)) => Ok(()),
+        Some(value) => Err(anyhow!(
+            "`commands` must be a list of tables but it is a {}, write each command as:\n{IMPORT_EXAMPLE}",
+            value.type_str()
+        )),
+        None => Err(anyhow!(
+            "The import file has no `commands`, write each command as:\n{IMPORT_EXAMPLE}"
+        )),
+    }
+}
+
+/// Parses the commands of an import file, filling in the executable default descriptions and
+/// generating the aliases left out. Nothing is returned unless every entry is valid.
+pub fn parse_commands(toml_string: &str, max_alias_len: usize) -> Result<Vec<Command>> {
+    check_import_shape(toml_string)?;
+
+    let ImportFile {
+        mut commands,
+        executable_defaults,
+    } = toml::from_str(toml_string)
+        .with_context(|| "Failed to deserialise the commands from file")?;
+
+    let mut taken: HashSet<String> = commands
+        .iter()
+        .map(|command| command.alias.clone())
+        .collect();
+    for command in &mut commands {
+        if command.alias.trim().is_empty() && !command.command.trim().is_empty() {
+            command.alias = generate_unique_alias(&command.command, &taken);
+            taken.insert(command.alias.clone());
+        }
+    }
+
+    if let Err(errors) = validate_import(&commands, max_alias_len) {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        return Err(anyhow!(
+            "The import file has {} invalid entries:\n{}",
+            errors.len(),
+            errors.join("\n")
+        ));
+    }
+
+    for command in &mut commands {
+        if command.description.is_none() {
+            command.description = executable_defaults.get(&command.executable).cloned();
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Header every SQLite database file starts with
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+
+/// True when the file at `path` starts like a SQLite database
+pub fn is_sqlite_file(path: &Path) -> std::io::Result<bool> {
+    let mut header = [0u8; SQLITE_MAGIC.len()];
+    let mut file = std::fs::File::open(path)?;
+
+    match std::io::Read::read_exact(&mut file, &mut header) {
+        Ok(()) => Ok(header == SQLITE_MAGIC),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Fails when the import file is the database itself, which would only lead to confusing
+/// parse errors
+pub fn check_not_database(file: &Path, db_file: &str) -> Result<()> {
+    let db_path = db_file_path(db_file);
+    let same = match (file.canonicalize(), db_path.canonicalize()) {
+        (Ok(file), Ok(db)) => file == db,
+        _ => false,
+    };
+
+    if same {
+        return Err(anyhow!(
+            "{file:?} is the database given with --db-file, pass a TOML file to --file"
+        ));
+    }
+
+    Ok(())
+}
+
+pub async fn read_commands_from_file(file: String, max_alias_len: usize) -> Result<Vec<Command>> {
     let input_file_path = Path::new(&file);
     if !input_file_path.is_file() {
         return Err(anyhow!("path is not a file"));
     }
+    if is_sqlite_file(input_file_path)
+        .with_context(|| format!("Failed to read {input_file_path:?}"))?
+    {
+        return Err(anyhow!(
+            "{input_file_path:?} is a SQLite database, not a TOML import file. \
+            Use `merge` to import the commands of another database"
+        ));
+    }
 
     trace!("Parsing the file: {file}");
 
     let toml_string = std::fs::read_to_string(input_file_path)
         .with_context(|| format!("Failed to read the contents from {input_file_path:?}"))?;
 
-    let commands: HashMap<String, Vec<Command>> = toml::from_str(&toml_string)
-        .with_context(|| format!("Failed to deserialise the commands from file"))?;
+    parse_commands(&toml_string, max_alias_len)
+}
+
+/// Parses `alias<TAB>command` lines, skipping the blank ones. A malformed line is reported and
+/// skipped without stopping the others.
+pub fn read_commands_from_reader<R: BufRead>(reader: R) -> Result<Vec<Command>> {
+    let mut commands = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.with_context(|| "Failed to read the commands")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let parsed = line
+            .split_once('\t')
+            .map(|(alias, command)| (alias.trim(), command.trim()))
+            .filter(|(alias, command)| !alias.is_empty() && !command.is_empty());
+        let Some((alias, command)) = parsed else {
+            warn!(
+                "Skipping line {} because it is not `alias<TAB>command`",
+                index + 1
+            );
+            continue;
+        };
 
-    let commands = commands["commands"].clone();
+        match normalized_executable(command) {
+            Ok(executable) => commands.push(Command::new(
+                executable,
+                command.to_string(),
+                alias.to_string(),
+                None,
+            )),
+            Err(e) => warn!("Skipping line {} because: {e}", index + 1),
+        }
+    }
 
     Ok(commands)
 }
 
-pub async fn populate_db() -> Result<()> {
-    let args = Args::parse();
+/// How many commands of an import file would be inserted, asked about, skipped or fail
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportPreview {
+    pub inserted: usize,
+    /// Commands whose alias, environment and os are already stored, asked about in
+    /// interactive mode. Only counted there, otherwise they are skipped.
+    pub asked: usize,
+    /// Commands whose alias or command string is already stored
+    pub skipped: usize,
+    /// Commands failing the syntax check or with no executable
+    pub failed: usize,
+}
+
+impl std::fmt::Display for ImportPreview {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} would be inserted, {} asked about, {} skipped, {} failed",
+            self.inserted, self.asked, self.skipped, self.failed
+        )
+    }
+}
+
+/// Classifies the imported commands the way `populate_db` would store them, without writing
+/// anything. `interactive` and `check` stand for its flags of the same name. The service is
+/// expected to be unlocked when the file holds sensitive commands, as `populate_db` does.
+pub async fn preview_import(
+    command_service: &CommandService,
+    commands: &[Command],
+    interactive: bool,
+    check: bool,
+) -> Result<ImportPreview> {
+    let stored = command_service
+        .get_all_commands_including_disabled()
+        .await?;
+    let mut preview = ImportPreview::default();
+
+    let valid: Vec<Command> = commands
+        .iter()
+        .filter(|command| {
+            !(check && command.syntax_check().is_err())
+                && normalized_executable(&command.command).is_ok()
+        })
+        .cloned()
+        .collect();
+    preview.failed = commands.len() - valid.len();
+
+    // Only the variant of a command decides whether it conflicts, the import never compares
+    // the rest of the row
+    let added = watch::diff_imports(&stored, &valid).added;
+    let conflicts = valid.len() - added.len();
+    if interactive {
+        preview.asked += conflicts;
+    } else {
+        preview.skipped += conflicts;
+    }
+
+    // A sensitive command is stored sealed, so it only collides with a sensitive row
+    let same_text = |a: &Command, b: &Command| a.command == b.command && a.sensitive == b.sensitive;
+    let mut inserted: Vec<Command> = Vec::new();
+    for command in added {
+        // Entries of the file conflict with the ones inserted before them
+        let conflict = inserted
+            .iter()
+            .any(|previous| watch::same_entry(previous, &command));
+        if conflict && interactive {
+            preview.asked += 1;
+        } else if conflict
+            || stored
+                .iter()
+                .chain(&inserted)
+                .any(|existing| same_text(existing, &command))
+        {
+            preview.skipped += 1;
+        } else {
+            preview.inserted += 1;
+            inserted.push(command);
+        }
+    }
+
+    Ok(preview)
+}
 
-    if let Some(file) = args.file {
+pub async fn populate_db(args: &Args, config: &AppConfig) -> Result<()> {
+    if let Some(file) = args.file.clone() {
         info!("Populating the db from input file: {}", file);
-        let commands = read_commands_from_file(file).await?;
+        check_not_database(Path::new(&file), &args.db_file)?;
+        let commands = read_commands_from_file(file.clone(), config.max_alias_len).await?;
 
         info!("Creating the command service...");
-        let command_service = CommandService::new(&args.db_file)
+        let mut command_service = command_service(args, config).await?;
+        command_service
+            .unlock_when_needed(
+                commands.iter().any(|command| command.sensitive),
+                read_passphrase,
+            )
             .await
-            .with_context(|| format!("Failed to create the Command Service"))?;
+            .with_context(|| "Failed to unlock the sensitive commands")?;
 
-        for command in commands {
-            let inserted = command_service
-                .insert_command(&command.command, &command.alias, command.description)
-                .await;
+        if args.preview {
+            let commands: Vec<Command> = commands
+                .into_iter()
+                .map(|command| Command {
+                    command: if args.strip_comments {
+                        strip_trailing_comment(&command.command)
+                    } else {
+                        command.command
+                    },
+                    ..command
+                })
+                .collect();
+            let interactive = args.interactive && std::io::stdin().is_terminal();
+            let preview =
+                preview_import(&command_service, &commands, interactive, args.check).await?;
+            println!("Preview of {file}: {preview}");
+            return Ok(());
+        }
 
-            if let Err(e) = inserted {
-                warn!(
-                    "Could not insert command {} because: {:?}",
-                    command.alias, e
-                );
+        let mut report = ImportReport::default();
+        for mut command in commands {
+            if args.strip_comments {
+                command.command = strip_trailing_comment(&command.command);
+            }
+
+            if args.check {
+                if let Err(e) = command.syntax_check() {
+                    warn!("Skipping command {} because: {e}", command.alias);
+                    report.record(ImportOutcome::Failed);
+                    continue;
+                }
+            }
+
+            let mut replace = false;
+            if args.interactive && std::io::stdin().is_terminal() {
+                if let Some(existing) = command_service.find_variant(&command).await? {
+                    match resolve_conflict(&existing, &command)? {
+                        ConflictResolution::Skip => {
+                            report.record(ImportOutcome::Duplicate);
+                            continue;
+                        }
+                        ConflictResolution::Replace => replace = true,
+                        ConflictResolution::Rename(alias) => command.alias = alias,
+                    }
+                }
             }
+
+            let outcome = if replace {
+                let alias = command.alias.clone();
+                match command_service.upsert_command(command).await {
+                    Ok(_) => ImportOutcome::Inserted,
+                    Err(e) => {
+                        warn!("Could not insert command {alias} because: {e:?}");
+                        ImportOutcome::Failed
+                    }
+                }
+            } else {
+                command_service.import_command(command).await
+            };
+            report.record(outcome);
         }
+
+        println!("Imported {file}: {report}");
     }
 
     Ok(())
 }
+
+/// Deletes the commands archived for longer than `archive_retention_days`, when it is set
+pub async fn purge_archived(args: &Args, config: &AppConfig) -> Result<()> {
+    let Some(days) = config.archive_retention_days else {
+        return Ok(());
+    };
+
+    let command_service = command_service(args, config).await?;
+    let purged = command_service
+        .purge_archived_older_than(days)
+        .await
+        .with_context(|| "Failed to purge the archived commands")?;
+    info!("Purged {purged} commands archived more than {days} days ago");
+    command_service.close().await;
+
+    Ok(())
+}
+
+/// Asks a yes/no question on the terminal, anything but an explicit yes is a no
+pub fn confirm(question: &str) -> Result<bool> {
+    print!("{question} [y/N] ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .with_context(|| "Failed to read the answer")?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// What to do with an imported command whose alias is already stored
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConflictResolution {
+    Skip,
+    Replace,
+    Rename(String),
+}
+
+/// The choice typed at the conflict prompt, `None` when it is none of them
+pub fn parse_conflict_choice(input: &str) -> Option<ConflictPolicy> {
+    match input.trim().to_lowercase().as_str() {
+        "s" | "skip" => Some(ConflictPolicy::Skip),
+        "r" | "replace" => Some(ConflictPolicy::Replace),
+        "n" | "rename" => Some(ConflictPolicy::Rename),
+        _ => None,
+    }
+}
+
+/// Asks whether to skip `incoming`, replace `existing` with it or store it under a new alias,
+/// until a valid answer is given
+pub fn resolve_conflict(existing: &Command, incoming: &Command) -> Result<ConflictResolution> {
+    println!("The alias {} is already taken:", existing.alias);
+    println!("  stored:   {}", existing.command);
+    println!("  imported: {}", incoming.command);
+
+    let choice = loop {
+        match parse_conflict_choice(&prompt("[s]kip, [r]eplace or re[n]ame")?) {
+            Some(choice) => break choice,
+            None => println!("Please answer s, r or n"),
+        }
+    };
+
+    Ok(match choice {
+        ConflictPolicy::Skip => ConflictResolution::Skip,
+        ConflictPolicy::Replace => ConflictResolution::Replace,
+        ConflictPolicy::Rename => loop {
+            let alias = prompt("New alias")?;
+            if !alias.trim().is_empty() {
+                break ConflictResolution::Rename(alias.trim().to_string());
+            }
+        },
+    })
+}
+
+/// Reads one line from the terminal after showing `label`
+pub fn prompt(label: &str) -> Result<String> {
+    print!("{label}: ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .with_context(|| "Failed to read the answer")?;
+
+    Ok(answer.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// The passphrase from `COMMAND_ORGANISER_PASSPHRASE`, or else typed on the terminal without
+/// being echoed. Esc or Ctrl+C gives up.
+pub fn read_passphrase() -> std::io::Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VAR) {
+        return Ok(passphrase);
+    }
+
+    eprint!("Passphrase for the sensitive commands: ");
+    std::io::stderr().flush()?;
+
+    enable_raw_mode()?;
+    let mut passphrase = String::new();
+    let typed = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Err(std::io::Error::new(
+                        std::io::ErrorKind::Interrupted,
+                        "The passphrase prompt was interrupted",
+                    ))
+                }
+                KeyCode::Esc => {
+                    break Err(std::io::Error::new(
+                        std::io::ErrorKind::Interrupted,
+                        "No passphrase was given",
+                    ))
+                }
+                KeyCode::Backspace => {
+                    passphrase.pop();
+                }
+                KeyCode::Char(c) => passphrase.push(c),
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    disable_raw_mode()?;
+    eprintln!();
+
+    typed.map(|()| passphrase)
+}
+
+/// Prompts for every positional argument in order and fills them into the command
+fn fill_positionals(command: &str) -> Result<String> {
+    let positionals = extract_positionals(command);
+    let count = positionals.last().copied().unwrap_or(0);
+
+    let values = (1..=count)
+        .map(|position| prompt(&format!("${position}")))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(substitute_positionals(command, &values))
+}
+
+/// The service of the db given with `--db-file`, locked: sensitive commands keep their
+/// encrypted text
+async fn command_service(args: &Args, config: &AppConfig) -> Result<CommandService> {
+    let mut command_service =
+        CommandService::with_options(&args.db_file, &config.storage_options())
+            .await
+            .with_context(|| "Failed to create the Command Service")?;
+    command_service.set_default_description_template(config.default_description_template.clone());
+
+    Ok(command_service)
+}
+
+/// The service of the db given with `--db-file`, unlocked when it holds sensitive commands.
+/// Only the commands reading or writing command strings ask for the passphrase.
+async fn unlocked_command_service(args: &Args, config: &AppConfig) -> Result<CommandService> {
+    let mut command_service = command_service(args, config).await?;
+    command_service
+        .unlock_when_needed(false, read_passphrase)
+        .await
+        .with_context(|| "Failed to unlock the sensitive commands")?;
+
+    Ok(command_service)
+}
+
+pub async fn run_command(command: &CliCommand, args: &Args, config: &AppConfig) -> Result<()> {
+    match command {
+        CliCommand::Serve => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+            serve::serve(&command_service, stdin, tokio::io::stdout()).await
+        }
+        CliCommand::List {
+            format,
+            output,
+            sort,
+            all,
+            executable_prefix,
+        } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let commands = match executable_prefix {
+                Some(prefix) => command_service
+                    .get_commands_by_executable_prefix(prefix)
+                    .await
+                    .map(|commands| {
+                        commands
+                            .into_iter()
+                            .filter(|command| *all || command.enabled)
+                            .collect()
+                    }),
+                None => command_service.get_all_commands_ordered(*sort, *all).await,
+            }
+            .with_context(|| "Failed to read the commands")?;
+
+            // Files never get escape codes, whatever the terminal supports
+            let colorize = output.is_none() && color::should_colorize(args.no_color);
+            list::write_commands(&commands, *format, colorize, output.as_deref())
+        }
+        CliCommand::Search {
+            query,
+            limit,
+            offset,
+            format,
+        } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let commands = command_service
+                .search_commands(query, *limit, *offset)
+                .await
+                .with_context(|| format!("Failed to search for {query}"))?;
+
+            let colorize = color::should_colorize(args.no_color);
+            list::write_commands(&commands, *format, colorize, None)
+        }
+        CliCommand::Add {
+            alias,
+            command,
+            stdin,
+            sensitive,
+            shell,
+        } => {
+            let mut commands = match (alias, command) {
+                (Some(alias), Some(command)) if !*stdin => vec![Command::new(
+                    normalized_executable(command)?,
+                    command.clone(),
+                    alias.clone(),
+                    None,
+                )],
+                _ => read_commands_from_reader(std::io::stdin().lock())?,
+            };
+
+            for command in &mut commands {
+                command.sensitive = *sensitive;
+                command.shell = shell.clone();
+                if let Err(e) = command.validate_executable() {
+                    warn!("Adding {} anyway, but {e}", command.alias);
+                }
+            }
+
+            let mut command_service = command_service(args, config).await?;
+            command_service
+                .unlock_when_needed(*sensitive, read_passphrase)
+                .await
+                .with_context(|| "Failed to unlock the sensitive commands")?;
+            let report = command_service.import_commands(commands).await;
+            println!("Added the commands: {report}");
+            Ok(())
+        }
+        CliCommand::Ensure {
+            alias,
+            command,
+            description,
+        } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let (stored, inserted) = command_service
+                .ensure_command(command, alias, description.clone())
+                .await
+                .with_context(|| format!("Failed to store {alias}"))?;
+
+            if inserted {
+                println!("Added {}", stored.alias);
+            } else {
+                println!("{} is already stored: {}", stored.alias, stored.command);
+            }
+            Ok(())
+        }
+        CliCommand::Watch { file } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            println!("Watching {file:?}, press Ctrl+C to stop");
+            watch::watch(&command_service, file).await
+        }
+        CliCommand::Export {
+            path,
+            format,
+            tag,
+            executable,
+        } => {
+            let path = path
+                .clone()
+                .unwrap_or_else(|| format.default_file_name().to_string());
+            let command_service = unlocked_command_service(args, config).await?;
+            let written = command_service
+                .export_matching(*format, &path, tag.as_deref(), executable.as_deref())
+                .await
+                .with_context(|| format!("Failed to export the commands to {path}"))?;
+            println!("Exported {written} commands to {path}");
+            Ok(())
+        }
+        CliCommand::Justfile { path } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            command_service
+                .export_justfile(path)
+                .await
+                .with_context(|| format!("Failed to export the commands to {path}"))?;
+            println!("Exported the commands to {path}");
+            Ok(())
+        }
+        CliCommand::Markdown { path } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            command_service
+                .export_markdown(path)
+                .await
+                .with_context(|| format!("Failed to export the commands to {path}"))?;
+            println!("Exported the commands to {path}");
+            Ok(())
+        }
+        CliCommand::Doctor => {
+            let colorize = color::should_colorize(args.no_color);
+            let results = doctor::run_checks(&args.db_file, &args.config, config).await;
+            for result in &results {
+                println!("{}", result.line(colorize));
+            }
+
+            let failed = results.iter().filter(|result| !result.passed).count();
+            if failed > 0 {
+                return Err(anyhow!("{failed} of {} checks failed", results.len()));
+            }
+            println!("Every check passed");
+            Ok(())
+        }
+        CliCommand::Stats => {
+            let command_service = command_service(args, config).await?;
+            let commands = command_service
+                .get_all_commands()
+                .await
+                .with_context(|| "Failed to read the commands")?;
+
+            let mut per_executable: BTreeMap<&str, usize> = BTreeMap::new();
+            for command in &commands {
+                *per_executable.entry(&command.executable).or_default() += 1;
+            }
+
+            let colorize = color::should_colorize(args.no_color);
+            println!("{}", crate::build_info());
+            println!("{} commands in {}", commands.len(), args.db_file);
+            for (executable, count) in per_executable {
+                println!(
+                    "  {}: {count}",
+                    color::paint(executable, Color::Cyan, colorize)
+                );
+            }
+            Ok(())
+        }
+        CliCommand::AutoTag { by_group } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let tagged = if *by_group {
+                command_service
+                    .auto_tag_by_group(&config.executable_groups)
+                    .await
+            } else {
+                command_service.auto_tag_by_executable().await
+            }
+            .with_context(|| "Failed to tag the commands")?;
+            println!("Tagged {tagged} commands");
+            Ok(())
+        }
+        CliCommand::Reclassify => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let changes = command_service
+                .reclassify_all()
+                .await
+                .with_context(|| "Failed to reclassify the commands")?;
+
+            let colorize = color::should_colorize(args.no_color);
+            for (alias, old, new) in &changes {
+                let alias = color::paint(alias, Color::Cyan, colorize);
+                println!("{alias}: {old} -> {new}");
+            }
+            println!("Reclassified {} commands", changes.len());
+            Ok(())
+        }
+        CliCommand::Backup { path } => {
+            let command_service = command_service(args, config).await?;
+            let backup = backup::backup(&command_service, &args.db_file, path.as_deref()).await?;
+            println!("Backed up {} to {}", args.db_file, backup.display());
+            Ok(())
+        }
+        CliCommand::Aliases { path } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let report = command_service
+                .import_shell_aliases(path)
+                .await
+                .with_context(|| format!("Failed to import {path:?}"))?;
+            println!("Imported {path:?}: {report}");
+            Ok(())
+        }
+        CliCommand::Cheat { path } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let imported = command_service
+                .import_from_cheat(path)
+                .await
+                .with_context(|| format!("Failed to import {path:?}"))?;
+            println!("Imported {imported} commands from {path:?}");
+            Ok(())
+        }
+        CliCommand::Vacuum => {
+            let command_service = command_service(args, config).await?;
+            let summary = command_service
+                .vacuum()
+                .await
+                .with_context(|| format!("Failed to vacuum {}", args.db_file))?;
+            println!(
+                "Vacuumed {}: {} bytes before, {} bytes after",
+                args.db_file, summary.before, summary.after
+            );
+            Ok(())
+        }
+        CliCommand::Archive { alias } => {
+            let command_service = command_service(args, config).await?;
+            command_service
+                .archive(alias)
+                .await
+                .with_context(|| format!("Failed to archive {alias}"))?;
+            println!("Archived {alias}");
+            Ok(())
+        }
+        CliCommand::Unarchive { alias } => {
+            let command_service = command_service(args, config).await?;
+            command_service
+                .unarchive(alias)
+                .await
+                .with_context(|| format!("Failed to take {alias} out of the archive"))?;
+            println!("Restored {alias} from the archive");
+            Ok(())
+        }
+        CliCommand::Merge { path, on_conflict } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let summary = command_service
+                .import_from_db(path, *on_conflict)
+                .await
+                .with_context(|| format!("Failed to merge {path}"))?;
+
+            println!(
+                "Merged {path}: {} added, {} replaced, {} renamed, {} skipped",
+                summary.inserted, summary.replaced, summary.renamed, summary.skipped
+            );
+            Ok(())
+        }
+        CliCommand::Expect {
+            alias,
+            exit_code,
+            environment,
+        } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let command = command_service
+                .get_by_alias_and_env(alias, environment.as_deref())
+                .await
+                .with_context(|| format!("Failed to find the command {alias}"))?;
+            command_service
+                .set_expected_exit(&command, *exit_code)
+                .await
+                .with_context(|| format!("Failed to set the expected exit code of {alias}"))?;
+
+            match exit_code {
+                Some(code) => println!("{alias} is expected to exit with {code}"),
+                None => println!("{alias} has no expected exit code anymore"),
+            }
+            Ok(())
+        }
+        CliCommand::Run {
+            alias,
+            environment,
+            timeout,
+        } => {
+            let command_service = unlocked_command_service(args, config).await?;
+            let command = command_service
+                .get_by_alias_and_env(alias, environment.as_deref())
+                .await
+                .with_context(|| format!("Failed to find the command {alias}"))?;
+
+            if let Err(e) = command_service.record_use(&command).await {
+                warn!("Failed to count the use of {alias}: {e}");
+            }
+
+            let expected_exit = command.expected_exit;
+            let shell = command.shell.clone();
+            let working_dir = resolve_working_dir(&command, config);
+            let (command, _) = expand_automatic(&command.command);
+            let command = fill_positionals(&command)?;
+            if is_dangerous(&command, &config.dangerous_patterns)
+                && !confirm(&format!("{command} looks dangerous, run it anyway?"))?
+            {
+                info!("Run cancelled");
+                return Ok(());
+            }
+            info!("Running: {command}");
+
+            let timeout = timeout.or(config.run_timeout_secs).map(Duration::from_secs);
+            let outcome = run_shell(&command, shell.as_deref(), working_dir.as_deref(), timeout)
+                .await
+                .with_context(|| format!("Failed to run {command}"))?;
+
+            // Commands run as checks are judged by their expected exit code instead
+            match check_exit(&outcome, expected_exit) {
+                Some(ExitCheck::Passed) => {
+                    println!(
+                        "PASS: {alias} exited with {}",
+                        expected_exit.unwrap_or_default()
+                    );
+                    return Ok(());
+                }
+                Some(ExitCheck::Failed { expected, actual }) => {
+                    let actual = actual.map_or("no exit code".to_string(), |code| code.to_string());
+                    println!("FAIL: {alias} was expected to exit with {expected} but got {actual}");
+                    return Err(anyhow!("{alias} did not exit with {expected}"));
+                }
+                None => {}
+            }
+
+            match outcome {
+                RunOutcome::Exited(status) if status.success() => Ok(()),
+                RunOutcome::Exited(status) => Err(anyhow!("{command} exited with {status}")),
+                RunOutcome::TimedOut => Err(anyhow!(
+                    "{command} timed out after {}s and was killed",
+                    timeout.unwrap_or_default().as_secs()
+                )),
+            }
+        }
+        CliCommand::Restore { path, yes } => {
+            let question = format!("Replace {} with the backup {path}?", args.db_file);
+            if !yes && !confirm(&question)? {
+                info!("Restore cancelled");
+                return Ok(());
+            }
+
+            backup::restore(&args.db_file, path).await?;
+            println!("Restored {} from {path}", args.db_file);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use serial_test::serial;
+
+    use std::io::Cursor;
+
+    use super::{
+        check_not_database, create_db_dir, default_db_file, is_sqlite_file, parse_commands,
+        parse_conflict_choice, preview_import, read_commands_from_file, read_commands_from_reader,
+        validate_import, Args, CliCommand, ImportPreview,
+    };
+    use crate::model::command::{Command, ValidationError, DEFAULT_MAX_ALIAS_LEN};
+    use crate::service::command_service::{CommandService, ConflictPolicy};
+
+    #[test]
+    fn test_executable_defaults() {
+        let commands = parse_commands(
+            r#"
+            [executable_defaults]
+            git = "Version control"
+
+            [[commands]]
+            command = "git pull"
+            executable = "git"
+            alias = "git_pull"
+
+            [[commands]]
+            command = "git push"
+            executable = "git"
+            alias = "git_push"
+            description = "Publish the commits"
+
+            [[commands]]
+            command = "ls -a"
+            executable = "ls"
+            alias = "ls_all"
+            "#,
+            DEFAULT_MAX_ALIAS_LEN,
+        )
+        .unwrap();
+
+        assert_eq!(commands[0].description.as_deref(), Some("Version control"));
+        assert_eq!(
+            commands[1].description.as_deref(),
+            Some("Publish the commits")
+        );
+        assert_eq!(commands[2].description, None);
+    }
+
+    #[test]
+    fn test_import_title() {
+        let commands = parse_commands(
+            r#"
+            [[commands]]
+            command = "git log --graph --oneline"
+            executable = "git"
+            alias = "glg"
+            title = "Commit graph"
+
+            [[commands]]
+            command = "git pull"
+            executable = "git"
+            alias = "git_pull"
+            "#,
+            DEFAULT_MAX_ALIAS_LEN,
+        )
+        .unwrap();
+
+        assert_eq!(commands[0].display_name(), "Commit graph");
+        assert_eq!(commands[1].display_name(), "git_pull");
+    }
+
+    #[test]
+    fn test_import_examples() {
+        let commands = parse_commands(
+            r#"
+            [[commands]]
+            command = "ssh {host}"
+            executable = "ssh"
+            alias = "ssh_host"
+            examples = ["ssh db1", "ssh -p 2222 db2"]
+
+            [[commands]]
+            command = "ls -a"
+            executable = "ls"
+            alias = "ls_all"
+            "#,
+            DEFAULT_MAX_ALIAS_LEN,
+        )
+        .unwrap();
+
+        assert_eq!(
+            commands[0].examples.iter().collect::<Vec<_>>(),
+            ["ssh db1", "ssh -p 2222 db2"]
+        );
+        assert!(commands[1].examples.is_empty());
+    }
+
+    #[test]
+    fn test_import_generates_missing_aliases() {
+        let commands = parse_commands(
+            r#"
+            [[commands]]
+            command = "git status"
+            alias = "git_status"
+
+            [[commands]]
+            command = "git status"
+
+            [[commands]]
+            command = "git status --short"
+            "#,
+            DEFAULT_MAX_ALIAS_LEN,
+        )
+        .unwrap();
+
+        let aliases: Vec<&str> = commands.iter().map(|c| c.alias.as_str()).collect();
+        assert_eq!(aliases, ["git_status", "git_status_2", "git_status_short"]);
+    }
+
+    #[test]
+    fn test_parse_conflict_choice() {
+        assert_eq!(parse_conflict_choice("s"), Some(ConflictPolicy::Skip));
+        assert_eq!(
+            parse_conflict_choice(" Replace\n"),
+            Some(ConflictPolicy::Replace)
+        );
+        assert_eq!(parse_conflict_choice("n"), Some(ConflictPolicy::Rename));
+        assert_eq!(
+            parse_conflict_choice("rename"),
+            Some(ConflictPolicy::Rename)
+        );
+        assert_eq!(parse_conflict_choice(""), None);
+        assert_eq!(parse_conflict_choice("yes"), None);
+    }
+
+    #[test]
+    fn test_without_executable_defaults() {
+        let commands = parse_commands(
+            &std::fs::read_to_string("commands.toml").unwrap(),
+            DEFAULT_MAX_ALIAS_LEN,
+        )
+        .unwrap();
+        assert!(!commands.is_empty());
+    }
+
+    #[test]
+    fn test_validate_import() {
+        let import = r#"
+            [[commands]]
+            command = "git pull"
+            executable = "git"
+            alias = "git_pull"
+
+            [[commands]]
+            "#;
+
+        let commands: Vec<_> = toml::from_str::<toml::Table>(import).unwrap()["commands"]
+            .clone()
+            .try_into()
+            .unwrap();
+        let errors = validate_import(&commands, DEFAULT_MAX_ALIAS_LEN).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].index, 2);
+        assert_eq!(
+            errors[0].source,
+            ValidationError::Empty { field: "command" }
+        );
+
+        let error = parse_commands(import, DEFAULT_MAX_ALIAS_LEN)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Entry 2"));
+    }
+
+    #[test]
+    fn test_commands_not_a_list() {
+        let error = parse_commands("commands = \"oops\"", DEFAULT_MAX_ALIAS_LEN)
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.contains("`commands` must be a list of tables but it is a string"));
+        assert!(error.contains("[[commands]]"));
+
+        let error = parse_commands(
+            "[executable_defaults]\ngit = \"Version control\"",
+            DEFAULT_MAX_ALIAS_LEN,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("has no `commands`"));
+    }
+
+    #[test]
+    fn test_search_arguments() {
+        let args = Args::parse_from([
+            "command_organiser",
+            "search",
+            "foo",
+            "--limit",
+            "2",
+            "--offset",
+            "1",
+        ]);
+        match args.command {
+            Some(CliCommand::Search {
+                query,
+                limit,
+                offset,
+                ..
+            }) => {
+                assert_eq!(query, "foo");
+                assert_eq!(limit, Some(2));
+                assert_eq!(offset, Some(1));
+            }
+            command => panic!("parsed {command:?}"),
+        }
+    }
+
+    #[test]
+    fn test_expect_arguments() {
+        let args = Args::parse_from(["command_organiser", "expect", "in_repo", "-1"]);
+        match args.command {
+            Some(CliCommand::Expect {
+                alias, exit_code, ..
+            }) => {
+                assert_eq!(alias, "in_repo");
+                assert_eq!(exit_code, Some(-1));
+            }
+            command => panic!("parsed {command:?}"),
+        }
+
+        let args = Args::parse_from(["command_organiser", "expect", "in_repo"]);
+        assert!(matches!(
+            args.command,
+            Some(CliCommand::Expect {
+                exit_code: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_read_commands_from_reader() {
+        let input = Cursor::new(
+            "git_pull\tgit pull --rebase\n\nnot a command\nls_all\t  ls -a\n\tgit push\n",
+        );
+
+        let commands = read_commands_from_reader(input).unwrap();
+        let parsed: Vec<(&str, &str, &str)> = commands
+            .iter()
+            .map(|c| (c.alias.as_str(), c.executable.as_str(), c.command.as_str()))
+            .collect();
+        assert_eq!(
+            parsed,
+            [
+                ("git_pull", "git", "git pull --rebase"),
+                ("ls_all", "ls", "ls -a")
+            ]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[serial]
+    fn test_default_db_file() {
+        let dir = std::env::temp_dir().join("command_organiser_xdg");
+        let _ = std::fs::remove_dir_all(&dir);
+        let previous = std::env::var_os("XDG_DATA_HOME");
+        std::env::set_var("XDG_DATA_HOME", &dir);
+
+        let db_file = default_db_file();
+        assert_eq!(db_file, dir.join("command_organiser").join("commands.db"));
+
+        create_db_dir(&db_file.display().to_string()).unwrap();
+        assert!(dir.join("command_organiser").is_dir());
+        create_db_dir("commands.db").unwrap();
+
+        let args = Args::try_parse_from(["command_organiser"]).unwrap();
+        assert_eq!(args.db_file, db_file.display().to_string());
+        let args = Args::try_parse_from(["command_organiser", "--db-file", "other.db"]).unwrap();
+        assert_eq!(args.db_file, "other.db");
+
+        match previous {
+            Some(previous) => std::env::set_var("XDG_DATA_HOME", previous),
+            None => std::env::remove_var("XDG_DATA_HOME"),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_file_is_the_database() {
+        let dir = std::env::temp_dir().join("command_organiser_same_path");
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("commands.db");
+        let import = dir.join("commands.toml");
+        std::fs::write(&db, "").unwrap();
+        std::fs::write(&import, "").unwrap();
+
+        let db_url = format!("sqlite://{}", db.display());
+        assert!(check_not_database(&db, &db_url).is_err());
+        assert!(check_not_database(&dir.join(".").join("commands.db"), &db_url).is_err());
+        assert!(check_not_database(&import, &db_url).is_ok());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_import_rejected() {
+        let dir = std::env::temp_dir().join("command_organiser_sqlite_magic");
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("other.db");
+        let short = dir.join("short.toml");
+        std::fs::write(&db, b"SQLite format 3\0\x10\x00").unwrap();
+        std::fs::write(&short, "x").unwrap();
+
+        assert!(is_sqlite_file(&db).unwrap());
+        assert!(!is_sqlite_file(&short).unwrap());
+        let err = read_commands_from_file(db.display().to_string(), DEFAULT_MAX_ALIAS_LEN)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("SQLite database"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_preview_import() {
+        let mut service = CommandService::new("test.sqlite").await.unwrap();
+        for (command, alias) in [("git status", "gs"), ("git pull", "gp"), ("ls -la", "ll")] {
+            service.insert_command(command, alias, None).await.unwrap();
+        }
+        service.unlock("hunter2").await.unwrap();
+        service
+            .add_command(Command {
+                sensitive: true,
+                ..Command::new(
+                    String::new(),
+                    "curl -u admin:secret https://api.example.com".to_string(),
+                    "api".to_string(),
+                    None,
+                )
+            })
+            .await
+            .unwrap();
+        let stored = service.get_all_commands().await.unwrap();
+
+        let commands = parse_commands(
+            r#"
+            [[commands]]
+            command = "git status"
+            executable = "git"
+            alias = "gs"
+
+            [[commands]]
+            command = "git pull --rebase"
+            executable = "git"
+            alias = "gp"
+
+            [[commands]]
+            command = "ls -la"
+            executable = "ls"
+            alias = "la"
+
+            [[commands]]
+            command = "git fetch"
+            executable = "git"
+            alias = "gf"
+
+            [[commands]]
+            command = "git fetch --all"
+            executable = "git"
+            alias = "gf"
+
+            [[commands]]
+            command = "curl -u admin:secret https://api.example.com"
+            executable = "curl"
+            alias = "admin"
+            sensitive = true
+
+            [[commands]]
+            command = "echo 'unterminated"
+            executable = "echo"
+            alias = "oops"
+            "#,
+            DEFAULT_MAX_ALIAS_LEN,
+        )
+        .unwrap();
+
+        // Without -i a changed command under a stored alias is a duplicate like the others, and
+        // the sensitive command is already stored sealed
+        let preview = preview_import(&service, &commands, false, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            preview,
+            ImportPreview {
+                inserted: 2,
+                asked: 0,
+                skipped: 5,
+                failed: 0,
+            }
+        );
+
+        let preview = preview_import(&service, &commands, true, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            preview,
+            ImportPreview {
+                inserted: 1,
+                asked: 3,
+                skipped: 2,
+                failed: 1,
+            }
+        );
+        assert_eq!(service.get_all_commands().await.unwrap(), stored);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::trace;
+
+use crate::model::command::Command;
+use crate::service::command_service::CommandService;
+
+/// A single request line, e.g. `{"op":"search","query":"git"}`
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    Search {
+        query: String,
+        /// Returns at most this many commands
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    Get {
+        alias: String,
+        #[serde(default)]
+        environment: Option<String>,
+        /// Only the row with both this command string and the alias, the environment is
+        /// then ignored
+        #[serde(default)]
+        command: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Commands {
+        commands: Vec<Command>,
+        /// Number of matches when the commands were cut short by a limit
+        #[serde(skip_serializing_if = "Option::is_none")]
+        total: Option<i64>,
+    },
+    Command {
+        command: Box<Command>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+pub async fn dispatch(command_service: &CommandService, request: Request) -> Response {
+    match request {
+        Request::Search { query, limit } => {
+            let searched = match command_service.search_commands(&query, limit, None).await {
+                Ok(commands) if limit.is_some() => command_service
+                    .count_search_matches(&query)
+                    .await
+                    .map(|total| (commands, Some(total))),
+                Ok(commands) => Ok((commands, None)),
+                Err(e) => Err(e),
+            };
+
+            match searched {
+                Ok((commands, total)) => Response::Commands { commands, total },
+                Err(e) => Response::Error {
+                    message: e.to_string(),
+                },
+            }
+        }
+        Request::Get {
+            alias,
+            environment,
+            command,
+        } => {
+            let command = match (command, environment) {
+                (Some(command), _) => command_service.get_command(&command, &alias).await,
+                (None, Some(environment)) => {
+                    command_service
+                        .get_by_alias_and_env(&alias, Some(&environment))
+                        .await
+                }
+                (None, None) => command_service.get_command_by_alias(&alias).await,
+            };
+
+            match command {
+                Ok(command) => Response::Command {
+                    command: Box::new(command),
+                },
+                Err(e) => Response::Error {
+                    message: e.to_string(),
+                },
+            }
+        }
+    }
+}
+
+/// Answers every newline-delimited JSON request from `reader` with one JSON line on `writer`
+/// until the reader is exhausted.
+pub async fn serve<R, W>(command_service: &CommandService, reader: R, mut writer: W) -> Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut lines = reader.lines();
+
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .with_context(|| "Failed to read the next request")?
+    {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        trace!("Serving request: {line}");
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => dispatch(command_service, request).await,
+            Err(e) => Response::Error {
+                message: format!("Invalid request: {e}"),
+            },
+        };
+
+        let mut response =
+            serde_json::to_string(&response).with_context(|| "Failed to serialise the response")?;
+        response.push('\n');
+
+        writer
+            .write_all(response.as_bytes())
+            .await
+            .with_context(|| "Failed to write the response")?;
+        writer.flush().await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use serial_test::serial;
+
+    use super::{serve, Request};
+    use crate::service::command_service::CommandService;
+
+    #[test]
+    fn test_request_parsing() {
+        let request: Request = serde_json::from_str(r#"{"op":"search","query":"git"}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::Search {
+                query: "git".to_string(),
+                limit: None,
+            }
+        );
+
+        let request: Request = serde_json::from_str(r#"{"op":"get","alias":"git_pull"}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::Get {
+                alias: "git_pull".to_string(),
+                environment: None,
+                command: None,
+            }
+        );
+
+        let request: Request =
+            serde_json::from_str(r#"{"op":"get","alias":"pods","environment":"prod"}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::Get {
+                alias: "pods".to_string(),
+                environment: Some("prod".to_string()),
+                command: None,
+            }
+        );
+
+        let request: Request =
+            serde_json::from_str(r#"{"op":"get","alias":"gp","command":"git pull"}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::Get {
+                alias: "gp".to_string(),
+                environment: None,
+                command: Some("git pull".to_string()),
+            }
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_serve_requests() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("ls -a", "ls_all", None)
+            .await
+            .unwrap();
+
+        let input = "{\"op\":\"search\",\"query\":\"git\"}\n\
+                     {\"op\":\"search\",\"query\":\"l\",\"limit\":1}\n\
+                     {\"op\":\"get\",\"alias\":\"ls_all\"}\n\
+                     {\"op\":\"get\",\"alias\":\"git_pull\",\"command\":\"git pull\"}\n\
+                     {\"op\":\"get\",\"alias\":\"ls_all\",\"command\":\"git pull\"}\n\
+                     not json\n";
+        let mut output = Vec::new();
+
+        serve(&service, input.as_bytes(), &mut output)
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let responses: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(responses.len(), 6);
+
+        assert_eq!(responses[0]["type"], "commands");
+        assert_eq!(responses[0]["commands"].as_array().unwrap().len(), 1);
+        assert_eq!(responses[0]["commands"][0]["alias"], "git_pull");
+        assert!(responses[0].get("total").is_none());
+
+        assert_eq!(responses[1]["commands"].as_array().unwrap().len(), 1);
+        assert_eq!(responses[1]["total"], 2);
+
+        assert_eq!(responses[2]["type"], "command");
+        assert_eq!(responses[2]["command"]["command"], "ls -a");
+
+        assert_eq!(responses[3]["command"]["alias"], "git_pull");
+        // A command string and alias of different rows are no match
+        assert_eq!(responses[4]["type"], "error");
+
+        assert_eq!(responses[5]["type"], "error");
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+}
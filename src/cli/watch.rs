@@ -0,0 +1,178 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use notify::{RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::model::command::Command;
+use crate::service::command_service::CommandService;
+
+/// Saves landing within this long of each other are imported once
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// How the commands of an import file changed between two reads
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportDiff {
+    pub added: Vec<Command>,
+    pub updated: Vec<Command>,
+    pub removed: Vec<Command>,
+}
+
+impl ImportDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A command is the same entry of the file as long as its alias, environment and os match
+pub fn same_entry(a: &Command, b: &Command) -> bool {
+    a.alias == b.alias && a.environment == b.environment && a.os == b.os
+}
+
+/// The entries of `new` missing from `old` or changed since, and those of `old` that are gone
+pub fn diff_imports(old: &[Command], new: &[Command]) -> ImportDiff {
+    let mut diff = ImportDiff::default();
+
+    for command in new {
+        match old.iter().find(|previous| same_entry(previous, command)) {
+            None => diff.added.push(command.clone()),
+            Some(previous) if previous != command => diff.updated.push(command.clone()),
+            Some(_) => {}
+        }
+    }
+
+    diff.removed = old
+        .iter()
+        .filter(|previous| !new.iter().any(|command| same_entry(previous, command)))
+        .cloned()
+        .collect();
+
+    diff
+}
+
+/// Brings the db in line with `diff`, logging every change. A failing entry does not stop the
+/// others from being applied.
+async fn apply_diff(command_service: &CommandService, diff: &ImportDiff) {
+    for command in &diff.removed {
+        match command_service.delete_variant(command).await {
+            Ok(()) => info!("Watch removed {}", command.alias),
+            Err(e) => warn!("Could not remove command {} because: {e:?}", command.alias),
+        }
+    }
+
+    let changes = diff
+        .added
+        .iter()
+        .map(|command| ("added", command))
+        .chain(diff.updated.iter().map(|command| ("updated", command)));
+
+    for (change, command) in changes {
+        match command_service.upsert_command(command.clone()).await {
+            Ok(_) => info!("Watch {change} {}", command.alias),
+            Err(e) => warn!("Could not store command {} because: {e:?}", command.alias),
+        }
+    }
+}
+
+/// Imports `file`, then imports it again after every change until interrupted
+pub async fn watch(command_service: &CommandService, file: &Path) -> Result<()> {
+    let file_name = file
+        .file_name()
+        .ok_or_else(|| anyhow!("{file:?} is not a file"))?
+        .to_owned();
+    // Editors often save by replacing the file, so its directory is watched instead
+    let dir = match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let _ = sender.send(event);
+        }
+    })
+    .with_context(|| "Failed to create the file watcher")?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {dir:?}"))?;
+
+    let mut imported = Vec::new();
+    loop {
+        let path = file.to_string_lossy().to_string();
+        match super::read_commands_from_file(path, command_service.max_alias_len()).await {
+            Ok(commands) => {
+                let diff = diff_imports(&imported, &commands);
+                if !diff.is_empty() {
+                    apply_diff(command_service, &diff).await;
+                    println!(
+                        "Imported {file:?}: {} added, {} updated, {} removed",
+                        diff.added.len(),
+                        diff.updated.len(),
+                        diff.removed.len()
+                    );
+                }
+                imported = commands;
+            }
+            Err(e) => warn!("Keeping the previous import of {file:?} because: {e:?}"),
+        }
+
+        // Wait for a change to the file, then for the saves to settle down
+        loop {
+            let event = receiver
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("Stopped watching {file:?}"))?;
+            if event
+                .paths
+                .iter()
+                .any(|path| path.file_name() == Some(&file_name))
+            {
+                break;
+            }
+        }
+        while let Ok(Some(_)) = tokio::time::timeout(DEBOUNCE, receiver.recv()).await {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff_imports;
+    use crate::model::command::Command;
+
+    fn command(command: &str, alias: &str) -> Command {
+        Command::new(
+            "git".to_string(),
+            command.to_string(),
+            alias.to_string(),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_diff_imports() {
+        let old = vec![
+            command("git status", "git_status"),
+            command("git pull", "git_pull"),
+            command("git push", "git_push"),
+        ];
+        let mut new = vec![
+            command("git status --short", "git_status"),
+            command("git pull", "git_pull"),
+            command("git fetch", "git_fetch"),
+        ];
+        new[1].environment = Some("work".to_string());
+
+        let diff = diff_imports(&old, &new);
+        let aliases = |commands: &[Command]| -> Vec<String> {
+            commands.iter().map(|c| c.alias.clone()).collect()
+        };
+
+        assert_eq!(aliases(&diff.added), ["git_pull", "git_fetch"]);
+        assert_eq!(aliases(&diff.updated), ["git_status"]);
+        assert_eq!(aliases(&diff.removed), ["git_pull", "git_push"]);
+        assert!(diff_imports(&new, &new).is_empty());
+    }
+}
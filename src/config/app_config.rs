@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::info;
+
+use super::keymap::KeyMap;
+use crate::model::command::DEFAULT_MAX_ALIAS_LEN;
+use crate::model::danger::DEFAULT_DANGEROUS_PATTERNS;
+use crate::model::group::default_executable_groups;
+use crate::service::export::ExportFormat;
+use crate::storage::command_storage::{OrderBy, StorageOptions};
+use crate::ui::clipboard::ClipboardProviderKind;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read the config file: {0}")]
+    Read(#[from] std::io::Error),
+
+    #[error("Failed to parse the config file: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+/// User settings, every missing key falls back to its default
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Close the TUI once a command was copied to the clipboard. The preview of the copied
+    /// command is only shown when the TUI stays open.
+    pub exit_on_copy: bool,
+    /// Unix permissions of a newly created db file, e.g. `0o600`
+    pub db_file_mode: u32,
+    /// Seconds after which a command started with `run` is killed, no limit when absent
+    pub run_timeout_secs: Option<u64>,
+    /// Where copied commands go: `auto`, `system`, `osc52` or `file`
+    pub clipboard: ClipboardProviderKind,
+    /// The file copied commands are written to with the `file` clipboard
+    pub clipboard_file: Option<String>,
+    /// Keys of the TUI, checked for conflicts when the config is loaded
+    pub keybindings: KeyMap,
+    /// Commands containing one of these need a confirmation before being copied or run
+    pub dangerous_patterns: Vec<String>,
+    /// Group of each executable when the tabs are grouped that way, e.g. `git = "vcs"`
+    pub executable_groups: HashMap<String, String>,
+    /// Order of the commands within a tab, pinned ones still come first
+    pub sort: OrderBy,
+    /// Days an archived command is kept before being deleted on startup, forever when absent
+    pub archive_retention_days: Option<i64>,
+    /// Format the TUI exports the visible commands in: `toml`, `json`, `justfile` or
+    /// `markdown`
+    pub export_format: ExportFormat,
+    /// Description given to commands added without one, e.g. `"Runs {executable}"`. `{alias}`
+    /// and `{executable}` are replaced by those of the command.
+    pub default_description_template: Option<String>,
+    /// Down on the last command selects the first one and Up on the first the last, when set
+    pub wrap_navigation: bool,
+    /// End copied commands with a newline, so pasting them in a terminal runs them
+    pub copy_trailing_newline: bool,
+    /// Directory the commands of each executable are run in when they have none of their own,
+    /// e.g. `cargo = "/home/me/projects"`
+    pub working_dirs: HashMap<String, String>,
+    /// Longest alias a command can have. Raising it widens the alias column of an existing db.
+    pub max_alias_len: usize,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            exit_on_copy: true,
+            db_file_mode: 0o600,
+            run_timeout_secs: None,
+            clipboard: ClipboardProviderKind::default(),
+            clipboard_file: None,
+            keybindings: KeyMap::default(),
+            dangerous_patterns: DEFAULT_DANGEROUS_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            executable_groups: default_executable_groups(),
+            sort: OrderBy::default(),
+            archive_retention_days: None,
+            export_format: ExportFormat::default(),
+            default_description_template: None,
+            wrap_navigation: true,
+            copy_trailing_newline: false,
+            working_dirs: HashMap::new(),
+            max_alias_len: DEFAULT_MAX_ALIAS_LEN,
+        }
+    }
+}
+
+impl AppConfig {
+    /// Loads the config from `path`, a missing file yields the default config
+    pub fn load(path: &str) -> Result<AppConfig, ConfigError> {
+        if !Path::new(path).is_file() {
+            info!("No config file at {path}, using the defaults");
+            return Ok(AppConfig::default());
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        AppConfig::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<AppConfig, ConfigError> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    pub fn storage_options(&self) -> StorageOptions {
+        StorageOptions {
+            file_mode: Some(self.db_file_mode),
+            max_alias_len: self.max_alias_len,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppConfig;
+    use crate::storage::command_storage::OrderBy;
+    use crate::ui::clipboard::ClipboardProviderKind;
+
+    #[test]
+    fn test_defaults() {
+        let config = AppConfig::parse("").unwrap();
+        assert_eq!(config, AppConfig::default());
+        assert!(config.exit_on_copy);
+
+        let config = AppConfig::load("does_not_exist.toml").unwrap();
+        assert_eq!(config, AppConfig::default());
+    }
+
+    #[test]
+    fn test_parse() {
+        let config =
+            AppConfig::parse("exit_on_copy = false\ndb_file_mode = 0o640\nrun_timeout_secs = 30")
+                .unwrap();
+        assert!(!config.exit_on_copy);
+        assert_eq!(config.run_timeout_secs, Some(30));
+        assert_eq!(config.storage_options().file_mode, Some(0o640));
+
+        let config = AppConfig::parse("[executable_groups]\nls = \"files\"").unwrap();
+        assert_eq!(config.executable_groups.len(), 1);
+        assert_eq!(config.executable_groups["ls"], "files");
+
+        let config = AppConfig::parse("sort = \"created-at\"").unwrap();
+        assert_eq!(config.sort, OrderBy::CreatedAt);
+
+        let config = AppConfig::parse("clipboard = \"osc52\"").unwrap();
+        assert_eq!(config.clipboard, ClipboardProviderKind::Osc52);
+
+        assert!(AppConfig::parse("exit_on_copy = \"nope\"").is_err());
+    }
+}
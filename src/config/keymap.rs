@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use crossterm::event::KeyCode;
+use serde::Deserialize;
+
+use crate::ui::action::Action;
+
+/// Actions that can be bound in the `[keybindings]` table, with their default keys
+const BINDABLE: &[(&str, Action, &[&str])] = &[
+    ("quit", Action::Quit, &["q"]),
+    ("next_tab", Action::NextTab, &["right"]),
+    ("previous_tab", Action::PreviousTab, &["left"]),
+    ("next_command", Action::NextCommand, &["down"]),
+    ("previous_command", Action::PreviousCommand, &["up"]),
+    ("jump_to_top", Action::JumpToTop, &["home"]),
+    ("cycle_group", Action::CycleGroup, &["g"]),
+    ("jump_to_tab", Action::StartJump, &["'"]),
+    ("start_search", Action::StartSearch, &["G"]),
+    ("scroll_down", Action::ScrollDown, &["pagedown"]),
+    ("scroll_up", Action::ScrollUp, &["pageup"]),
+    ("copy", Action::Copy, &["enter"]),
+    ("copy_with_sudo", Action::CopyWithSudo, &["S"]),
+    ("copy_resolved", Action::CopyResolved, &["P"]),
+    ("delete", Action::Delete, &["d"]),
+    ("undo", Action::Undo, &["u"]),
+    ("toggle_pin", Action::TogglePin, &["p"]),
+    ("toggle_select", Action::ToggleSelect, &["space"]),
+    ("tag", Action::StartTagging, &["t"]),
+    ("rename", Action::StartRename, &["R"]),
+    ("edit_description", Action::EditDescription, &["e"]),
+    ("duplicate", Action::Duplicate, &["D"]),
+    ("edit_in_editor", Action::EditInEditor, &["E"]),
+    ("cycle_label", Action::CycleLabel, &["c"]),
+    ("toggle_examples", Action::ToggleExamples, &["x"]),
+    ("toggle_list_display", Action::ToggleListDisplay, &["tab"]),
+    ("run", Action::Run, &["r"]),
+    ("append_output", Action::AppendOutput, &["o"]),
+    ("export_view", Action::StartExport, &["X"]),
+    ("toggle_enabled", Action::ToggleEnabled, &["H"]),
+    ("toggle_show_disabled", Action::ToggleShowDisabled, &["V"]),
+];
+
+/// One key or several keys bound to the same action, e.g. `copy = ["enter", "y"]`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Keys {
+    fn names(&self) -> Vec<&str> {
+        match self {
+            Keys::One(key) => vec![key.as_str()],
+            Keys::Many(keys) => keys.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// Parses a key as written in the config: a single character or a named key like `pagedown`
+pub fn parse_key(key: &str) -> Option<KeyCode> {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+
+    let code = match key.to_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "space" => KeyCode::Char(' '),
+        _ => return None,
+    };
+
+    Some(code)
+}
+
+/// The keys of the normal mode, the defaults overridden per action by the `[keybindings]` table
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "HashMap<String, Keys>")]
+pub struct KeyMap {
+    bindings: HashMap<KeyCode, Action>,
+}
+
+impl KeyMap {
+    pub fn action(&self, key: KeyCode) -> Action {
+        self.bindings.get(&key).copied().unwrap_or(Action::None)
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap::try_from(HashMap::new()).expect("the default keybindings conflict")
+    }
+}
+
+impl TryFrom<HashMap<String, Keys>> for KeyMap {
+    type Error = String;
+
+    fn try_from(overrides: HashMap<String, Keys>) -> Result<Self, Self::Error> {
+        if let Some(unknown) = overrides
+            .keys()
+            .find(|name| !BINDABLE.iter().any(|(bindable, ..)| bindable == name))
+        {
+            return Err(format!("Unknown action in the keybindings: {unknown}"));
+        }
+
+        let mut bindings: HashMap<KeyCode, Action> = HashMap::new();
+        let mut bound_by: HashMap<KeyCode, &str> = HashMap::new();
+
+        for (name, action, defaults) in BINDABLE {
+            let keys = match overrides.get(*name) {
+                Some(keys) => keys.names(),
+                None => defaults.to_vec(),
+            };
+
+            for key in keys {
+                let code = parse_key(key).ok_or_else(|| format!("Unknown key {key} for {name}"))?;
+                if let Some(other) = bound_by.insert(code, name) {
+                    return Err(format!("The key {key} is bound to both {other} and {name}"));
+                }
+                bindings.insert(code, *action);
+            }
+        }
+
+        Ok(KeyMap { bindings })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyCode;
+
+    use super::KeyMap;
+    use crate::config::app_config::AppConfig;
+    use crate::ui::action::Action;
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = KeyMap::default();
+        assert_eq!(keymap.action(KeyCode::Down), Action::NextCommand);
+        assert_eq!(keymap.action(KeyCode::Enter), Action::Copy);
+        assert_eq!(keymap.action(KeyCode::Char('j')), Action::None);
+    }
+
+    #[test]
+    fn test_vim_keymap() {
+        let config = AppConfig::parse(
+            "[keybindings]\n\
+             next_command = [\"j\", \"down\"]\n\
+             previous_command = \"k\"\n\
+             next_tab = \"l\"\n\
+             previous_tab = \"h\"\n",
+        )
+        .unwrap();
+        let keymap = config.keybindings;
+
+        assert_eq!(keymap.action(KeyCode::Char('j')), Action::NextCommand);
+        assert_eq!(keymap.action(KeyCode::Down), Action::NextCommand);
+        assert_eq!(keymap.action(KeyCode::Char('k')), Action::PreviousCommand);
+        assert_eq!(keymap.action(KeyCode::Up), Action::None);
+        assert_eq!(keymap.action(KeyCode::Char('l')), Action::NextTab);
+        assert_eq!(keymap.action(KeyCode::Char('h')), Action::PreviousTab);
+        assert_eq!(keymap.action(KeyCode::Char('q')), Action::Quit);
+    }
+
+    #[test]
+    fn test_conflicting_keymap() {
+        let res = AppConfig::parse("[keybindings]\nquit = \"d\"\n");
+        let error = res.unwrap_err().to_string();
+        assert!(error.contains("bound to both"), "{error}");
+
+        assert!(AppConfig::parse("[keybindings]\nfly = \"f\"\n").is_err());
+        assert!(AppConfig::parse("[keybindings]\nquit = \"ctrl-q\"\n").is_err());
+    }
+}
@@ -1,15 +1,24 @@
 mod cli;
+mod config;
 mod model;
 mod service;
 mod storage;
 mod ui;
 
+use clap::Parser;
+use config::app_config::AppConfig;
 use tracing::{error, info, Level};
 use tracing_subscriber::FmtSubscriber;
 
+/// Name and version of this build, e.g. `command_organiser 0.1.0`
+pub fn build_info() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
 fn set_tracing() {
     let subscriber = FmtSubscriber::builder()
         .with_max_level(Level::DEBUG)
+        .with_writer(std::io::stderr)
         .finish();
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 }
@@ -20,21 +29,66 @@ async fn main() {
 
     info!("Starting the command organiser...");
 
-    let populated = cli::populate_db().await;
+    let args = cli::Args::parse();
+    if let Err(e) = cli::create_db_dir(&args.db_file) {
+        error!("{e:?}");
+    }
+
+    let config = AppConfig::load(&args.config).unwrap_or_else(|e| {
+        error!("Failed to load the config, using the defaults: {e}");
+        AppConfig::default()
+    });
+
+    if let Some(command) = &args.command {
+        if let Err(e) = cli::run_command(command, &args, &config).await {
+            error!("Failed to run the command: {e:?}");
+        }
+        return;
+    }
+
+    let populated = cli::populate_db(&args, &config).await;
 
     if let Err(e) = populated {
         error!("Failed to populate the db from file: {e}");
     }
 
-    let _ = ui::tui::run_terminal().await;
+    if let Err(e) = cli::purge_archived(&args, &config).await {
+        error!("{e:?}");
+    }
+
+    if let Err(e) = ui::tui::run_terminal(&args.db_file, config.clone()).await {
+        error!("Failed to start the terminal ui: {e}");
+
+        let corrupt = e
+            .downcast_ref::<ui::app::ApplicationError>()
+            .is_some_and(|e| e.is_corrupt_database());
+        if !corrupt {
+            return;
+        }
+
+        match cli::backup::offer_restore(&args.db_file).await {
+            Ok(true) => {
+                let _ = ui::tui::run_terminal(&args.db_file, config).await;
+            }
+            Ok(false) => {}
+            Err(e) => error!("Failed to restore the database: {e:?}"),
+        }
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
 
+    use super::build_info;
     use crate::model::command::Command;
     use crate::storage::command_storage::CommandStorageManager;
 
+    #[test]
+    fn test_build_info() {
+        assert!(build_info().contains(env!("CARGO_PKG_VERSION")));
+        assert!(build_info().starts_with("command_organiser "));
+    }
+
     #[tokio::test]
     #[ignore]
     async fn populate_db() {
@@ -45,6 +99,7 @@ pub mod tests {
             command: "git pull".to_string(),
             alias: "git_pull".to_string(),
             description: Some("Just a git pull".to_string()),
+            ..Default::default()
         };
 
         let command2 = Command {
@@ -52,6 +107,7 @@ pub mod tests {
             command: "ssh --version".to_string(),
             alias: "ssh_version".to_string(),
             description: Some("Just a ssh version".to_string()),
+            ..Default::default()
         };
 
         let command3 = Command {
@@ -59,6 +115,7 @@ pub mod tests {
             command: "ls .".to_string(),
             alias: "ls_current".to_string(),
             description: Some("Just a ls".to_string()),
+            ..Default::default()
         };
 
         let command4 = Command {
@@ -66,18 +123,21 @@ pub mod tests {
             command: "ls -a".to_string(),
             alias: "ls_all".to_string(),
             description: Some("Just a ls all".to_string()),
+            ..Default::default()
         };
         let command5 = Command {
             executable: "ls".to_string(),
             command: "ls ..".to_string(),
             alias: "ls_previous".to_string(),
             description: Some("Just a ls previous".to_string()),
+            ..Default::default()
         };
         let command6 = Command {
             executable: "ls".to_string(),
             command: "ls --version".to_string(),
             alias: "ls_version".to_string(),
             description: Some("Just a ls version".to_string()),
+            ..Default::default()
         };
 
         manager.insert_command(command1.clone()).await.unwrap();
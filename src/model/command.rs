@@ -1,14 +1,447 @@
-use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use thiserror::Error;
 
 // todo: introduce alias for commands
 
-#[derive(Clone, FromRow, Debug, Deserialize)]
+/// Tags attached to a command, stored as a comma separated list in the db
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Tags(pub Vec<String>);
+
+impl Tags {
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for Tags {
+    fn from(value: String) -> Self {
+        Tags(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for Tags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join(","))
+    }
+}
+
+/// Sample invocations of a command with its placeholders filled in, stored as a JSON array in
+/// the db
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Examples(pub Vec<String>);
+
+impl Examples {
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The JSON array stored in the db
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.0).unwrap_or_else(|_| "[]".to_string())
+    }
+}
+
+impl From<String> for Examples {
+    fn from(value: String) -> Self {
+        Examples(serde_json::from_str(&value).unwrap_or_default())
+    }
+}
+
+/// Color label shown as a bullet before the alias, stored by name in the db
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum Label {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl Label {
+    pub const ALL: [Label; 5] = [
+        Label::Red,
+        Label::Yellow,
+        Label::Green,
+        Label::Blue,
+        Label::Purple,
+    ];
+
+    /// The label following `label` when cycling through them, no label after the last one
+    pub fn cycle(label: Option<Label>) -> Option<Label> {
+        match label {
+            None => Some(Label::ALL[0]),
+            Some(label) => {
+                let index = Label::ALL
+                    .iter()
+                    .position(|l| *l == label)
+                    .unwrap_or_default();
+                Label::ALL.get(index + 1).copied()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SyntaxError {
+    #[error("Failed to run the shell to check the command: {0}")]
+    Shell(#[from] std::io::Error),
+
+    #[error("The command is not valid shell syntax: {0}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ExecutableError {
+    #[error("{0} is neither a shell builtin nor an executable on the PATH")]
+    NotFound(String),
+}
+
+/// Commands of `sh` and `bash` that run inside the shell, so they are never found on the PATH
+const SHELL_BUILTINS: &[&str] = &[
+    ".", ":", "[", "alias", "bg", "bind", "break", "builtin", "cd", "command", "continue",
+    "declare", "dirs", "echo", "eval", "exec", "exit", "export", "false", "fc", "fg", "getopts",
+    "hash", "help", "history", "jobs", "kill", "let", "local", "popd", "printf", "pushd", "pwd",
+    "read", "readonly", "return", "set", "shift", "source", "test", "times", "trap", "true",
+    "type", "typeset", "ulimit", "umask", "unalias", "unset", "wait",
+];
+
+/// True when `name` is run by the shell itself rather than found on the PATH, e.g. `cd`
+pub fn is_shell_builtin(name: &str) -> bool {
+    SHELL_BUILTINS.contains(&name)
+}
+
+/// True when `text` is a `YYYY-MM-DD` date, which sort in chronological order as strings
+pub fn is_iso_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    let digits = |range: std::ops::Range<usize>| -> Option<u32> {
+        let part = text.get(range)?;
+        part.bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| part.parse().ok())?
+    };
+
+    bytes.len() == 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && digits(0..4).is_some()
+        && digits(5..7).is_some_and(|month| (1..=12).contains(&month))
+        && digits(8..10).is_some_and(|day| (1..=31).contains(&day))
+}
+
+/// Reads a date written either as a string or, in TOML, as a bare local date
+fn deserialize_date<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Date {
+        Text(String),
+        Toml(toml::value::Datetime),
+    }
+
+    Ok(
+        Option::<Date>::deserialize(deserializer)?.map(|date| match date {
+            Date::Text(text) => text,
+            Date::Toml(date) => date.to_string(),
+        }),
+    )
+}
+
+/// True when `path` is a file the current user may execute
+fn is_executable_file(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+/// Absolute path the shell runs for `name`: the file itself when `name` is a path, otherwise the
+/// first executable of that name in the `PATH` directories. Shell builtins have none.
+pub fn resolve_executable(name: &str) -> Option<PathBuf> {
+    if is_shell_builtin(name) {
+        return None;
+    }
+
+    if name.contains('/') {
+        let path = Path::new(name);
+        return is_executable_file(path)
+            .then(|| path.canonicalize().ok())
+            .flatten();
+    }
+
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable_file(candidate))
+}
+
+/// Column widths of the commands table, the alias one unless the config sets `max_alias_len`
+pub const DEFAULT_MAX_ALIAS_LEN: usize = 64;
+pub const MAX_EXECUTABLE_LEN: usize = 50;
+pub const MAX_DESCRIPTION_LEN: usize = 300;
+
+/// Generated aliases are kept this short, which fits any alias column
+pub const GENERATED_ALIAS_LEN: usize = 20;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("The {field} can not be empty")]
+    Empty { field: &'static str },
+
+    #[error("The {field} is {len} characters long, at most {max} are allowed")]
+    TooLong {
+        field: &'static str,
+        len: usize,
+        max: usize,
+    },
+
+    #[error("The {field} {value} is not a date written as YYYY-MM-DD")]
+    NotADate { field: &'static str, value: String },
+}
+
+impl ValidationError {
+    /// The field that failed the validation, e.g. `description`
+    pub fn field(&self) -> &'static str {
+        match self {
+            ValidationError::Empty { field }
+            | ValidationError::TooLong { field, .. }
+            | ValidationError::NotADate { field, .. } => field,
+        }
+    }
+}
+
+/// Missing fields deserialise to their defaults so an import reports every incomplete entry
+/// through `validate_import` instead of stopping at the first one
+#[derive(Clone, FromRow, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
 pub struct Command {
     pub alias: String,
     pub executable: String,
     pub command: String,
     pub description: Option<String>,
+    #[sqlx(try_from = "String")]
+    #[serde(default)]
+    pub tags: Tags,
+    #[serde(default)]
+    pub favorite: bool,
+    /// Distinguishes variants of the same alias, e.g. `prod` and `staging`
+    #[serde(default)]
+    pub environment: Option<String>,
+    /// Listed ahead of the other commands of its tab
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<Label>,
+    /// Exit code the command is expected to end with when run as a check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_exit: Option<i32>,
+    /// The operating system the variant is for, as named by `std::env::consts::OS`, e.g.
+    /// `linux` or `macos`. Variants without one are used on every other system.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    #[sqlx(try_from = "String")]
+    #[serde(default, skip_serializing_if = "Examples::is_empty")]
+    pub examples: Examples,
+    /// Disabled commands are hidden without being deleted
+    #[serde(skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+    /// Sensitive commands are stored encrypted, e.g. those holding a token
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sensitive: bool,
+    /// Shell the command is run with, e.g. `bash` or `fish`, `sh` when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// The last day the command is of use, as `YYYY-MM-DD`, e.g. for temporary infrastructure.
+    /// It is flagged as stale afterwards.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_date",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires_at: Option<String>,
+    /// Name the command is listed under instead of its alias, which stays the key it is
+    /// looked up by
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Directory the command is run in, the current one when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Command {
+            alias: String::new(),
+            executable: String::new(),
+            command: String::new(),
+            description: None,
+            tags: Tags::default(),
+            favorite: false,
+            environment: None,
+            pinned: false,
+            label: None,
+            expected_exit: None,
+            os: None,
+            examples: Examples::default(),
+            enabled: true,
+            sensitive: false,
+            shell: None,
+            expires_at: None,
+            title: None,
+            working_dir: None,
+        }
+    }
+}
+
+/// The variant meant for `os`, or else the one without an operating system
+pub fn select_for_os<'a>(variants: &'a [Command], os: &str) -> Option<&'a Command> {
+    variants
+        .iter()
+        .find(|variant| variant.os.as_deref() == Some(os))
+        .or_else(|| variants.iter().find(|variant| variant.os.is_none()))
+}
+
+/// An alias made of the words of `command`, e.g. `git_log_oneline` for `git log --oneline`,
+/// suffixed with `_2`, `_3`, ... when `taken` already has it
+pub fn generate_unique_alias(command: &str, taken: &HashSet<String>) -> String {
+    let words: Vec<String> = command
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect();
+    let base: String = match words.join("_") {
+        joined if joined.is_empty() => "command".to_string(),
+        joined => joined
+            .chars()
+            .take(GENERATED_ALIAS_LEN)
+            .collect::<String>()
+            .trim_end_matches('_')
+            .to_string(),
+    };
+
+    if !taken.contains(&base) {
+        return base;
+    }
+
+    (2..)
+        .map(|n| {
+            let suffix = format!("_{n}");
+            let shortened: String = base
+                .chars()
+                .take(GENERATED_ALIAS_LEN - suffix.len())
+                .collect();
+            format!("{}{suffix}", shortened.trim_end_matches('_'))
+        })
+        .find(|alias| !taken.contains(alias))
+        .unwrap_or_default()
+}
+
+/// `command` with its `@alias` references pointing to `new_alias` instead. Only whole aliases
+/// count, so renaming `build` leaves `@builder` and `me@build.host` alone.
+pub fn rewrite_references(command: &str, alias: &str, new_alias: &str) -> String {
+    let is_alias_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let reference = format!("@{alias}");
+    let mut rewritten = String::with_capacity(command.len());
+    let mut rest = command;
+
+    while let Some(start) = rest.find(&reference) {
+        let end = start + reference.len();
+        let before = rest[..start].chars().next_back();
+        let after = rest[end..].chars().next();
+        rewritten.push_str(&rest[..start]);
+        if before.is_some_and(is_alias_char) || after.is_some_and(is_alias_char) {
+            rewritten.push_str(&reference);
+        } else {
+            rewritten.push('@');
+            rewritten.push_str(new_alias);
+        }
+        rest = &rest[end..];
+    }
+    rewritten.push_str(rest);
+
+    rewritten
+}
+
+/// Removes the `# comment` ending each line of `command`. Like the shell, a `#` only starts a
+/// comment at the beginning of a word and outside quotes, so `echo "# x"` and `a#b` are kept.
+pub fn strip_trailing_comment(command: &str) -> String {
+    let mut stripped = String::with_capacity(command.len());
+    let (mut single, mut double, mut escaped, mut comment) = (false, false, false, false);
+
+    for c in command.chars() {
+        if comment {
+            if c == '\n' {
+                comment = false;
+                stripped.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if !single => escaped = true,
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            '#' if !single
+                && !double
+                && stripped.chars().next_back().is_none_or(char::is_whitespace) =>
+            {
+                comment = true;
+                let kept = stripped.trim_end_matches([' ', '\t']).len();
+                stripped.truncate(kept);
+                continue;
+            }
+            _ => {}
+        }
+        stripped.push(c);
+    }
+
+    stripped.trim_end().to_string()
 }
 
 impl Command {
@@ -25,6 +458,405 @@ impl Command {
             executable,
             command,
             description,
+            ..Default::default()
+        }
+    }
+
+    /// The description as shown and exported, with every `{command}` replaced by the command
+    pub fn rendered_description(&self) -> Option<String> {
+        self.description
+            .as_ref()
+            .map(|description| description.replace("{command}", &self.command))
+    }
+
+    /// Checks the fields fit the columns of the commands table, whose alias column holds
+    /// `max_alias_len` characters
+    pub fn validate(&self, max_alias_len: usize) -> Result<(), ValidationError> {
+        let required = [("command", &self.command), ("alias", &self.alias)];
+        if let Some((field, _)) = required.iter().find(|(_, value)| value.trim().is_empty()) {
+            return Err(ValidationError::Empty { field });
+        }
+
+        let limited = [
+            ("alias", Some(&self.alias), max_alias_len),
+            ("executable", Some(&self.executable), MAX_EXECUTABLE_LEN),
+            (
+                "description",
+                self.description.as_ref(),
+                MAX_DESCRIPTION_LEN,
+            ),
+        ];
+        for (field, value, max) in limited {
+            let len = value.map_or(0, |value| value.chars().count());
+            if len > max {
+                return Err(ValidationError::TooLong { field, len, max });
+            }
+        }
+
+        if let Some(expires_at) = self.expires_at.as_ref().filter(|date| !is_iso_date(date)) {
+            return Err(ValidationError::NotADate {
+                field: "expires_at",
+                value: expires_at.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The title of the command, or its alias when it has none
+    pub fn display_name(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.alias)
+    }
+
+    /// True when `today`, a `YYYY-MM-DD` date, is past the expiry of the command. Commands
+    /// without one never expire.
+    pub fn is_expired(&self, today: &str) -> bool {
+        self.expires_at
+            .as_deref()
+            .is_some_and(|expires_at| today > expires_at)
+    }
+
+    /// Checks the executable can be run: a shell builtin, a path to an executable file or an
+    /// executable found in one of the `PATH` directories
+    pub fn validate_executable(&self) -> Result<(), ExecutableError> {
+        if is_shell_builtin(&self.executable) || self.resolved_path().is_some() {
+            Ok(())
+        } else {
+            Err(ExecutableError::NotFound(self.executable.clone()))
         }
     }
+
+    /// Absolute path of the executable, `None` for shell builtins and missing executables
+    pub fn resolved_path(&self) -> Option<PathBuf> {
+        resolve_executable(&self.executable)
+    }
+
+    /// The command string with the word naming the executable replaced by the path `resolve`
+    /// finds for the command, e.g. `/usr/bin/git pull`. The arguments are left untouched.
+    pub fn with_resolved_executable(
+        &self,
+        resolve: impl Fn(&Command) -> Option<PathBuf>,
+    ) -> Option<String> {
+        let mut start = 0;
+        for word in self.command.split_whitespace() {
+            start += self.command[start..].find(word)?;
+            if word.eq_ignore_ascii_case(&self.executable) {
+                let path = resolve(self)?;
+                return Some(format!(
+                    "{}{}{}",
+                    &self.command[..start],
+                    path.display(),
+                    &self.command[start + word.len()..]
+                ));
+            }
+            start += word.len();
+        }
+
+        None
+    }
+
+    /// Lets `sh -n` parse the command without executing it to catch syntax errors such as
+    /// unbalanced quotes
+    pub fn syntax_check(&self) -> Result<(), SyntaxError> {
+        let output = std::process::Command::new("sh")
+            .arg("-n")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::null())
+            .output()?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(SyntaxError::Invalid(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{
+        generate_unique_alias, is_shell_builtin, rewrite_references, select_for_os,
+        strip_trailing_comment, Command, Examples, ExecutableError, Label, SyntaxError,
+        ValidationError, DEFAULT_MAX_ALIAS_LEN, GENERATED_ALIAS_LEN, MAX_DESCRIPTION_LEN,
+    };
+
+    fn command(command: &str) -> Command {
+        Command::new(
+            "echo".to_string(),
+            command.to_string(),
+            "test".to_string(),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_syntax_check_valid() {
+        assert!(command("echo \"hello\" | grep -c h").syntax_check().is_ok());
+        assert!(command("for i in 1 2; do\n  echo $i\ndone")
+            .syntax_check()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_validate_executable() {
+        let cd = Command::new(
+            "cd".to_string(),
+            "cd /tmp".to_string(),
+            "tmp".to_string(),
+            None,
+        );
+        assert!(is_shell_builtin("cd"));
+        assert_eq!(cd.validate_executable(), Ok(()));
+
+        let sh = Command::new("sh".to_string(), "sh".to_string(), "sh".to_string(), None);
+        assert_eq!(sh.validate_executable(), Ok(()));
+
+        let missing = Command::new(
+            "definitely_not_a_command".to_string(),
+            "definitely_not_a_command --help".to_string(),
+            "missing".to_string(),
+            None,
+        );
+        assert!(!is_shell_builtin("definitely_not_a_command"));
+        assert_eq!(
+            missing.validate_executable(),
+            Err(ExecutableError::NotFound(
+                "definitely_not_a_command".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_syntax_check_unbalanced_quotes() {
+        let res = command("echo \"hello").syntax_check();
+        assert!(matches!(res, Err(SyntaxError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(command("ls -a").validate(DEFAULT_MAX_ALIAS_LEN), Ok(()));
+
+        let mut long = command("ls -a");
+        long.description = Some("d".repeat(MAX_DESCRIPTION_LEN + 1));
+        let error = long.validate(DEFAULT_MAX_ALIAS_LEN).unwrap_err();
+        assert_eq!(error.field(), "description");
+        assert_eq!(
+            error,
+            ValidationError::TooLong {
+                field: "description",
+                len: 301,
+                max: 300
+            }
+        );
+
+        long.description = Some("d".repeat(MAX_DESCRIPTION_LEN));
+        assert_eq!(long.validate(DEFAULT_MAX_ALIAS_LEN), Ok(()));
+
+        let mut unnamed = command("ls -a");
+        unnamed.alias = " ".to_string();
+        assert_eq!(
+            unnamed.validate(DEFAULT_MAX_ALIAS_LEN),
+            Err(ValidationError::Empty { field: "alias" })
+        );
+
+        let mut named = command("ls -a");
+        named.alias = "a".repeat(40);
+        assert_eq!(named.validate(DEFAULT_MAX_ALIAS_LEN), Ok(()));
+        assert_eq!(
+            named.validate(30),
+            Err(ValidationError::TooLong {
+                field: "alias",
+                len: 40,
+                max: 30
+            })
+        );
+        named.alias = "a".repeat(DEFAULT_MAX_ALIAS_LEN + 1);
+        assert!(named.validate(DEFAULT_MAX_ALIAS_LEN).is_err());
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let mut temporary = command("kubectl --context preview-42 get pods");
+        assert!(!temporary.is_expired("2026-10-16"));
+
+        temporary.expires_at = Some("2026-10-15".to_string());
+        assert!(temporary.is_expired("2026-10-16"));
+        assert!(temporary.is_expired("2027-01-01"));
+
+        temporary.expires_at = Some("2026-10-16".to_string());
+        assert!(!temporary.is_expired("2026-10-16"));
+        temporary.expires_at = Some("2026-11-01".to_string());
+        assert!(!temporary.is_expired("2026-10-16"));
+    }
+
+    #[test]
+    fn test_expiry_is_a_date() {
+        let mut temporary = command("ls -a");
+        temporary.expires_at = Some("2026-12-31".to_string());
+        assert_eq!(temporary.validate(DEFAULT_MAX_ALIAS_LEN), Ok(()));
+
+        for date in ["31/12/2026", "2026-13-01", "2026-12-1", "next week"] {
+            temporary.expires_at = Some(date.to_string());
+            assert_eq!(
+                temporary
+                    .validate(DEFAULT_MAX_ALIAS_LEN)
+                    .unwrap_err()
+                    .field(),
+                "expires_at"
+            );
+        }
+
+        let imported: Command =
+            toml::from_str("command = \"ls -a\"\nalias = \"ls_all\"\nexpires_at = 2026-12-31")
+                .unwrap();
+        assert_eq!(imported.expires_at.as_deref(), Some("2026-12-31"));
+    }
+
+    #[test]
+    fn test_label_serde() {
+        let mut labelled = command("ls -a");
+        labelled.label = Some(Label::Yellow);
+
+        let json = serde_json::to_string(&labelled).unwrap();
+        assert!(json.contains(r#""label":"yellow""#));
+        assert_eq!(serde_json::from_str::<Command>(&json).unwrap(), labelled);
+
+        let unlabelled = serde_json::to_string(&command("ls -a")).unwrap();
+        assert!(!unlabelled.contains("label"));
+
+        assert!(serde_json::from_str::<Label>(r#""orange""#).is_err());
+    }
+
+    #[test]
+    fn test_label_cycle() {
+        let mut label = None;
+        let mut seen = Vec::new();
+        for _ in 0..=Label::ALL.len() {
+            label = Label::cycle(label);
+            seen.push(label);
+        }
+
+        assert_eq!(seen[0], Some(Label::Red));
+        assert_eq!(seen[4], Some(Label::Purple));
+        assert_eq!(seen[5], None);
+    }
+
+    #[test]
+    fn test_strip_trailing_comment() {
+        assert_eq!(strip_trailing_comment("ls # list"), "ls");
+        assert_eq!(strip_trailing_comment("ls -a\t# all"), "ls -a");
+        assert_eq!(
+            strip_trailing_comment("echo \"# not a comment\""),
+            "echo \"# not a comment\""
+        );
+        assert_eq!(
+            strip_trailing_comment("echo '# nor this'"),
+            "echo '# nor this'"
+        );
+        assert_eq!(strip_trailing_comment("echo \\# kept"), "echo \\# kept");
+        assert_eq!(
+            strip_trailing_comment("echo a#b ${#PATH}"),
+            "echo a#b ${#PATH}"
+        );
+        assert_eq!(
+            strip_trailing_comment("for i in 1 2; do # loop\n  echo $i\ndone"),
+            "for i in 1 2; do\n  echo $i\ndone"
+        );
+    }
+
+    #[test]
+    fn test_select_for_os() {
+        let variant = |command: &str, os: Option<&str>| Command {
+            os: os.map(str::to_string),
+            ..Command::new(
+                "sed".to_string(),
+                command.to_string(),
+                "sed_in_place".to_string(),
+                None,
+            )
+        };
+        let variants = vec![
+            variant("sed -i '' s/a/b/ file", Some("macos")),
+            variant("sed -i s/a/b/ file", Some("linux")),
+            variant("perl -pi -e s/a/b/ file", None),
+        ];
+
+        let selected = select_for_os(&variants, "linux").unwrap();
+        assert_eq!(selected.command, "sed -i s/a/b/ file");
+
+        let selected = select_for_os(&variants, "windows").unwrap();
+        assert_eq!(selected.os, None);
+
+        assert_eq!(select_for_os(&variants[..2], "windows"), None);
+    }
+
+    #[test]
+    fn test_examples_round_trip() {
+        let examples = Examples(vec![
+            "ssh -p 2222 admin@db1".to_string(),
+            "echo \"quoted\"".to_string(),
+        ]);
+
+        assert_eq!(Examples::from(examples.to_json()), examples);
+        assert_eq!(Examples::from(String::new()), Examples::default());
+        assert_eq!(Examples::default().to_json(), "[]");
+    }
+
+    #[test]
+    fn test_generate_unique_alias() {
+        let mut taken = HashSet::new();
+        assert_eq!(
+            generate_unique_alias("git log --oneline", &taken),
+            "git_log_oneline"
+        );
+
+        taken.insert("git_log_oneline".to_string());
+        assert_eq!(
+            generate_unique_alias("git log --oneline", &taken),
+            "git_log_oneline_2"
+        );
+
+        let alias = generate_unique_alias("kubectl get pods --all-namespaces -o wide", &taken);
+        assert_eq!(alias, "kubectl_get_pods_all");
+        assert!(alias.len() <= GENERATED_ALIAS_LEN);
+        assert_eq!(generate_unique_alias("!!", &taken), "command");
+    }
+
+    #[test]
+    fn test_rewrite_references() {
+        assert_eq!(
+            rewrite_references("@build && test", "build", "compile"),
+            "@compile && test"
+        );
+        assert_eq!(
+            rewrite_references("@build; @build_all; me@build; @builder", "build", "compile"),
+            "@compile; @build_all; me@build; @builder"
+        );
+        assert_eq!(
+            rewrite_references("cargo build", "build", "compile"),
+            "cargo build"
+        );
+    }
+
+    #[test]
+    fn test_rendered_description() {
+        let mut command = Command::new(
+            "git".to_string(),
+            "git pull".to_string(),
+            "git_pull".to_string(),
+            Some("Does: {command}".to_string()),
+        );
+        assert_eq!(
+            command.rendered_description().as_deref(),
+            Some("Does: git pull")
+        );
+
+        command.description = None;
+        assert_eq!(command.rendered_description(), None);
+    }
 }
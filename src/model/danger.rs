@@ -0,0 +1,104 @@
+/// Patterns of commands that destroy data or take the machine down, used unless the config
+/// lists its own
+pub const DEFAULT_DANGEROUS_PATTERNS: &[&str] = &[
+    "rm -rf",
+    "rm -fr",
+    "rm -r",
+    "dd",
+    "mkfs",
+    "shred",
+    ":(){ :|:& };:",
+    "> /dev/sd",
+    "chmod -R 777 /",
+    "git push --force",
+    "git reset --hard",
+    "shutdown",
+    "reboot",
+];
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Whether `pattern` occurs in `text`. Patterns starting with a word only match whole words,
+/// so `dd` matches `dd if=x` but neither `git add` nor `ddclient`, while `> /dev/sd` still
+/// matches `> /dev/sda`.
+fn contains_pattern(text: &str, pattern: &str) -> bool {
+    if !pattern.starts_with(is_word_char) {
+        return text.contains(pattern);
+    }
+
+    text.match_indices(pattern).any(|(start, matched)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + matched.len()..].chars().next();
+
+        let starts_word = !before.is_some_and(is_word_char);
+        let ends_word = !pattern.ends_with(is_word_char) || !after.is_some_and(is_word_char);
+
+        starts_word && ends_word
+    })
+}
+
+/// Heuristic for commands that deserve a confirmation before they are copied or run.
+/// Runs of whitespace are collapsed so `rm  -rf` is caught as well.
+pub fn is_dangerous(command: &str, patterns: &[String]) -> bool {
+    let command = command.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    patterns
+        .iter()
+        .map(|pattern| pattern.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|pattern| !pattern.is_empty())
+        .any(|pattern| contains_pattern(&command, &pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_dangerous, DEFAULT_DANGEROUS_PATTERNS};
+
+    fn defaults() -> Vec<String> {
+        DEFAULT_DANGEROUS_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_dangerous_commands() {
+        for command in [
+            "rm -rf /",
+            "sudo rm  -rf ~/projects",
+            "dd if=/dev/zero of=/dev/sda bs=1M",
+            ":(){ :|:& };:",
+            "mkfs.ext4 /dev/sdb1",
+            "git push --force origin main",
+            "echo oops > /dev/sda",
+        ] {
+            assert!(is_dangerous(command, &defaults()), "{command}");
+        }
+    }
+
+    #[test]
+    fn test_benign_commands() {
+        for command in [
+            "git add .",
+            "ls -a",
+            "rm file.txt",
+            "git push origin main",
+            "odd --flag",
+            "ddclient -daemon 300",
+            "systemctl status reboot-notifier",
+            "kubectl get pods",
+        ] {
+            assert!(!is_dangerous(command, &defaults()), "{command}");
+        }
+    }
+
+    #[test]
+    fn test_custom_patterns() {
+        let patterns = vec!["kubectl delete".to_string()];
+
+        assert!(is_dangerous("kubectl delete pod web", &patterns));
+        assert!(!is_dangerous("rm -rf /", &patterns));
+        assert!(!is_dangerous("rm -rf /", &[]));
+    }
+}
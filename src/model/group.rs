@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+/// Coarse groups of the well known executables, used unless the config maps its own
+pub const DEFAULT_EXECUTABLE_GROUPS: &[(&str, &str)] = &[
+    ("git", "vcs"),
+    ("hg", "vcs"),
+    ("svn", "vcs"),
+    ("docker", "container"),
+    ("podman", "container"),
+    ("kubectl", "container"),
+    ("ssh", "network"),
+    ("scp", "network"),
+    ("curl", "network"),
+    ("wget", "network"),
+];
+
+/// The default executable to group mapping as the config holds it
+pub fn default_executable_groups() -> HashMap<String, String> {
+    DEFAULT_EXECUTABLE_GROUPS
+        .iter()
+        .map(|(executable, group)| (executable.to_string(), group.to_string()))
+        .collect()
+}
+
+/// The group `executable` belongs to, if the mapping has one for it
+pub fn classify_group(executable: &str, map: &HashMap<String, String>) -> Option<String> {
+    map.get(&executable.to_lowercase()).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{classify_group, default_executable_groups};
+
+    #[test]
+    fn test_classify_group() {
+        let map = default_executable_groups();
+        assert_eq!(classify_group("git", &map).as_deref(), Some("vcs"));
+        assert_eq!(classify_group("podman", &map).as_deref(), Some("container"));
+        assert_eq!(classify_group("Curl", &map).as_deref(), Some("network"));
+        assert_eq!(classify_group("ls", &map), None);
+
+        let custom = HashMap::from([("ls".to_string(), "files".to_string())]);
+        assert_eq!(classify_group("ls", &custom).as_deref(), Some("files"));
+        assert_eq!(classify_group("git", &custom), None);
+    }
+}
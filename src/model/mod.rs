@@ -1 +1,4 @@
 pub mod command;
+pub mod danger;
+pub mod group;
+pub mod placeholder;
@@ -0,0 +1,307 @@
+use std::collections::{BTreeSet, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A piece of a command: literal text or a `{name}` placeholder
+#[derive(Debug, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Named(&'a str),
+}
+
+/// Splits a command around its `{name}` placeholders. Only identifiers count, so shell
+/// constructs like `{a,b}`, `${HOME}` or `{{` are kept as text.
+pub fn segments(command: &str) -> Vec<Segment<'_>> {
+    let bytes = command.as_bytes();
+    let mut segments = Vec::new();
+    let (mut text_start, mut i) = (0, 0);
+
+    while i < bytes.len() {
+        if bytes[i] == b'{' && bytes.get(i + 1) == Some(&b'{') {
+            i += 2;
+            continue;
+        }
+
+        let after_dollar = i > 0 && bytes[i - 1] == b'$';
+        let name = match command[i..].find('}') {
+            Some(len) if bytes[i] == b'{' && !after_dollar => &command[i + 1..i + len],
+            _ => "",
+        };
+
+        if is_identifier(name) {
+            if text_start < i {
+                segments.push(Segment::Text(&command[text_start..i]));
+            }
+            segments.push(Segment::Named(name));
+            i += name.len() + 2;
+            text_start = i;
+        } else {
+            i += 1;
+        }
+    }
+
+    if text_start < command.len() {
+        segments.push(Segment::Text(&command[text_start..]));
+    }
+
+    segments
+}
+
+/// The `{name}` placeholders of a command in order of first appearance
+pub fn extract_named(command: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+
+    for segment in segments(command) {
+        if let Segment::Named(name) = segment {
+            if !names.iter().any(|known| known == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+
+    names
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Fills every `{name}` placeholder `lookup` knows a value for, keeping the others as they are.
+/// Also tells whether anything was filled in.
+pub fn expand_named(command: &str, lookup: impl Fn(&str) -> Option<String>) -> (String, bool) {
+    let mut expanded = String::with_capacity(command.len());
+    let mut any = false;
+
+    for segment in segments(command) {
+        match segment {
+            Segment::Text(text) => expanded.push_str(text),
+            Segment::Named(name) => match lookup(name) {
+                Some(value) => {
+                    expanded.push_str(&value);
+                    any = true;
+                }
+                None => {
+                    expanded.push('{');
+                    expanded.push_str(name);
+                    expanded.push('}');
+                }
+            },
+        }
+    }
+
+    (expanded, any)
+}
+
+/// Placeholders filled in without asking: `{pwd}` the current directory, `{home}` the home
+/// directory and `{date}` today's date in UTC, as `YYYY-MM-DD`
+pub fn builtin_placeholders() -> HashMap<&'static str, String> {
+    let mut builtins = HashMap::new();
+
+    if let Ok(pwd) = std::env::current_dir() {
+        builtins.insert("pwd", pwd.to_string_lossy().to_string());
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        builtins.insert("home", home);
+    }
+    builtins.insert("date", today());
+
+    builtins
+}
+
+/// Today's date in UTC, as `YYYY-MM-DD`
+pub fn today() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    format_date(now.as_secs() / 86_400)
+}
+
+/// The `YYYY-MM-DD` date `days` after the epoch, in the proleptic Gregorian calendar
+fn format_date(days: u64) -> String {
+    // Howard Hinnant's civil_from_days, shifted so years start in March
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Fills the built-in placeholders, any other `{name}` is kept as it is. Also tells whether
+/// anything was filled in.
+pub fn expand_automatic(command: &str) -> (String, bool) {
+    let builtins = builtin_placeholders();
+
+    expand_named(command, |name| builtins.get(name).cloned())
+}
+
+/// The environment variables a command expands as `$NAME` or `${NAME}`, in order of first
+/// appearance. Escaped dollars, positionals and `${NAME:-default}` style expansions are left out.
+pub fn required_env_vars(command: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = command;
+
+    while let Some(start) = rest.find('$') {
+        let escaped = rest[..start].ends_with('\\');
+        let after = &rest[start + 1..];
+
+        let name = if let Some(braced) = after.strip_prefix('{') {
+            braced.find('}').map_or("", |end| &braced[..end])
+        } else {
+            let end = after
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(after.len());
+            &after[..end]
+        };
+
+        if !escaped && is_identifier(name) && !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+        rest = after;
+    }
+
+    names
+}
+
+/// The positional arguments `$1`..`$9` referenced by a command, in order
+pub fn extract_positionals(command: &str) -> BTreeSet<u8> {
+    let bytes = command.as_bytes();
+
+    bytes
+        .windows(2)
+        .filter(|pair| pair[0] == b'$' && (b'1'..=b'9').contains(&pair[1]))
+        .map(|pair| pair[1] - b'0')
+        .collect()
+}
+
+/// Replaces every `$n` with the n-th value, leaving the positionals without a value untouched
+pub fn substitute_positionals(command: &str, values: &[String]) -> String {
+    let mut substituted = String::with_capacity(command.len());
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let value = match (c, chars.peek().and_then(|next| next.to_digit(10))) {
+            ('$', Some(position @ 1..=9)) => values.get(position as usize - 1),
+            _ => None,
+        };
+
+        match value {
+            Some(value) => {
+                substituted.push_str(value);
+                chars.next();
+            }
+            None => substituted.push(c),
+        }
+    }
+
+    substituted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::{
+        builtin_placeholders, expand_automatic, expand_named, extract_named, extract_positionals,
+        format_date, required_env_vars, segments, substitute_positionals, Segment,
+    };
+
+    #[test]
+    fn test_extract_positionals() {
+        assert_eq!(extract_positionals("grep $1 $2"), BTreeSet::from([1, 2]));
+        assert_eq!(extract_positionals("echo $2 $1 $2"), BTreeSet::from([1, 2]));
+        assert!(extract_positionals("echo $HOME $0").is_empty());
+    }
+
+    #[test]
+    fn test_substitute_positionals() {
+        let values = vec!["foo".to_string(), "notes.txt".to_string()];
+        assert_eq!(
+            substitute_positionals("grep $1 $2", &values),
+            "grep foo notes.txt"
+        );
+        assert_eq!(
+            substitute_positionals("grep $1 $3 $HOME", &values),
+            "grep foo $3 $HOME"
+        );
+    }
+
+    #[test]
+    fn test_extract_named() {
+        assert_eq!(
+            extract_named("kubectl logs {pod} -n {namespace} | grep {pod}"),
+            vec!["pod".to_string(), "namespace".to_string()]
+        );
+        assert!(extract_named("echo ${HOME} {a,b} {{x}} {}").is_empty());
+    }
+
+    #[test]
+    fn test_segments() {
+        assert_eq!(
+            segments("ssh {host} 'ls {{}}'"),
+            vec![
+                Segment::Text("ssh "),
+                Segment::Named("host"),
+                Segment::Text(" 'ls {{}}'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_named() {
+        let lookup = |name: &str| (name == "host").then(|| "db1".to_string());
+
+        assert_eq!(
+            expand_named("ssh {host} -p {port}", lookup),
+            ("ssh db1 -p {port}".to_string(), true)
+        );
+        assert_eq!(
+            expand_named("ssh ${host} {{host}}", lookup),
+            ("ssh ${host} {{host}}".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn test_required_env_vars() {
+        assert_eq!(
+            required_env_vars("deploy $REGION ${CLUSTER}"),
+            ["REGION", "CLUSTER"]
+        );
+        assert_eq!(
+            required_env_vars("echo $1 \\$HOME ${USER:-me} $PATH:$PATH"),
+            ["PATH"]
+        );
+    }
+
+    #[test]
+    fn test_builtin_placeholders() {
+        let pwd = std::env::current_dir().unwrap();
+        let pwd = pwd.to_string_lossy();
+
+        assert_eq!(builtin_placeholders()["pwd"], pwd);
+        assert_eq!(
+            expand_automatic("ls {pwd}/src"),
+            (format!("ls {pwd}/src"), true)
+        );
+        assert_eq!(builtin_placeholders()["date"].len(), "2024-01-31".len());
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(11_016), "2000-02-29");
+        assert_eq!(format_date(19_753), "2024-01-31");
+    }
+}
@@ -0,0 +1,69 @@
+/// An entry of a cheat sheet: the command and the comment lines above it
+#[derive(Debug, PartialEq, Eq)]
+pub struct CheatEntry {
+    pub description: Option<String>,
+    pub command: String,
+}
+
+/// Parses a cheat sheet made of blank line separated blocks, each being `# description` lines
+/// followed by the command. Only the first command line of a block is kept, blocks without
+/// one are dropped.
+pub fn parse_cheat(text: &str) -> Vec<CheatEntry> {
+    let mut entries = Vec::new();
+    let mut description: Vec<&str> = Vec::new();
+    let mut command: Option<&str> = None;
+
+    for line in text.lines().map(str::trim).chain(std::iter::once("")) {
+        if line.is_empty() {
+            if let Some(command) = command.take() {
+                entries.push(CheatEntry {
+                    description: Some(description.join(" ")).filter(|d| !d.is_empty()),
+                    command: command.to_string(),
+                });
+            }
+            description.clear();
+        } else if let Some(comment) = line.strip_prefix('#') {
+            if command.is_none() {
+                description.push(comment.trim());
+            }
+        } else if command.is_none() {
+            command = Some(line);
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cheat, CheatEntry};
+
+    #[test]
+    fn test_parse_cheat() {
+        let entries = parse_cheat(
+            "# Show the files changed since the last commit\n\
+             git status --short\n\
+             \n\
+             # Build the image\n\
+             # and tag it\n\
+             docker build -t app .\n\
+             docker push app\n\
+             \n\
+             # Nothing to run here\n",
+        );
+
+        assert_eq!(
+            entries,
+            vec![
+                CheatEntry {
+                    description: Some("Show the files changed since the last commit".to_string()),
+                    command: "git status --short".to_string(),
+                },
+                CheatEntry {
+                    description: Some("Build the image and tag it".to_string()),
+                    command: "docker build -t app .".to_string(),
+                },
+            ]
+        );
+    }
+}
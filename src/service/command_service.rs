@@ -1,8 +1,89 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::future;
+use futures::TryStreamExt;
 use thiserror::Error;
-use tracing::info;
+use tracing::{info, warn};
+
+use super::cheat::parse_cheat;
+use super::crypto::{from_hex, generate_salt, to_hex, Cipher, CryptoError};
+use super::export::{export_commands, ExportFormat};
+use super::shell_aliases::parse_shell_aliases;
+use crate::model::command::{
+    generate_unique_alias, select_for_os, Command, Label, ValidationError, DEFAULT_MAX_ALIAS_LEN,
+};
+use crate::model::group::classify_group;
+use crate::storage::command_storage::{
+    db_file_path, CommandStorageError, CommandStorageManager, OrderBy, StorageOptions,
+};
+
+/// What merging does with a command whose alias or command string is already stored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConflictPolicy {
+    /// Keep the stored command
+    #[default]
+    Skip,
+    /// Overwrite the stored command
+    Replace,
+    /// Store the merged command under a free alias such as `git_pull_2`
+    Rename,
+}
+
+/// How the commands of a merged database were stored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    pub inserted: usize,
+    pub replaced: usize,
+    pub renamed: usize,
+    pub skipped: usize,
+}
+
+/// How the commands of an import were stored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub inserted: usize,
+    /// Commands whose alias or command string was already stored
+    pub skipped_duplicates: usize,
+    /// Commands that are invalid or could not be written
+    pub failed: usize,
+}
+
+impl ImportReport {
+    pub fn record(&mut self, outcome: ImportOutcome) {
+        match outcome {
+            ImportOutcome::Inserted => self.inserted += 1,
+            ImportOutcome::Duplicate => self.skipped_duplicates += 1,
+            ImportOutcome::Failed => self.failed += 1,
+        }
+    }
+}
+
+impl std::fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} duplicates skipped, {} failed",
+            self.inserted, self.skipped_duplicates, self.failed
+        )
+    }
+}
+
+/// What happened to a single imported command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportOutcome {
+    Inserted,
+    Duplicate,
+    Failed,
+}
 
-use crate::model::command::Command;
-use crate::storage::command_storage::{CommandStorageError, CommandStorageManager};
+/// Size of the database in bytes around a vacuum
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VacuumSummary {
+    pub before: u64,
+    pub after: u64,
+}
 
 #[derive(Debug, Error)]
 pub enum CommandServiceError {
@@ -18,25 +99,339 @@ pub enum CommandServiceError {
     #[error("Failed to retrieve single command : {0}")]
     StorageManagerGetCommand(CommandStorageError),
 
+    #[error("Failed to check whether the command exists : {0}")]
+    StorageManagerCommandExists(CommandStorageError),
+
+    #[error("Failed to search for commands : {0}")]
+    StorageManagerSearch(CommandStorageError),
+
+    #[error("Failed to back up the database : {0}")]
+    StorageManagerBackup(CommandStorageError),
+
+    #[error("Failed to vacuum the database : {0}")]
+    StorageManagerVacuum(CommandStorageError),
+
     #[error("Failed to delete a command : {0}")]
     StorageManagerDeleteCommand(CommandStorageError),
 
+    #[error("Failed to update a command : {0}")]
+    StorageManagerUpdateCommand(CommandStorageError),
+
+    #[error("Failed to export the commands : {0}")]
+    Export(#[from] std::io::Error),
+
+    #[error("Failed to serialise the commands : {0}")]
+    Serialize(String),
+
+    #[error("Invalid command : {0}")]
+    Invalid(#[from] ValidationError),
+
+    #[error("Failed to read the cheat sheet : {0}")]
+    ReadCheat(std::io::Error),
+
+    #[error("Failed to read the shell aliases : {0}")]
+    ReadAliases(std::io::Error),
+
+    #[error("Failed to read the passphrase : {0}")]
+    ReadPassphrase(std::io::Error),
+
+    #[error("Failed to encrypt or decrypt a sensitive command : {0}")]
+    Crypto(#[from] CryptoError),
+
+    #[error("There is no database at {0}")]
+    MissingDatabase(String),
+
+    #[error("{0} holds sensitive commands sealed with its own passphrase, they cannot be merged")]
+    SensitiveMerge(String),
+
+    #[error("The retention period cannot be negative, got {0} days")]
+    NegativeRetention(i64),
+
+    #[error("No command {command} is stored under the alias {alias}")]
+    NotFound { command: String, alias: String },
+
+    #[error("No command is stored under the alias {0}")]
+    AliasNotFound(String),
+
     #[error("Unable to parse the executable out of the given command")]
     NoExecutable,
+
+    #[error("The alias {0} is already taken")]
+    AliasTaken(String),
+}
+
+/// Checks `new_alias` fits an alias column `max_alias_len` wide and is not the alias of any of
+/// `stored`
+pub fn check_new_alias<'a>(
+    new_alias: &str,
+    mut stored: impl Iterator<Item = &'a Command>,
+    max_alias_len: usize,
+) -> Result<(), CommandServiceError> {
+    if new_alias.trim().is_empty() {
+        return Err(ValidationError::Empty { field: "alias" }.into());
+    }
+    let len = new_alias.chars().count();
+    if len > max_alias_len {
+        return Err(ValidationError::TooLong {
+            field: "alias",
+            len,
+            max: max_alias_len,
+        }
+        .into());
+    }
+    if stored.any(|command| command.alias == new_alias) {
+        return Err(CommandServiceError::AliasTaken(new_alias.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Commands that run the rest of the command line, e.g. `sudo apt update`
+const WRAPPERS: &[&str] = &["sudo", "doas", "env", "time", "nohup", "nice", "exec"];
+
+/// Options of a wrapper that take a value, e.g. `sudo -u root`
+fn options_with_value(wrapper: &str) -> &'static [&'static str] {
+    match wrapper {
+        "sudo" | "doas" => &["-u", "-g", "-C"],
+        "nice" => &["-n"],
+        "env" => &["-u", "-C"],
+        _ => &[],
+    }
+}
+
+/// The executable is the first word of the command, multi-line commands included, looking
+/// past wrappers like `sudo` along with their options and `VAR=value` assignments.
+pub fn parse_executable(command: &str) -> Result<&str, CommandServiceError> {
+    let mut words = command.split_whitespace();
+    let mut wrapper = None;
+
+    while let Some(word) = words.next() {
+        let is_assignment = word.contains('=') && !word.starts_with('=') && !word.starts_with('-');
+
+        if WRAPPERS.contains(&word) {
+            wrapper = Some(word);
+        } else if let (Some(wrapper), true) = (wrapper, word.starts_with('-')) {
+            if options_with_value(wrapper).contains(&word) {
+                words.next();
+            }
+        } else if !is_assignment {
+            return Ok(word);
+        }
+    }
+
+    // A bare wrapper is a command of its own
+    command
+        .split_whitespace()
+        .next()
+        .ok_or(CommandServiceError::NoExecutable)
+}
+
+/// The executable as it is stored, lower cased so `Git` and `git` share a tab
+pub fn normalized_executable(command: &str) -> Result<String, CommandServiceError> {
+    Ok(parse_executable(command)?.to_lowercase())
+}
+
+/// The first of `alias_2`, `alias_3`, ... not used by `stored` in the environment of `command`,
+/// shortening the alias when the suffix would not fit a column `max_alias_len` wide
+fn free_alias(command: &Command, stored: &[Command], max_alias_len: usize) -> String {
+    (2..)
+        .map(|n| {
+            let suffix = format!("_{n}");
+            let base: String = command
+                .alias
+                .chars()
+                .take(max_alias_len.saturating_sub(suffix.len()))
+                .collect();
+            format!("{base}{suffix}")
+        })
+        .find(|alias| {
+            !stored.iter().any(|existing| {
+                existing.alias == *alias && existing.environment == command.environment
+            })
+        })
+        .unwrap_or_default()
 }
 
+/// Settings key of the salt the keys of the sensitive commands are derived with
+const KDF_SALT_KEY: &str = "kdf_salt";
+
 pub struct CommandService {
     storage_manager: CommandStorageManager,
+    /// Encrypts the sensitive commands, `None` until unlocked with the passphrase
+    cipher: Option<Cipher>,
+    /// Description of the commands stored without one, see `set_default_description_template`
+    default_description_template: Option<String>,
+    /// Longest alias the commands are stored with
+    max_alias_len: usize,
 }
 
 impl CommandService {
+    #[cfg(test)]
     pub async fn new(db_url: &str) -> Result<Self, CommandServiceError> {
+        CommandService::with_options(db_url, &StorageOptions::default()).await
+    }
+
+    pub async fn with_options(
+        db_url: &str,
+        options: &StorageOptions,
+    ) -> Result<Self, CommandServiceError> {
         info!("Creating the Storage Manager");
-        let storage_manager = CommandStorageManager::new(db_url)
+        let storage_manager = CommandStorageManager::with_options(db_url, options)
+            .await
+            .map_err(CommandServiceError::StorageManagerConstruction)?;
+
+        Ok(CommandService {
+            storage_manager,
+            cipher: None,
+            default_description_template: None,
+            max_alias_len: options.max_alias_len,
+        })
+    }
+
+    /// The service of a db that is only read from, e.g. a backup, which is left untouched
+    pub async fn open_read_only(db_url: &str) -> Result<Self, CommandServiceError> {
+        let storage_manager = CommandStorageManager::open_read_only(db_url)
+            .await
+            .map_err(CommandServiceError::StorageManagerConstruction)?;
+
+        Ok(CommandService {
+            storage_manager,
+            cipher: None,
+            default_description_template: None,
+            max_alias_len: DEFAULT_MAX_ALIAS_LEN,
+        })
+    }
+
+    /// Longest alias the commands are stored with, the width of the alias column
+    pub fn max_alias_len(&self) -> usize {
+        self.max_alias_len
+    }
+
+    /// Gives the commands stored without a description one made from `template`, in which
+    /// `{executable}` and `{alias}` are replaced by those of the command
+    pub fn set_default_description_template(&mut self, template: Option<String>) {
+        self.default_description_template = template;
+    }
+
+    /// `description`, or else the default one of a command with `executable` and `alias`
+    fn description_or_default(
+        &self,
+        description: Option<String>,
+        executable: &str,
+        alias: &str,
+    ) -> Option<String> {
+        description.or_else(|| {
+            self.default_description_template.as_ref().map(|template| {
+                template
+                    .replace("{executable}", executable)
+                    .replace("{alias}", alias)
+            })
+        })
+    }
+
+    /// Derives the key of the sensitive commands from `passphrase`, failing when it does not
+    /// decrypt the ones already stored
+    pub async fn unlock(&mut self, passphrase: &str) -> Result<(), CommandServiceError> {
+        let salt = match self
+            .storage_manager
+            .get_setting(KDF_SALT_KEY)
+            .await
+            .map_err(CommandServiceError::StorageManagerGetCommand)?
+        {
+            Some(salt) => from_hex(&salt).ok_or(CryptoError::Malformed)?,
+            None => {
+                let salt = generate_salt();
+                self.storage_manager
+                    .set_setting(KDF_SALT_KEY, &to_hex(&salt))
+                    .await
+                    .map_err(CommandServiceError::StorageManagerInsertCommand)?;
+                salt.to_vec()
+            }
+        };
+
+        let cipher = Cipher::new(passphrase, &salt)?;
+        // A mistyped passphrase must not go on to encrypt commands with another key
+        if let Some(sample) = self
+            .storage_manager
+            .first_sensitive()
+            .await
+            .map_err(CommandServiceError::StorageManagerGetCommand)?
+        {
+            cipher.open(&sample.command)?;
+        }
+
+        self.cipher = Some(cipher);
+        Ok(())
+    }
+
+    /// Unlocks with the passphrase given by `passphrase` when sensitive commands are stored,
+    /// or whenever `force` is set
+    pub async fn unlock_when_needed(
+        &mut self,
+        force: bool,
+        passphrase: impl FnOnce() -> std::io::Result<String>,
+    ) -> Result<(), CommandServiceError> {
+        if self.cipher.is_some() || !(force || self.has_sensitive_commands().await?) {
+            return Ok(());
+        }
+
+        let passphrase = passphrase().map_err(CommandServiceError::ReadPassphrase)?;
+        self.unlock(&passphrase).await
+    }
+
+    pub async fn has_sensitive_commands(&self) -> Result<bool, CommandServiceError> {
+        let sample = self
+            .storage_manager
+            .first_sensitive()
             .await
-            .map_err(|e| CommandServiceError::StorageManagerConstruction(e))?;
+            .map_err(CommandServiceError::StorageManagerGetCommand)?;
+
+        Ok(sample.is_some())
+    }
+
+    /// `command` as stored: encrypted when it is sensitive
+    fn seal(&self, command: Command) -> Result<Command, CommandServiceError> {
+        if !command.sensitive {
+            return Ok(command);
+        }
+
+        let cipher = self.cipher.as_ref().ok_or(CryptoError::Locked)?;
+        Ok(Command {
+            command: cipher.seal(&command.command)?,
+            ..command
+        })
+    }
+
+    /// The command string `command` is stored under
+    fn sealed_text(&self, command: &Command) -> Result<String, CommandServiceError> {
+        Ok(self.seal(command.clone())?.command)
+    }
+
+    /// `command` as read from the db, decrypted when it is sensitive and the service is
+    /// unlocked. Locked sensitive commands keep their encrypted text.
+    fn reveal(&self, command: Command) -> Result<Command, CommandServiceError> {
+        match &self.cipher {
+            Some(cipher) if command.sensitive => Ok(Command {
+                command: cipher.open(&command.command)?,
+                ..command
+            }),
+            _ => Ok(command),
+        }
+    }
 
-        Ok(CommandService { storage_manager })
+    /// `commands` as read from the db, see `reveal`. A command that fails to decrypt keeps its
+    /// encrypted text instead of failing the whole list.
+    fn reveal_all(&self, commands: Vec<Command>) -> Vec<Command> {
+        commands
+            .into_iter()
+            .map(|command| match self.reveal(command.clone()) {
+                Ok(revealed) => revealed,
+                Err(e) => {
+                    warn!("Showing {} encrypted: {e}", command.alias);
+                    command
+                }
+            })
+            .collect()
     }
 
     pub async fn insert_command(
@@ -45,177 +440,2365 @@ impl CommandService {
         alias: &str,
         description: Option<String>,
     ) -> Result<Command, CommandServiceError> {
-        let executable = command
-            .split(' ')
-            .collect::<Vec<&str>>()
-            .first()
-            .ok_or(CommandServiceError::NoExecutable)?
-            .to_owned();
+        self.insert_variant(command, alias, description, None).await
+    }
 
-        let command = Command::new(
-            executable.to_string(),
-            command.to_string(),
-            alias.to_string(),
-            description,
-        );
+    /// Inserts the variant of `alias` used in `environment`, `None` being the default one
+    pub async fn insert_variant(
+        &self,
+        command: &str,
+        alias: &str,
+        description: Option<String>,
+        environment: Option<String>,
+    ) -> Result<Command, CommandServiceError> {
+        let executable = normalized_executable(command)?;
+        let description = self.description_or_default(description, &executable, alias);
+
+        let command = Command {
+            environment,
+            ..Command::new(
+                executable,
+                command.to_string(),
+                alias.to_string(),
+                description,
+            )
+        };
         self.storage_manager
             .insert_command(command.clone())
             .await
-            .map_err(|e| CommandServiceError::StorageManagerInsertCommand(e))?;
+            .map_err(CommandServiceError::StorageManagerInsertCommand)?;
 
         Ok(command)
     }
 
-    pub async fn get_all_commands(&self) -> Result<Vec<Command>, CommandServiceError> {
+    pub async fn command_exists(
+        &self,
+        command: &str,
+        alias: &str,
+    ) -> Result<bool, CommandServiceError> {
         self.storage_manager
-            .get_all_commands()
+            .command_exists(command, alias)
             .await
-            .map_err(|e| CommandServiceError::StorageManagerGetAll(e))
+            .map_err(CommandServiceError::StorageManagerCommandExists)
     }
 
-    pub async fn get_command(
+    /// Inserts the command unless one with the same command string or alias is already stored,
+    /// in which case the stored one is returned untouched. The flag tells whether it was inserted.
+    pub async fn ensure_command(
         &self,
         command: &str,
         alias: &str,
-    ) -> Result<Command, CommandServiceError> {
-        let executable = command
-            .split(' ')
-            .collect::<Vec<&str>>()
-            .first()
-            .ok_or(CommandServiceError::NoExecutable)?
-            .to_owned();
+        description: Option<String>,
+    ) -> Result<(Command, bool), CommandServiceError> {
+        if self.command_exists(command, alias).await? {
+            let existing = self
+                .storage_manager
+                .get_command_by_command_or_alias(command, alias)
+                .await
+                .map_err(CommandServiceError::StorageManagerGetCommand)?;
 
-        let command = Command::new(
-            executable.to_string(),
-            command.to_string(),
-            alias.to_string(),
-            None,
-        );
+            return Ok((self.reveal(existing)?, false));
+        }
 
-        self.storage_manager
-            .get_command(command)
+        let inserted = self.insert_command(command, alias, description).await?;
+
+        Ok((inserted, true))
+    }
+
+    /// Closes the db, see `CommandStorageManager::close`
+    pub async fn close(self) {
+        self.storage_manager.close().await;
+    }
+
+    /// Every enabled command ordered by alias
+    pub async fn get_all_commands(&self) -> Result<Vec<Command>, CommandServiceError> {
+        let commands = self
+            .storage_manager
+            .get_all_commands()
             .await
-            .map_err(|e| CommandServiceError::StorageManagerGetCommand(e))
+            .map_err(CommandServiceError::StorageManagerGetAll)?;
+
+        Ok(self.reveal_all(commands))
     }
 
-    pub async fn delete_command(
+    /// Every stored command ordered by alias, the disabled ones included
+    pub async fn get_all_commands_including_disabled(
         &self,
-        command: &str,
-        alias: &str,
-        description: Option<String>,
-    ) -> Result<Command, CommandServiceError> {
-        let executable = command
-            .split(' ')
-            .collect::<Vec<&str>>()
-            .first()
-            .ok_or(CommandServiceError::NoExecutable)?
-            .to_owned();
+    ) -> Result<Vec<Command>, CommandServiceError> {
+        self.get_all_commands_ordered(OrderBy::Alias, true).await
+    }
 
-        let command = Command::new(
-            executable.to_string(),
-            command.to_string(),
-            alias.to_string(),
-            description,
-        );
-        self.storage_manager
-            .delete_command(command.clone())
+    pub async fn get_all_commands_ordered(
+        &self,
+        order_by: OrderBy,
+        include_disabled: bool,
+    ) -> Result<Vec<Command>, CommandServiceError> {
+        let commands = self
+            .storage_manager
+            .get_all_commands_ordered(order_by, include_disabled)
             .await
-            .map_err(|e| CommandServiceError::StorageManagerDeleteCommand(e))?;
+            .map_err(CommandServiceError::StorageManagerGetAll)?;
 
-        Ok(command)
+        Ok(self.reveal_all(commands))
     }
-}
 
-#[cfg(test)]
-mod tests {
+    /// Hides every variant of `alias` from the listings when it is enabled, shows it again
+    /// when it is disabled
+    pub async fn toggle_enabled(&self, alias: &str) -> Result<(), CommandServiceError> {
+        let toggled = self
+            .storage_manager
+            .toggle_enabled(alias)
+            .await
+            .map_err(CommandServiceError::StorageManagerUpdateCommand)?;
 
-    use serial_test::serial;
+        if toggled == 0 {
+            return Err(CommandServiceError::AliasNotFound(alias.to_string()));
+        }
 
-    use crate::model::command::Command;
+        Ok(())
+    }
 
-    use super::CommandService;
+    /// Counts one more copy or run of `command`
+    pub async fn record_use(&self, command: &Command) -> Result<(), CommandServiceError> {
+        self.storage_manager
+            .record_use(&self.sealed_text(command)?)
+            .await
+            .map_err(CommandServiceError::StorageManagerUpdateCommand)
+    }
 
-    #[tokio::test]
-    #[serial]
-    async fn test_service_construction() {
-        let _ = CommandService::new("test.sqlite").await.unwrap();
+    pub async fn get_recent_commands(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<Command>, CommandServiceError> {
+        let commands = self
+            .storage_manager
+            .get_recent_commands(limit)
+            .await
+            .map_err(CommandServiceError::StorageManagerGetAll)?;
 
-        assert!(std::fs::metadata("test.sqlite").unwrap().is_file());
-        std::fs::remove_file("test.sqlite").unwrap();
+        Ok(self.reveal_all(commands))
     }
 
-    #[tokio::test]
-    #[serial]
-    async fn test_insertion() {
-        let service = CommandService::new("test.sqlite").await.unwrap();
+    pub async fn get_command_by_alias(&self, alias: &str) -> Result<Command, CommandServiceError> {
+        let command = self
+            .storage_manager
+            .get_command_by_alias(alias)
+            .await
+            .map_err(CommandServiceError::StorageManagerGetCommand)?;
 
-        let inserted = service
-            .insert_command("test command arguments", "my_test", None)
+        self.reveal(command)
+    }
+
+    pub async fn get_by_alias_and_env(
+        &self,
+        alias: &str,
+        environment: Option<&str>,
+    ) -> Result<Command, CommandServiceError> {
+        self.get_for_os(alias, environment, std::env::consts::OS)
             .await
-            .unwrap();
+    }
 
-        assert_eq!(inserted.executable, "test".to_string());
-        assert_eq!(inserted.command, "test command arguments".to_string());
+    /// The variant of `alias` in `environment` meant for `os`, or else the one for every system
+    pub async fn get_for_os(
+        &self,
+        alias: &str,
+        environment: Option<&str>,
+        os: &str,
+    ) -> Result<Command, CommandServiceError> {
+        let variants = self
+            .storage_manager
+            .get_by_alias_and_env(alias, environment)
+            .await
+            .map_err(CommandServiceError::StorageManagerGetCommand)?;
+        // A variant that fails to decrypt must not be run as its encrypted text
+        let variants = variants
+            .into_iter()
+            .map(|variant| self.reveal(variant))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        std::fs::remove_file("test.sqlite").unwrap();
+        select_for_os(&variants, os)
+            .cloned()
+            .ok_or_else(|| CommandServiceError::AliasNotFound(alias.to_string()))
     }
 
-    #[tokio::test]
-    #[serial]
-    async fn test_get_all_commands() {
-        let service = CommandService::new("test.sqlite").await.unwrap();
+    /// The stored variant sharing the alias, environment and operating system of `command`
+    pub async fn find_variant(
+        &self,
+        command: &Command,
+    ) -> Result<Option<Command>, CommandServiceError> {
+        let variants = self
+            .storage_manager
+            .get_by_alias_and_env(&command.alias, command.environment.as_deref())
+            .await
+            .map_err(CommandServiceError::StorageManagerGetCommand)?;
 
-        let _ = service
-            .insert_command("test command arguments", "my_test", None)
+        variants
+            .into_iter()
+            .find(|variant| variant.os == command.os)
+            .map(|variant| self.reveal(variant))
+            .transpose()
+    }
+
+    /// Stores every imported command that is valid and not stored yet, logging the others
+    pub async fn import_commands(&self, commands: Vec<Command>) -> ImportReport {
+        let mut report = ImportReport::default();
+        for command in commands {
+            report.record(self.import_command(command).await);
+        }
+
+        report
+    }
+
+    /// Stores an imported command unless it is invalid or its alias or command string is
+    /// already stored, logging why it was not
+    pub async fn import_command(&self, command: Command) -> ImportOutcome {
+        let alias = command.alias.clone();
+        match self.add_command(command).await {
+            Ok(_) => ImportOutcome::Inserted,
+            Err(CommandServiceError::StorageManagerInsertCommand(e)) if e.is_unique_violation() => {
+                info!("Skipping command {alias} because it is already stored");
+                ImportOutcome::Duplicate
+            }
+            Err(e) => {
+                warn!("Could not insert command {alias} because: {e:?}");
+                ImportOutcome::Failed
+            }
+        }
+    }
+
+    /// Stores an imported command as given, its executable taken from the command string
+    pub async fn add_command(&self, command: Command) -> Result<Command, CommandServiceError> {
+        let executable = normalized_executable(&command.command)?;
+        let command = Command {
+            description: self.description_or_default(
+                command.description,
+                &executable,
+                &command.alias,
+            ),
+            executable,
+            ..command
+        };
+        command.validate(self.max_alias_len)?;
+
+        self.restore_command(command.clone()).await?;
+
+        Ok(command)
+    }
+
+    /// Commands whose executable starts with `prefix`, e.g. `kube` for `kubectl` and `kubectx`
+    pub async fn get_commands_by_executable_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<Command>, CommandServiceError> {
+        let commands = self
+            .storage_manager
+            .get_commands_by_executable_prefix(prefix)
             .await
-            .unwrap();
+            .map_err(CommandServiceError::StorageManagerSearch)?;
 
-        let _ = service
-            .insert_command("test2 command arguments", "my_test2", None)
+        Ok(self.reveal_all(commands))
+    }
+
+    /// Sensitive commands are only found by their alias or description, their command string
+    /// is encrypted in the db
+    pub async fn search_commands(
+        &self,
+        query: &str,
+        limit: Option<u32>,
+        offset: Option<u32>,
+    ) -> Result<Vec<Command>, CommandServiceError> {
+        let commands = self
+            .storage_manager
+            .search_commands(query, limit, offset)
             .await
-            .unwrap();
+            .map_err(CommandServiceError::StorageManagerSearch)?;
 
-        let res = service.get_all_commands().await.unwrap();
+        Ok(self.reveal_all(commands))
+    }
 
-        assert_eq!(res.len(), 2);
-        std::fs::remove_file("test.sqlite").unwrap();
+    pub async fn count_search_matches(&self, query: &str) -> Result<i64, CommandServiceError> {
+        self.storage_manager
+            .count_search_matches(query)
+            .await
+            .map_err(CommandServiceError::StorageManagerSearch)
     }
 
-    #[tokio::test]
-    #[serial]
-    async fn test_get_command() {
-        let service = CommandService::new("test.sqlite").await.unwrap();
+    /// Gets the command stored with both this command string and this alias, a pair that
+    /// does not belong to the same row is `NotFound`
+    pub async fn get_command(
+        &self,
+        command: &str,
+        alias: &str,
+    ) -> Result<Command, CommandServiceError> {
+        let executable = normalized_executable(command)?;
 
-        let command = service
-            .insert_command("test command arguments", "my_test", None)
-            .await
-            .unwrap();
+        let lookup = Command::new(executable, command.to_string(), alias.to_string(), None);
 
-        let res = service
-            .get_command(&command.command, &command.alias)
+        let found = self
+            .storage_manager
+            .get_command(lookup)
             .await
-            .unwrap();
+            .map_err(CommandServiceError::StorageManagerGetCommand)?
+            .ok_or_else(|| CommandServiceError::NotFound {
+                command: command.to_string(),
+                alias: alias.to_string(),
+            })?;
 
-        assert_eq!(res.alias, "my_test".to_string());
-        std::fs::remove_file("test.sqlite").unwrap();
+        self.reveal(found)
     }
 
-    #[tokio::test]
-    #[serial]
-    async fn test_delete_command() {
-        let service = CommandService::new("test.sqlite").await.unwrap();
+    /// Replaces the description of `command` once it passes the validation
+    pub async fn update_description(
+        &self,
+        command: &Command,
+        description: Option<String>,
+    ) -> Result<Command, CommandServiceError> {
+        let updated = Command {
+            description,
+            ..command.clone()
+        };
+        updated.validate(self.max_alias_len)?;
 
-        let command = service
-            .insert_command("test command arguments", "my_test", None)
+        self.storage_manager
+            .update_description(&self.sealed_text(&updated)?, updated.description.as_deref())
             .await
-            .unwrap();
+            .map_err(CommandServiceError::StorageManagerUpdateCommand)?;
 
-        let res = service
-            .delete_command(&command.command, &command.alias, command.description)
+        Ok(updated)
+    }
+
+    /// Replaces the command string of `command` once it passes the validation, filing it under
+    /// the executable of the new string
+    pub async fn update_command(
+        &self,
+        command: &Command,
+        new_command: &str,
+    ) -> Result<Command, CommandServiceError> {
+        let updated = Command {
+            executable: normalized_executable(new_command)?,
+            command: new_command.to_string(),
+            ..command.clone()
+        };
+        updated.validate(self.max_alias_len)?;
+
+        self.storage_manager
+            .update_command(&self.sealed_text(command)?, &self.seal(updated.clone())?)
             .await
-            .unwrap();
+            .map_err(CommandServiceError::StorageManagerUpdateCommand)?;
 
-        assert_eq!(res.alias, "my_test".to_string());
-        std::fs::remove_file("test.sqlite").unwrap();
+        Ok(updated)
+    }
+
+    /// Adds `tag` to every variant of each of `aliases` that does not have it yet.
+    /// Returns the number of commands that were tagged.
+    pub async fn add_tag_to_many(
+        &self,
+        aliases: &[String],
+        tag: &str,
+    ) -> Result<usize, CommandServiceError> {
+        let mut tagged = 0;
+
+        for mut command in self.get_all_commands_including_disabled().await? {
+            if !aliases.contains(&command.alias) || command.tags.iter().any(|t| t == tag) {
+                continue;
+            }
+
+            command.tags.0.push(tag.to_string());
+            self.storage_manager
+                .update_tags(&self.sealed_text(&command)?, &command.tags)
+                .await
+                .map_err(CommandServiceError::StorageManagerUpdateCommand)?;
+            tagged += 1;
+        }
+
+        Ok(tagged)
+    }
+
+    /// Tags every command with its executable, unless it has that tag already. Returns the
+    /// number of commands that were tagged.
+    pub async fn auto_tag_by_executable(&self) -> Result<usize, CommandServiceError> {
+        self.auto_tag(|command| Some(command.executable.clone()))
+            .await
+    }
+
+    /// Tags every command with the group `groups` maps its executable to, unless it has that
+    /// tag already or its executable is in no group. Returns the number of commands that were
+    /// tagged.
+    pub async fn auto_tag_by_group(
+        &self,
+        groups: &HashMap<String, String>,
+    ) -> Result<usize, CommandServiceError> {
+        self.auto_tag(|command| classify_group(&command.executable, groups))
+            .await
+    }
+
+    async fn auto_tag(
+        &self,
+        tag_of: impl Fn(&Command) -> Option<String>,
+    ) -> Result<usize, CommandServiceError> {
+        let mut tagged = 0;
+
+        for mut command in self.get_all_commands_including_disabled().await? {
+            let Some(tag) = tag_of(&command) else {
+                continue;
+            };
+            if command.tags.iter().any(|t| *t == tag) {
+                continue;
+            }
+
+            command.tags.0.push(tag);
+            self.storage_manager
+                .update_tags(&self.sealed_text(&command)?, &command.tags)
+                .await
+                .map_err(CommandServiceError::StorageManagerUpdateCommand)?;
+            tagged += 1;
+        }
+
+        Ok(tagged)
+    }
+
+    /// Gives every variant of `alias` the alias `new_alias`, which must not be taken yet. The
+    /// `@alias` references of the other commands follow the rename.
+    pub async fn rename_alias(
+        &self,
+        alias: &str,
+        new_alias: &str,
+    ) -> Result<(), CommandServiceError> {
+        let stored = self.get_all_commands_including_disabled().await?;
+        check_new_alias(new_alias, stored.iter(), self.max_alias_len)?;
+
+        let renamed = self
+            .storage_manager
+            .rename_alias(alias, new_alias)
+            .await
+            .map_err(CommandServiceError::StorageManagerUpdateCommand)?;
+
+        if renamed == 0 {
+            return Err(CommandServiceError::AliasNotFound(alias.to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_pinned(&self, alias: &str, pinned: bool) -> Result<(), CommandServiceError> {
+        self.storage_manager
+            .set_pinned(alias, pinned)
+            .await
+            .map_err(CommandServiceError::StorageManagerUpdateCommand)
+    }
+
+    /// Labels every variant of `alias`, `None` removes the label
+    pub async fn set_label(
+        &self,
+        alias: &str,
+        label: Option<Label>,
+    ) -> Result<(), CommandServiceError> {
+        self.storage_manager
+            .set_label(alias, label)
+            .await
+            .map_err(CommandServiceError::StorageManagerUpdateCommand)
+    }
+
+    /// Records the exit code `command` is expected to end with, `None` removes the expectation
+    pub async fn set_expected_exit(
+        &self,
+        command: &Command,
+        expected_exit: Option<i32>,
+    ) -> Result<(), CommandServiceError> {
+        self.storage_manager
+            .set_expected_exit(&self.sealed_text(command)?, expected_exit)
+            .await
+            .map_err(CommandServiceError::StorageManagerUpdateCommand)
+    }
+
+    /// Recomputes the executable of every stored command and updates the ones that were
+    /// filed under the wrong one. Returns the (alias, old, new) executable of each change.
+    pub async fn reclassify_all(
+        &self,
+    ) -> Result<Vec<(String, String, String)>, CommandServiceError> {
+        let mut changes = Vec::new();
+
+        for command in self.get_all_commands_including_disabled().await? {
+            let executable = normalized_executable(&command.command)?;
+            if executable == command.executable {
+                continue;
+            }
+
+            self.storage_manager
+                .update_executable(&self.sealed_text(&command)?, &executable)
+                .await
+                .map_err(CommandServiceError::StorageManagerUpdateCommand)?;
+
+            changes.push((command.alias, command.executable, executable));
+        }
+
+        Ok(changes)
+    }
+
+    /// Writes every command as a recipe of the justfile at `path`
+    pub async fn export_justfile(&self, path: &str) -> Result<(), CommandServiceError> {
+        let commands = self.get_all_commands().await?;
+
+        export_commands(&commands, ExportFormat::Justfile, Path::new(path))
+    }
+
+    /// The enabled commands ordered by alias, narrowed down to those tagged `tag` and filed
+    /// under `executable` when given. The db looks the executable up, the commands without
+    /// the tag are dropped as they are read.
+    pub async fn get_commands_matching(
+        &self,
+        tag: Option<&str>,
+        executable: Option<&str>,
+    ) -> Result<Vec<Command>, CommandServiceError> {
+        let tagged =
+            |command: &Command| tag.is_none_or(|tag| command.tags.iter().any(|t| t == tag));
+
+        let commands: Vec<Command> = match executable {
+            Some(executable) => self
+                .storage_manager
+                .get_commands_by_executable(executable.to_string())
+                .await
+                .map(|commands| {
+                    commands
+                        .into_iter()
+                        .filter(|command| command.enabled && tagged(command))
+                        .collect()
+                }),
+            None => {
+                self.storage_manager
+                    .stream_all_commands()
+                    .try_filter(|command| future::ready(tagged(command)))
+                    .try_collect()
+                    .await
+            }
+        }
+        .map_err(CommandServiceError::StorageManagerGetAll)?;
+
+        Ok(self.reveal_all(commands))
+    }
+
+    /// Writes the commands tagged `tag` and filed under `executable` to `path` in `format`,
+    /// every command when neither is given. Returns the number of commands written.
+    pub async fn export_matching(
+        &self,
+        format: ExportFormat,
+        path: &str,
+        tag: Option<&str>,
+        executable: Option<&str>,
+    ) -> Result<usize, CommandServiceError> {
+        let commands = self.get_commands_matching(tag, executable).await?;
+        export_commands(&commands, format, Path::new(path))?;
+
+        Ok(commands.len())
+    }
+
+    /// Writes a markdown cheatsheet of every command to `path`, with a section per executable
+    pub async fn export_markdown(&self, path: &str) -> Result<(), CommandServiceError> {
+        let commands = self.get_all_commands().await?;
+
+        export_commands(&commands, ExportFormat::Markdown, Path::new(path))
+    }
+
+    pub async fn backup_to(&self, path: &str) -> Result<(), CommandServiceError> {
+        self.storage_manager
+            .backup_to(path)
+            .await
+            .map_err(CommandServiceError::StorageManagerBackup)
+    }
+
+    /// Stores the commands of a cheat sheet under generated aliases, returning how many were
+    /// stored. Commands already stored are skipped.
+    pub async fn import_from_cheat(&self, path: &Path) -> Result<usize, CommandServiceError> {
+        let text = std::fs::read_to_string(path).map_err(CommandServiceError::ReadCheat)?;
+
+        let mut taken: HashSet<String> = self
+            .get_all_commands_including_disabled()
+            .await?
+            .into_iter()
+            .map(|command| command.alias)
+            .collect();
+        let mut imported = 0;
+
+        for entry in parse_cheat(&text) {
+            let alias = generate_unique_alias(&entry.command, &taken);
+            let command = Command {
+                description: entry.description,
+                ..Command::new(String::new(), entry.command, alias.clone(), None)
+            };
+
+            match self.add_command(command).await {
+                Ok(_) => {
+                    taken.insert(alias);
+                    imported += 1;
+                }
+                Err(e) => warn!("Skipping the cheat sheet command {alias} because: {e}"),
+            }
+        }
+
+        info!("Imported {imported} commands from {path:?}");
+        Ok(imported)
+    }
+
+    /// Stores the aliases defined in the shell rc file at `path`, e.g. `~/.bash_aliases`, under
+    /// their own names. Other lines of the file are ignored.
+    pub async fn import_shell_aliases(
+        &self,
+        path: &Path,
+    ) -> Result<ImportReport, CommandServiceError> {
+        let text = std::fs::read_to_string(path).map_err(CommandServiceError::ReadAliases)?;
+
+        let commands = parse_shell_aliases(&text)
+            .into_iter()
+            .map(|alias| Command::new(String::new(), alias.command, alias.name, None))
+            .collect();
+        let report = self.import_commands(commands).await;
+
+        info!("Imported {path:?}: {report}");
+        Ok(report)
+    }
+
+    /// Hides every variant of `alias` in the archive, where it is purged once it is older than
+    /// the retention period
+    pub async fn archive(&self, alias: &str) -> Result<(), CommandServiceError> {
+        self.set_archived(alias, true).await
+    }
+
+    /// Takes every variant of `alias` out of the archive and shows it again
+    pub async fn unarchive(&self, alias: &str) -> Result<(), CommandServiceError> {
+        self.set_archived(alias, false).await
+    }
+
+    async fn set_archived(&self, alias: &str, archived: bool) -> Result<(), CommandServiceError> {
+        let changed = self
+            .storage_manager
+            .set_archived(alias, archived)
+            .await
+            .map_err(CommandServiceError::StorageManagerUpdateCommand)?;
+
+        if changed == 0 {
+            return Err(CommandServiceError::AliasNotFound(alias.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the commands archived more than `days` days ago, returning how many. A period
+    /// too long to compute keeps every command.
+    pub async fn purge_archived_older_than(&self, days: i64) -> Result<u64, CommandServiceError> {
+        if days < 0 {
+            return Err(CommandServiceError::NegativeRetention(days));
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs() as i64);
+
+        self.storage_manager
+            .purge_archived_before(now.saturating_sub(days.saturating_mul(86_400)))
+            .await
+            .map_err(CommandServiceError::StorageManagerDeleteCommand)
+    }
+
+    /// Shrinks the database after deletes, reporting its size before and after
+    pub async fn vacuum(&self) -> Result<VacuumSummary, CommandServiceError> {
+        let before = self
+            .storage_manager
+            .size()
+            .await
+            .map_err(CommandServiceError::StorageManagerVacuum)?;
+        self.storage_manager
+            .vacuum()
+            .await
+            .map_err(CommandServiceError::StorageManagerVacuum)?;
+        let after = self
+            .storage_manager
+            .size()
+            .await
+            .map_err(CommandServiceError::StorageManagerVacuum)?;
+
+        info!("Vacuumed the database from {before} to {after} bytes");
+        Ok(VacuumSummary { before, after })
+    }
+
+    /// Stores the commands of the database at `other_db_url`, settling the ones whose alias
+    /// (within an environment and operating system) or command string is already stored
+    /// according to `on_conflict`. A command string can only be stored once, so renaming skips
+    /// identical commands. Databases holding sensitive commands are refused, their rows are
+    /// sealed with a passphrase this database does not know.
+    pub async fn import_from_db(
+        &self,
+        other_db_url: &str,
+        on_conflict: ConflictPolicy,
+    ) -> Result<MergeSummary, CommandServiceError> {
+        if !db_file_path(other_db_url).is_file() {
+            return Err(CommandServiceError::MissingDatabase(
+                other_db_url.to_string(),
+            ));
+        }
+
+        let other = CommandService::open_read_only(other_db_url).await?;
+        if other.has_sensitive_commands().await? {
+            return Err(CommandServiceError::SensitiveMerge(
+                other_db_url.to_string(),
+            ));
+        }
+
+        let mut stored = self.get_all_commands_including_disabled().await?;
+        let mut summary = MergeSummary::default();
+
+        for command in other.get_all_commands_including_disabled().await? {
+            let same_command = |existing: &Command| existing.command == command.command;
+            let same_alias = |existing: &Command| {
+                existing.alias == command.alias
+                    && existing.environment == command.environment
+                    && existing.os == command.os
+            };
+            let conflicts: Vec<Command> = stored
+                .iter()
+                .filter(|existing| same_command(existing) || same_alias(existing))
+                .cloned()
+                .collect();
+
+            let merged = match on_conflict {
+                _ if conflicts.is_empty() => {
+                    summary.inserted += 1;
+                    command
+                }
+                ConflictPolicy::Skip => {
+                    summary.skipped += 1;
+                    continue;
+                }
+                ConflictPolicy::Replace => {
+                    // Deleted by variant, the command string of a sensitive row is sealed at rest
+                    for existing in conflicts {
+                        self.storage_manager
+                            .delete_variant(&existing)
+                            .await
+                            .map_err(CommandServiceError::StorageManagerDeleteCommand)?;
+                        stored.retain(|stored| {
+                            stored.alias != existing.alias
+                                || stored.environment != existing.environment
+                                || stored.os != existing.os
+                        });
+                    }
+                    summary.replaced += 1;
+                    command
+                }
+                ConflictPolicy::Rename if conflicts.iter().any(same_command) => {
+                    summary.skipped += 1;
+                    continue;
+                }
+                ConflictPolicy::Rename => {
+                    summary.renamed += 1;
+                    Command {
+                        alias: free_alias(&command, &stored, self.max_alias_len),
+                        ..command
+                    }
+                }
+            };
+
+            self.restore_command(merged.clone()).await?;
+            stored.push(merged);
+        }
+
+        info!("Merged {other_db_url}: {summary:?}");
+        Ok(summary)
+    }
+
+    /// Stores an imported command, replacing the variant already stored for its alias,
+    /// environment and operating system
+    pub async fn upsert_command(&self, command: Command) -> Result<Command, CommandServiceError> {
+        let command = Command {
+            executable: normalized_executable(&command.command)?,
+            ..command
+        };
+        command.validate(self.max_alias_len)?;
+
+        self.storage_manager
+            .replace_variant(&self.seal(command.clone())?)
+            .await
+            .map_err(CommandServiceError::StorageManagerInsertCommand)?;
+
+        Ok(command)
+    }
+
+    /// Deletes the variant of `command` for its environment and operating system
+    pub async fn delete_variant(&self, command: &Command) -> Result<(), CommandServiceError> {
+        self.storage_manager
+            .delete_variant(command)
+            .await
+            .map_err(CommandServiceError::StorageManagerDeleteCommand)
+    }
+
+    /// Stores a command exactly as given, e.g. to bring back a deleted one
+    pub async fn restore_command(&self, command: Command) -> Result<(), CommandServiceError> {
+        self.storage_manager
+            .insert_command(self.seal(command)?)
+            .await
+            .map_err(CommandServiceError::StorageManagerInsertCommand)?;
+
+        Ok(())
+    }
+
+    pub async fn delete_command(
+        &self,
+        command: &str,
+        alias: &str,
+        description: Option<String>,
+    ) -> Result<Command, CommandServiceError> {
+        let executable = normalized_executable(command)?;
+
+        let command = Command::new(
+            executable,
+            command.to_string(),
+            alias.to_string(),
+            description,
+        );
+        let mut deleted = self
+            .storage_manager
+            .delete_command(command.clone())
+            .await
+            .map_err(CommandServiceError::StorageManagerDeleteCommand)?;
+
+        // The command string does not tell whether it is stored encrypted
+        if let (0, Some(cipher)) = (deleted, &self.cipher) {
+            let sealed = Command {
+                command: cipher.seal(&command.command)?,
+                ..command.clone()
+            };
+            deleted = self
+                .storage_manager
+                .delete_command(sealed)
+                .await
+                .map_err(CommandServiceError::StorageManagerDeleteCommand)?;
+        }
+
+        if deleted == 0 {
+            return Err(CommandServiceError::NotFound {
+                command: command.command,
+                alias: command.alias,
+            });
+        }
+
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serial_test::serial;
+    use sqlx::SqlitePool;
+
+    use super::{
+        parse_executable, CommandService, CommandServiceError, ConflictPolicy, ExportFormat,
+        ImportReport, MergeSummary,
+    };
+    use crate::model::command::{Command, Examples, Label};
+    use crate::model::group::default_executable_groups;
+    use crate::storage::command_storage::StorageOptions;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_service_construction() {
+        let _ = CommandService::new("test.sqlite").await.unwrap();
+
+        assert!(std::fs::metadata("test.sqlite").unwrap().is_file());
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_insertion() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let inserted = service
+            .insert_command("test command arguments", "my_test", None)
+            .await
+            .unwrap();
+
+        assert_eq!(inserted.executable, "test".to_string());
+        assert_eq!(inserted.command, "test command arguments".to_string());
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_ensure_command_creates() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let (command, inserted) = service
+            .ensure_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        assert!(inserted);
+        assert_eq!(command.alias, "git_pull".to_string());
+        assert!(service
+            .command_exists("git pull", "git_pull")
+            .await
+            .unwrap());
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_ensure_command_existing() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_command("git pull", "git_pull", Some("Original".to_string()))
+            .await
+            .unwrap();
+
+        let (command, inserted) = service
+            .ensure_command("git pull", "git_pull", Some("Changed".to_string()))
+            .await
+            .unwrap();
+
+        assert!(!inserted);
+        assert_eq!(command.description, Some("Original".to_string()));
+
+        let all = service.get_all_commands().await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].description, Some("Original".to_string()));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_multi_line_command() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let multi_line = "for f in *.log; do\n  gzip \"$f\"\ndone";
+        let _ = service
+            .insert_command(multi_line, "gzip_logs", None)
+            .await
+            .unwrap();
+
+        let res = service.get_command_by_alias("gzip_logs").await.unwrap();
+
+        assert_eq!(res.command, multi_line.to_string());
+        assert_eq!(res.command.lines().count(), 3);
+        assert_eq!(res.executable, "for".to_string());
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_import_from_cheat() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git log", "git_status", None)
+            .await
+            .unwrap();
+        std::fs::write(
+            "test_cheat.txt",
+            "# Short status\ngit status --short\n\n# Everything\ngit status\n",
+        )
+        .unwrap();
+
+        let imported = service
+            .import_from_cheat(Path::new("test_cheat.txt"))
+            .await
+            .unwrap();
+        assert_eq!(imported, 2);
+
+        let everything = service.get_command_by_alias("git_status_2").await.unwrap();
+        assert_eq!(everything.command, "git status");
+        assert_eq!(everything.description.as_deref(), Some("Everything"));
+        let short = service
+            .get_command_by_alias("git_status_short")
+            .await
+            .unwrap();
+        assert_eq!(short.description.as_deref(), Some("Short status"));
+
+        std::fs::remove_file("test_cheat.txt").unwrap();
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_vacuum() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        for i in 0..200 {
+            let _ = service
+                .insert_command(
+                    &format!("echo {i} {}", "x".repeat(500)),
+                    &format!("echo_{i}"),
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        for i in 0..200 {
+            let _ = service
+                .delete_command(&format!("echo {i} {}", "x".repeat(500)), "", None)
+                .await
+                .unwrap();
+        }
+
+        let summary = service.vacuum().await.unwrap();
+        assert!(summary.after > 0);
+        assert!(summary.after < summary.before);
+        assert_eq!(
+            std::fs::metadata("test.sqlite").unwrap().len(),
+            summary.after
+        );
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_backup() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("ls -a", "ls_all", None)
+            .await
+            .unwrap();
+
+        service.backup_to("test_backup.sqlite").await.unwrap();
+
+        let backup = CommandService::new("test_backup.sqlite").await.unwrap();
+        let mut aliases: Vec<String> = backup
+            .get_all_commands()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|command| command.alias)
+            .collect();
+        aliases.sort();
+
+        assert_eq!(aliases, vec!["git_pull".to_string(), "ls_all".to_string()]);
+        std::fs::remove_file("test.sqlite").unwrap();
+        std::fs::remove_file("test_backup.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_environment_variants() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_variant(
+                "kubectl --context prod get pods",
+                "pods",
+                None,
+                Some("prod".to_string()),
+            )
+            .await
+            .unwrap();
+        let _ = service
+            .insert_variant(
+                "kubectl --context staging get pods",
+                "pods",
+                None,
+                Some("staging".to_string()),
+            )
+            .await
+            .unwrap();
+
+        let prod = service
+            .get_by_alias_and_env("pods", Some("prod"))
+            .await
+            .unwrap();
+        assert_eq!(prod.command, "kubectl --context prod get pods".to_string());
+
+        let staging = service
+            .get_by_alias_and_env("pods", Some("staging"))
+            .await
+            .unwrap();
+        assert_eq!(staging.environment, Some("staging".to_string()));
+
+        // The same alias can not be used twice in one environment
+        assert!(service
+            .insert_variant("kubectl get pods", "pods", None, Some("prod".to_string()))
+            .await
+            .is_err());
+        assert!(matches!(
+            service.get_by_alias_and_env("pods", None).await,
+            Err(CommandServiceError::AliasNotFound(alias)) if alias == "pods"
+        ));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[test]
+    fn test_parse_executable() {
+        assert_eq!(parse_executable("git pull").unwrap(), "git");
+        assert_eq!(parse_executable("sudo apt update").unwrap(), "apt");
+        assert_eq!(parse_executable("sudo -u root apt update").unwrap(), "apt");
+        assert_eq!(parse_executable("sudo -n apt update").unwrap(), "apt");
+        assert_eq!(parse_executable("nice -n 10 make").unwrap(), "make");
+        assert_eq!(
+            parse_executable("RUST_LOG=debug cargo run").unwrap(),
+            "cargo"
+        );
+        assert_eq!(parse_executable("sudo").unwrap(), "sudo");
+        assert!(parse_executable("   ").is_err());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reclassify_all() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        // Stored by an older version which filed it under sudo
+        let misfiled = Command::new(
+            "sudo".to_string(),
+            "sudo apt update".to_string(),
+            "apt_update".to_string(),
+            None,
+        );
+        service
+            .storage_manager
+            .insert_command(misfiled)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        let changes = service.reclassify_all().await.unwrap();
+        assert_eq!(
+            changes,
+            vec![(
+                "apt_update".to_string(),
+                "sudo".to_string(),
+                "apt".to_string()
+            )]
+        );
+
+        let res = service.get_command_by_alias("apt_update").await.unwrap();
+        assert_eq!(res.executable, "apt".to_string());
+        assert!(service.reclassify_all().await.unwrap().is_empty());
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_get_all_commands() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_command("test command arguments", "my_test", None)
+            .await
+            .unwrap();
+
+        let _ = service
+            .insert_command("test2 command arguments", "my_test2", None)
+            .await
+            .unwrap();
+
+        let res = service.get_all_commands().await.unwrap();
+
+        assert_eq!(res.len(), 2);
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_get_command() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let command = service
+            .insert_command("test command arguments", "my_test", None)
+            .await
+            .unwrap();
+
+        let res = service
+            .get_command(&command.command, &command.alias)
+            .await
+            .unwrap();
+
+        assert_eq!(res.alias, "my_test".to_string());
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_delete_missing_command() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        let res = service.delete_command("git push", "git_push", None).await;
+        assert!(matches!(res, Err(CommandServiceError::NotFound { .. })));
+        assert_eq!(service.get_all_commands().await.unwrap().len(), 1);
+
+        let _ = service
+            .delete_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        let res = service.delete_command("git pull", "git_pull", None).await;
+        assert!(matches!(res, Err(CommandServiceError::NotFound { .. })));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_toggle_enabled() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        for (command, alias) in [("git pull", "git_pull"), ("git push", "git_push")] {
+            let _ = service.insert_command(command, alias, None).await.unwrap();
+        }
+
+        service.toggle_enabled("git_pull").await.unwrap();
+        let aliases = |commands: Vec<Command>| -> Vec<String> {
+            commands.into_iter().map(|command| command.alias).collect()
+        };
+        assert_eq!(
+            aliases(service.get_all_commands().await.unwrap()),
+            ["git_push"]
+        );
+        assert_eq!(
+            aliases(service.get_all_commands_including_disabled().await.unwrap()),
+            ["git_pull", "git_push"]
+        );
+        assert!(service
+            .search_commands("pull", None, None)
+            .await
+            .unwrap()
+            .is_empty());
+
+        service.toggle_enabled("git_pull").await.unwrap();
+        assert_eq!(service.get_all_commands().await.unwrap().len(), 2);
+        assert!(matches!(
+            service.toggle_enabled("git_fetch").await,
+            Err(CommandServiceError::AliasNotFound(alias)) if alias == "git_fetch"
+        ));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_purge_archived_older_than() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        for (command, alias) in [
+            ("git pull", "git_pull"),
+            ("git push", "git_push"),
+            ("git fetch", "git_fetch"),
+        ] {
+            let _ = service.insert_command(command, alias, None).await.unwrap();
+        }
+        service.archive("git_pull").await.unwrap();
+        service.archive("git_push").await.unwrap();
+        // Disabling only hides a command, it is never purged
+        service.toggle_enabled("git_fetch").await.unwrap();
+        assert!(service.get_all_commands().await.unwrap().is_empty());
+
+        // Backdate the archiving of git_pull past the retention window
+        let pool = SqlitePool::connect("sqlite://test.sqlite").await.unwrap();
+        sqlx::query("UPDATE commands SET archived_at = archived_at - 31 * 86400 WHERE alias=?")
+            .bind("git_pull")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        assert!(matches!(
+            service.purge_archived_older_than(-1).await,
+            Err(CommandServiceError::NegativeRetention(-1))
+        ));
+        assert_eq!(
+            service.purge_archived_older_than(i64::MAX).await.unwrap(),
+            0
+        );
+        assert_eq!(service.purge_archived_older_than(30).await.unwrap(), 1);
+        let aliases: Vec<String> = service
+            .get_all_commands_including_disabled()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|command| command.alias)
+            .collect();
+        assert_eq!(aliases, ["git_fetch", "git_push"]);
+
+        service.unarchive("git_push").await.unwrap();
+        assert_eq!(service.get_all_commands().await.unwrap().len(), 1);
+        assert!(matches!(
+            service.archive("git_pull").await,
+            Err(CommandServiceError::AliasNotFound(alias)) if alias == "git_pull"
+        ));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_import_report() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        let command = |command: &str, alias: &str| {
+            Command::new(String::new(), command.to_string(), alias.to_string(), None)
+        };
+        let report = service
+            .import_commands(vec![
+                command("git push", "git_push"),
+                command("git pull", "git_pull"),
+                command("git fetch", ""),
+            ])
+            .await;
+
+        assert_eq!(
+            report,
+            ImportReport {
+                inserted: 1,
+                skipped_duplicates: 1,
+                failed: 1,
+            }
+        );
+        assert_eq!(
+            report.to_string(),
+            "1 inserted, 1 duplicates skipped, 1 failed"
+        );
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_sensitive_round_trip() {
+        let mut service = CommandService::new("test.sqlite").await.unwrap();
+        service.unlock("hunter2").await.unwrap();
+
+        let token = Command {
+            sensitive: true,
+            ..Command::new(
+                String::new(),
+                "curl -H 'Authorization: Bearer abc' https://api.example.com".to_string(),
+                "api".to_string(),
+                None,
+            )
+        };
+        service.add_command(token.clone()).await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        service.close().await;
+
+        // Only the sensitive command is encrypted at rest
+        let pool = SqlitePool::connect("sqlite://test.sqlite").await.unwrap();
+        let stored: Vec<String> = sqlx::query_scalar("SELECT command FROM commands ORDER BY alias")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        assert!(!stored[0].contains("Bearer"));
+        assert_eq!(stored[1], "git pull");
+
+        let mut service = CommandService::new("test.sqlite").await.unwrap();
+        assert!(service.has_sensitive_commands().await.unwrap());
+        assert!(matches!(
+            service.unlock("hunter3").await,
+            Err(CommandServiceError::Crypto(_))
+        ));
+
+        service.unlock("hunter2").await.unwrap();
+        let api = service.get_command_by_alias("api").await.unwrap();
+        assert_eq!(api.command, token.command);
+        service.record_use(&api).await.unwrap();
+        service
+            .delete_command(&api.command, &api.alias, None)
+            .await
+            .unwrap();
+
+        // A row that fails to decrypt is listed encrypted instead of failing the whole list
+        let pool = SqlitePool::connect("sqlite://test.sqlite").await.unwrap();
+        sqlx::query("UPDATE commands SET command='enc:v1:00', sensitive=1 WHERE alias='git_pull'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        let commands = service.get_all_commands().await.unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].command, "enc:v1:00");
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_get_command_mismatched_pair() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("git push", "git_push", None)
+            .await
+            .unwrap();
+
+        let res = service.get_command("git pull", "git_push").await;
+        assert!(matches!(res, Err(CommandServiceError::NotFound { .. })));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_get_command_by_alias() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_command("test command arguments", "my_test", None)
+            .await
+            .unwrap();
+
+        let res = service.get_command_by_alias("my_test").await.unwrap();
+
+        assert_eq!(res.command, "test command arguments".to_string());
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_search_commands() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        let _ = service
+            .insert_command("ls -a", "ls_all", Some("List everything".to_string()))
+            .await
+            .unwrap();
+
+        let res = service.search_commands("git", None, None).await.unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].alias, "git_pull".to_string());
+
+        let res = service
+            .search_commands("everything", None, None)
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].alias, "ls_all".to_string());
+
+        // Wildcards are matched literally
+        assert!(service
+            .search_commands("%", None, None)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(service.count_search_matches("_").await.unwrap(), 2);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_search_limit() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        for i in 0..100 {
+            let _ = service
+                .insert_command(&format!("echo {i}"), &format!("echo_{i}"), None)
+                .await
+                .unwrap();
+        }
+
+        let res = service
+            .search_commands("echo", Some(10), None)
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 10);
+
+        // Ordered by alias within the executable: echo_0, echo_1, echo_10, echo_11, ...
+        let page = service
+            .search_commands("echo", Some(2), Some(1))
+            .await
+            .unwrap();
+        let aliases: Vec<&str> = page.iter().map(|command| command.alias.as_str()).collect();
+        assert_eq!(aliases, vec!["echo_1", "echo_10"]);
+        assert_eq!(service.count_search_matches("echo").await.unwrap(), 100);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_delete_command() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let command = service
+            .insert_command("test command arguments", "my_test", None)
+            .await
+            .unwrap();
+
+        let res = service
+            .delete_command(&command.command, &command.alias, command.description)
+            .await
+            .unwrap();
+
+        assert_eq!(res.alias, "my_test".to_string());
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_mixed_case_executables_are_filed_together() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_command("Git status", "git_status", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        let commands = service.get_all_commands().await.unwrap();
+        assert_eq!(commands.len(), 2);
+        assert!(commands.iter().all(|command| command.executable == "git"));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_set_pinned() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        service.set_pinned("git_pull", true).await.unwrap();
+        assert!(
+            service
+                .get_command_by_alias("git_pull")
+                .await
+                .unwrap()
+                .pinned
+        );
+
+        service.set_pinned("git_pull", false).await.unwrap();
+        assert!(
+            !service
+                .get_command_by_alias("git_pull")
+                .await
+                .unwrap()
+                .pinned
+        );
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_import_shell_aliases() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git status", "gs", None)
+            .await
+            .unwrap();
+        std::fs::write(
+            "test_aliases.sh",
+            "# My aliases
+             alias gs='git status'
+             alias gl='git log --oneline'
+             umask 022
+             ll='ls -la'
+",
+        )
+        .unwrap();
+
+        let report = service
+            .import_shell_aliases(Path::new("test_aliases.sh"))
+            .await
+            .unwrap();
+        assert_eq!(report.inserted, 2);
+        assert_eq!(report.skipped_duplicates, 1);
+
+        let gl = service.get_command_by_alias("gl").await.unwrap();
+        assert_eq!(gl.command, "git log --oneline");
+        assert_eq!(gl.executable, "git");
+        let ll = service.get_command_by_alias("ll").await.unwrap();
+        assert_eq!(ll.executable, "ls");
+
+        std::fs::remove_file("test_aliases.sh").unwrap();
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_configured_alias_limit() {
+        let options = StorageOptions {
+            max_alias_len: 10,
+            ..StorageOptions::default()
+        };
+        let service = CommandService::with_options("test.sqlite", &options)
+            .await
+            .unwrap();
+
+        let fitting = Command::new(String::new(), "git pull".to_string(), "a".repeat(10), None);
+        let stored = service.add_command(fitting).await.unwrap();
+        let too_long = Command {
+            alias: "a".repeat(11),
+            ..stored.clone()
+        };
+
+        assert!(matches!(
+            service.add_command(too_long.clone()).await,
+            Err(CommandServiceError::Invalid(_))
+        ));
+        assert!(matches!(
+            service.upsert_command(too_long.clone()).await,
+            Err(CommandServiceError::Invalid(_))
+        ));
+        assert!(matches!(
+            service.update_description(&too_long, None).await,
+            Err(CommandServiceError::Invalid(_))
+        ));
+        assert!(matches!(
+            service.update_command(&too_long, "git pull --rebase").await,
+            Err(CommandServiceError::Invalid(_))
+        ));
+        assert_eq!(service.get_all_commands().await.unwrap(), [stored]);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_rename_alias() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_variant(
+                "git pull --rebase",
+                "git_pull",
+                None,
+                Some("work".to_string()),
+            )
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("git push", "git_push", None)
+            .await
+            .unwrap();
+
+        service.rename_alias("git_pull", "pull").await.unwrap();
+        let aliases: Vec<String> = service
+            .get_all_commands()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|command| command.alias)
+            .collect();
+        assert_eq!(aliases, ["git_push", "pull", "pull"]);
+
+        assert!(matches!(
+            service.rename_alias("pull", "git_push").await,
+            Err(CommandServiceError::AliasTaken(alias)) if alias == "git_push"
+        ));
+        assert!(matches!(
+            service.rename_alias("pull", " ").await,
+            Err(CommandServiceError::Invalid(_))
+        ));
+        assert!(matches!(
+            service.rename_alias("git_pull", "fetch").await,
+            Err(CommandServiceError::AliasNotFound(alias)) if alias == "git_pull"
+        ));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_rename_alias_rewrites_references() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("cargo build", "build", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("@build && test", "check", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("@builder --fast", "fast", None)
+            .await
+            .unwrap();
+
+        service.rename_alias("build", "compile").await.unwrap();
+        let commands: Vec<(String, String)> = service
+            .get_all_commands()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|command| (command.alias, command.command))
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                ("check".to_string(), "@compile && test".to_string()),
+                ("compile".to_string(), "cargo build".to_string()),
+                ("fast".to_string(), "@builder --fast".to_string()),
+            ]
+        );
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_set_label() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        service
+            .set_label("git_pull", Some(Label::Green))
+            .await
+            .unwrap();
+        let stored = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(stored.label, Some(Label::Green));
+
+        service.set_label("git_pull", None).await.unwrap();
+        let stored = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(stored.label, None);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_set_expected_exit() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let command = service
+            .insert_command("test -d .git", "in_repo", None)
+            .await
+            .unwrap();
+        assert_eq!(command.expected_exit, None);
+
+        service.set_expected_exit(&command, Some(0)).await.unwrap();
+        let stored = service.get_command_by_alias("in_repo").await.unwrap();
+        assert_eq!(stored.expected_exit, Some(0));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_examples_round_trip() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let examples = Examples(vec!["ssh db1".to_string(), "ssh -p 2222 db2".to_string()]);
+        let _ = service
+            .add_command(Command {
+                examples: examples.clone(),
+                ..Command::new(
+                    String::new(),
+                    "ssh {host}".to_string(),
+                    "ssh_host".to_string(),
+                    None,
+                )
+            })
+            .await
+            .unwrap();
+
+        let stored = service.get_command_by_alias("ssh_host").await.unwrap();
+        assert_eq!(stored.examples, examples);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_upsert_command() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let command = |command: &str, environment: Option<&str>| Command {
+            environment: environment.map(str::to_string),
+            ..Command::new(
+                String::new(),
+                command.to_string(),
+                "deploy".to_string(),
+                None,
+            )
+        };
+
+        let _ = service
+            .upsert_command(command("make deploy", None))
+            .await
+            .unwrap();
+        let _ = service
+            .upsert_command(command("make deploy ENV=prod", Some("prod")))
+            .await
+            .unwrap();
+        let _ = service
+            .upsert_command(command("make deploy ENV=staging", None))
+            .await
+            .unwrap();
+
+        let stored = service.get_all_commands().await.unwrap();
+        assert_eq!(stored.len(), 2);
+        let default = service.get_by_alias_and_env("deploy", None).await.unwrap();
+        assert_eq!(default.command, "make deploy ENV=staging");
+
+        // A command string stored under another variant is refused, the replaced one is kept
+        assert!(service
+            .upsert_command(command("make deploy ENV=prod", None))
+            .await
+            .is_err());
+        let default = service.get_by_alias_and_env("deploy", None).await.unwrap();
+        assert_eq!(default.command, "make deploy ENV=staging");
+
+        service
+            .delete_variant(&command("", Some("prod")))
+            .await
+            .unwrap();
+        assert_eq!(service.get_all_commands().await.unwrap().len(), 1);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_os_variants() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        for (command, os) in [
+            ("sed -i '' s/a/b/ file", Some("macos")),
+            ("sed -i s/a/b/ file", Some("linux")),
+            ("perl -pi -e s/a/b/ file", None),
+        ] {
+            let _ = service
+                .add_command(Command {
+                    os: os.map(str::to_string),
+                    ..Command::new(
+                        String::new(),
+                        command.to_string(),
+                        "replace".to_string(),
+                        None,
+                    )
+                })
+                .await
+                .unwrap();
+        }
+
+        let linux = service.get_for_os("replace", None, "linux").await.unwrap();
+        assert_eq!(linux.command, "sed -i s/a/b/ file");
+        assert_eq!(linux.executable, "sed");
+
+        let fallback = service
+            .get_for_os("replace", None, "windows")
+            .await
+            .unwrap();
+        assert_eq!(fallback.command, "perl -pi -e s/a/b/ file");
+
+        assert!(service.get_for_os("missing", None, "linux").await.is_err());
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_default_description_template() {
+        let mut service = CommandService::new("test.sqlite").await.unwrap();
+        service.set_default_description_template(Some("Runs {executable} as {alias}".to_string()));
+
+        let pull = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        assert_eq!(pull.description.as_deref(), Some("Runs git as git_pull"));
+
+        let push = service
+            .insert_command("git push", "git_push", Some("Push".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(push.description.as_deref(), Some("Push"));
+
+        let ls = service
+            .add_command(Command::new(
+                "".to_string(),
+                "ls -a".to_string(),
+                "ls_all".to_string(),
+                None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(ls.description.as_deref(), Some("Runs ls as ls_all"));
+
+        let stored = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(stored.description.as_deref(), Some("Runs git as git_pull"));
+        let stored = service.get_command_by_alias("git_push").await.unwrap();
+        assert_eq!(stored.description.as_deref(), Some("Push"));
+
+        service.close().await;
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_export_markdown() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", Some("Pull the changes".to_string()))
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("git push", "git_push", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("ls -a", "ls_all", None)
+            .await
+            .unwrap();
+
+        service.export_markdown("test.md").await.unwrap();
+
+        let markdown = std::fs::read_to_string("test.md").unwrap();
+        assert!(markdown.contains("## git\n"));
+        assert!(markdown.contains("## ls\n"));
+        assert!(markdown.contains("| git_pull | ` git pull ` | Pull the changes |\n"));
+        assert!(markdown.contains("| git_push | ` git push ` |  |\n"));
+        assert!(markdown.contains("| ls_all | ` ls -a ` |  |\n"));
+
+        std::fs::remove_file("test.md").unwrap();
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_export_matching() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        for (command, alias) in [
+            ("git pull", "git_pull"),
+            ("git push", "git_push"),
+            ("ls -a", "ls_all"),
+            ("docker ps", "docker_ps"),
+        ] {
+            let _ = service.insert_command(command, alias, None).await.unwrap();
+        }
+        service
+            .add_tag_to_many(&["git_push".to_string(), "ls_all".to_string()], "daily")
+            .await
+            .unwrap();
+
+        let written = service
+            .export_matching(ExportFormat::Json, "test_git.json", None, Some("git"))
+            .await
+            .unwrap();
+        assert_eq!(written, 2);
+        let exported = std::fs::read_to_string("test_git.json").unwrap();
+        assert!(exported.contains("git_pull"));
+        assert!(exported.contains("git_push"));
+        assert!(!exported.contains("ls_all"));
+        assert!(!exported.contains("docker_ps"));
+
+        let aliases = |commands: Vec<Command>| -> Vec<String> {
+            commands.into_iter().map(|command| command.alias).collect()
+        };
+        let daily = service
+            .get_commands_matching(Some("daily"), None)
+            .await
+            .unwrap();
+        assert_eq!(aliases(daily), ["git_push", "ls_all"]);
+        let daily_git = service
+            .get_commands_matching(Some("daily"), Some("GIT"))
+            .await
+            .unwrap();
+        assert_eq!(aliases(daily_git), ["git_push"]);
+
+        std::fs::remove_file("test_git.json").unwrap();
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_auto_tag_by_executable() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        for (command, alias) in [
+            ("git pull", "git_pull"),
+            ("git push", "git_push"),
+            ("ls -a", "ls_all"),
+            ("docker ps", "docker_ps"),
+        ] {
+            let _ = service.insert_command(command, alias, None).await.unwrap();
+        }
+        service
+            .add_tag_to_many(&["ls_all".to_string()], "ls")
+            .await
+            .unwrap();
+
+        assert_eq!(service.auto_tag_by_executable().await.unwrap(), 3);
+        assert_eq!(service.auto_tag_by_executable().await.unwrap(), 0);
+
+        let git_pull = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(git_pull.tags.iter().collect::<Vec<_>>(), ["git"]);
+        let ls_all = service.get_command_by_alias("ls_all").await.unwrap();
+        assert_eq!(ls_all.tags.iter().collect::<Vec<_>>(), ["ls"]);
+
+        let groups = default_executable_groups();
+        assert_eq!(service.auto_tag_by_group(&groups).await.unwrap(), 3);
+        assert_eq!(service.auto_tag_by_group(&groups).await.unwrap(), 0);
+        let docker_ps = service.get_command_by_alias("docker_ps").await.unwrap();
+        assert_eq!(
+            docker_ps.tags.iter().collect::<Vec<_>>(),
+            ["docker", "container"]
+        );
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_export_justfile() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("ssh {host}", "ssh_to", None)
+            .await
+            .unwrap();
+
+        service.export_justfile("test.justfile").await.unwrap();
+
+        let justfile = std::fs::read_to_string("test.justfile").unwrap();
+        assert!(justfile.contains("git_pull:\n    git pull\n"));
+        assert!(justfile.contains("ssh_to host:\n    ssh {{host}}\n"));
+
+        std::fs::remove_file("test.justfile").unwrap();
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_add_tag_to_many() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        for (command, alias) in [
+            ("git pull", "git_pull"),
+            ("git push", "git_push"),
+            ("ls -a", "ls_all"),
+            ("ssh --version", "ssh_version"),
+        ] {
+            let _ = service.insert_command(command, alias, None).await.unwrap();
+        }
+
+        let aliases = vec![
+            "git_pull".to_string(),
+            "git_push".to_string(),
+            "ls_all".to_string(),
+        ];
+        assert_eq!(service.add_tag_to_many(&aliases, "daily").await.unwrap(), 3);
+        // Tagging again leaves the tags untouched
+        assert_eq!(service.add_tag_to_many(&aliases, "daily").await.unwrap(), 0);
+
+        for alias in &aliases {
+            let command = service.get_command_by_alias(alias).await.unwrap();
+            assert_eq!(command.tags.0, vec!["daily".to_string()]);
+        }
+        let untouched = service.get_command_by_alias("ssh_version").await.unwrap();
+        assert!(untouched.tags.is_empty());
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_update_description() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let command = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        let res = service
+            .update_description(&command, Some("d".repeat(301)))
+            .await;
+        match res {
+            Err(CommandServiceError::Invalid(e)) => assert_eq!(e.field(), "description"),
+            other => panic!("Expected a validation error, got {other:?}"),
+        }
+
+        service
+            .update_description(&command, Some("Pull the changes".to_string()))
+            .await
+            .unwrap();
+        let stored = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(stored.description.as_deref(), Some("Pull the changes"));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_update_command() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let command = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        let updated = service
+            .update_command(&command, "sudo Make install")
+            .await
+            .unwrap();
+        assert_eq!(updated.executable, "make");
+
+        let stored = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(stored, updated);
+
+        assert!(matches!(
+            service.update_command(&updated, "  ").await,
+            Err(CommandServiceError::NoExecutable)
+        ));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    async fn merge_fixture() -> (CommandService, CommandService) {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        let _ = service.insert_command("ls -a", "ls", None).await.unwrap();
+
+        let other = CommandService::new("test_other.sqlite").await.unwrap();
+        let _ = other
+            .insert_command("git pull --rebase", "git_pull", None)
+            .await
+            .unwrap();
+        let _ = other.insert_command("ls -a", "ls_all", None).await.unwrap();
+        let _ = other
+            .insert_command("git push", "git_push", None)
+            .await
+            .unwrap();
+
+        (service, other)
+    }
+
+    fn remove_merge_fixture() {
+        std::fs::remove_file("test.sqlite").unwrap();
+        std::fs::remove_file("test_other.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_import_from_db_skip() {
+        let (service, _other) = merge_fixture().await;
+
+        let summary = service
+            .import_from_db("test_other.sqlite", ConflictPolicy::Skip)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            MergeSummary {
+                inserted: 1,
+                skipped: 2,
+                ..Default::default()
+            }
+        );
+
+        let pull = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(pull.command, "git pull");
+        assert!(service.get_command_by_alias("git_push").await.is_ok());
+
+        remove_merge_fixture();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_import_from_db_replace() {
+        let (service, _other) = merge_fixture().await;
+
+        let summary = service
+            .import_from_db("test_other.sqlite", ConflictPolicy::Replace)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            MergeSummary {
+                inserted: 1,
+                replaced: 2,
+                ..Default::default()
+            }
+        );
+
+        let pull = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(pull.command, "git pull --rebase");
+        assert!(service.get_command_by_alias("ls").await.is_err());
+        assert_eq!(service.get_all_commands().await.unwrap().len(), 3);
+
+        remove_merge_fixture();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_import_from_db_rename() {
+        let (service, _other) = merge_fixture().await;
+
+        let summary = service
+            .import_from_db("test_other.sqlite", ConflictPolicy::Rename)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            MergeSummary {
+                inserted: 1,
+                renamed: 1,
+                skipped: 1,
+                ..Default::default()
+            }
+        );
+
+        let pull = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(pull.command, "git pull");
+        let renamed = service.get_command_by_alias("git_pull_2").await.unwrap();
+        assert_eq!(renamed.command, "git pull --rebase");
+
+        assert!(matches!(
+            service
+                .import_from_db("missing.sqlite", ConflictPolicy::Rename)
+                .await,
+            Err(CommandServiceError::MissingDatabase(_))
+        ));
+
+        remove_merge_fixture();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_import_from_db_os_variant() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        // Same alias and environment, filed for another operating system
+        let other = CommandService::new("test_other.sqlite").await.unwrap();
+        other
+            .add_command(Command {
+                os: Some("macos".to_string()),
+                ..Command::new(
+                    String::new(),
+                    "git pull --ff-only".to_string(),
+                    "git_pull".to_string(),
+                    None,
+                )
+            })
+            .await
+            .unwrap();
+
+        for on_conflict in [ConflictPolicy::Replace, ConflictPolicy::Rename] {
+            let summary = service
+                .import_from_db("test_other.sqlite", on_conflict)
+                .await
+                .unwrap();
+            assert_eq!(summary.inserted + summary.skipped, 1);
+            assert_eq!(summary.replaced + summary.renamed, 0);
+        }
+
+        let stored = service.get_all_commands_including_disabled().await.unwrap();
+        assert_eq!(stored.len(), 2);
+        assert!(stored.iter().all(|command| command.alias == "git_pull"));
+
+        remove_merge_fixture();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_import_from_db_sensitive() {
+        let mut service = CommandService::new("test.sqlite").await.unwrap();
+        service.unlock("hunter2").await.unwrap();
+        service
+            .add_command(Command {
+                sensitive: true,
+                ..Command::new(
+                    String::new(),
+                    "curl -H 'Authorization: Bearer abc' https://api.example.com".to_string(),
+                    "api".to_string(),
+                    None,
+                )
+            })
+            .await
+            .unwrap();
+
+        let mut other = CommandService::new("test_other.sqlite").await.unwrap();
+        let _ = other
+            .insert_command("curl https://api.example.com", "api", None)
+            .await
+            .unwrap();
+
+        // The sealed row stored here is replaced like any other
+        let summary = service
+            .import_from_db("test_other.sqlite", ConflictPolicy::Replace)
+            .await
+            .unwrap();
+        assert_eq!(summary.replaced, 1);
+        let stored = service.get_all_commands_including_disabled().await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].command, "curl https://api.example.com");
+
+        // Rows sealed with the passphrase of the other database are refused
+        other.unlock("hunter3").await.unwrap();
+        other
+            .add_command(Command {
+                sensitive: true,
+                ..Command::new(
+                    String::new(),
+                    "curl -u admin:secret https://api.example.com".to_string(),
+                    "api_admin".to_string(),
+                    None,
+                )
+            })
+            .await
+            .unwrap();
+        assert!(matches!(
+            service
+                .import_from_db("test_other.sqlite", ConflictPolicy::Replace)
+                .await,
+            Err(CommandServiceError::SensitiveMerge(_))
+        ));
+        assert_eq!(
+            service
+                .get_all_commands_including_disabled()
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        remove_merge_fixture();
     }
 }
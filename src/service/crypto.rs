@@ -0,0 +1,166 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+
+/// Marks a command string that is stored encrypted
+pub const SEALED_PREFIX: &str = "enc:v1:";
+
+/// Read instead of prompting when set, for scripts and CI
+pub const PASSPHRASE_VAR: &str = "COMMAND_ORGANISER_PASSPHRASE";
+
+/// Length in bytes of the salt the keys are derived with
+pub const SALT_LEN: usize = 16;
+
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("Failed to derive the key from the passphrase: {0}")]
+    KeyDerivation(String),
+
+    #[error("Failed to encrypt the command")]
+    Encrypt,
+
+    #[error("Failed to decrypt the command, the passphrase is wrong or the data is damaged")]
+    Decrypt,
+
+    #[error("The encrypted command is malformed")]
+    Malformed,
+
+    #[error("The sensitive commands are locked, a passphrase is needed")]
+    Locked,
+}
+
+/// Encrypts the command strings of sensitive commands with keys derived from a passphrase.
+///
+/// The nonce is derived from the command string, so a command always encrypts to the same
+/// text. Rows can then still be looked up by their command string, at the cost of revealing
+/// which sensitive commands are equal.
+pub struct Cipher {
+    cipher: Aes256Gcm,
+    nonce_key: [u8; 32],
+}
+
+impl Cipher {
+    pub fn new(passphrase: &str, salt: &[u8]) -> Result<Cipher, CryptoError> {
+        let mut keys = [0u8; 64];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut keys)
+            .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
+
+        let (key, nonce_key) = keys.split_at(32);
+        let cipher = Aes256Gcm::new_from_slice(key)
+            .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
+
+        Ok(Cipher {
+            cipher,
+            nonce_key: nonce_key.try_into().expect("the nonce key is 32 bytes"),
+        })
+    }
+
+    /// The stored form of `plaintext`, left alone when it is sealed already
+    pub fn seal(&self, plaintext: &str) -> Result<String, CryptoError> {
+        if is_sealed(plaintext) {
+            return Ok(plaintext.to_string());
+        }
+
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.nonce_key)
+            .map_err(|_| CryptoError::Encrypt)?;
+        mac.update(plaintext.as_bytes());
+        let digest = mac.finalize().into_bytes();
+        let nonce = Nonce::from_slice(&digest[..NONCE_LEN]);
+
+        let ciphertext = self
+            .cipher
+            .encrypt(nonce, plaintext.as_bytes())
+            .map_err(|_| CryptoError::Encrypt)?;
+
+        Ok(format!(
+            "{SEALED_PREFIX}{}{}",
+            to_hex(nonce),
+            to_hex(&ciphertext)
+        ))
+    }
+
+    /// The command string behind `sealed`, which is returned as is unless it is sealed
+    pub fn open(&self, sealed: &str) -> Result<String, CryptoError> {
+        let Some(encoded) = sealed.strip_prefix(SEALED_PREFIX) else {
+            return Ok(sealed.to_string());
+        };
+
+        let bytes = from_hex(encoded).ok_or(CryptoError::Malformed)?;
+        if bytes.len() < NONCE_LEN {
+            return Err(CryptoError::Malformed);
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| CryptoError::Decrypt)?;
+
+        String::from_utf8(plaintext).map_err(|_| CryptoError::Malformed)
+    }
+}
+
+/// A random salt for a new key
+pub fn generate_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+
+    salt
+}
+
+/// True when `text` is the stored form of a sensitive command
+pub fn is_sealed(text: &str) -> bool {
+    text.starts_with(SEALED_PREFIX)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_hex, is_sealed, to_hex, Cipher, CryptoError};
+
+    #[test]
+    fn test_seal_and_open() {
+        let cipher = Cipher::new("hunter2", b"0123456789abcdef").unwrap();
+        let command = "curl -H 'Authorization: Bearer abc' https://api.example.com";
+
+        let sealed = cipher.seal(command).unwrap();
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("Bearer"));
+        assert_eq!(cipher.seal(command).unwrap(), sealed);
+        assert_eq!(cipher.seal(&sealed).unwrap(), sealed);
+        assert_eq!(cipher.open(&sealed).unwrap(), command);
+        assert_eq!(cipher.open("git pull").unwrap(), "git pull");
+
+        let wrong = Cipher::new("hunter3", b"0123456789abcdef").unwrap();
+        assert!(matches!(wrong.open(&sealed), Err(CryptoError::Decrypt)));
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(&[0, 15, 255]), "000fff");
+        assert_eq!(from_hex("000fff"), Some(vec![0, 15, 255]));
+        assert_eq!(from_hex("0f0"), None);
+        assert_eq!(from_hex("zz"), None);
+    }
+}
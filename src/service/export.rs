@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::command_service::CommandServiceError;
+use super::justfile::format_justfile;
+use super::markdown::format_markdown;
+use crate::model::command::Command;
+
+/// File formats the commands can be exported to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportFormat {
+    /// Can be imported back with `--file`
+    #[default]
+    Toml,
+    Json,
+    Justfile,
+    Markdown,
+}
+
+impl ExportFormat {
+    /// The file name exports are offered under, e.g. `commands.toml`
+    pub fn default_file_name(self) -> &'static str {
+        match self {
+            ExportFormat::Toml => "commands.toml",
+            ExportFormat::Json => "commands.json",
+            ExportFormat::Justfile => "justfile",
+            ExportFormat::Markdown => "commands.md",
+        }
+    }
+}
+
+/// Renders the commands in `format`
+pub fn render(commands: &[Command], format: ExportFormat) -> Result<String, CommandServiceError> {
+    match format {
+        ExportFormat::Toml => {
+            let commands = HashMap::from([("commands", commands)]);
+            toml::to_string(&commands).map_err(|e| CommandServiceError::Serialize(e.to_string()))
+        }
+        ExportFormat::Json => serde_json::to_string_pretty(commands)
+            .map_err(|e| CommandServiceError::Serialize(e.to_string())),
+        ExportFormat::Justfile => Ok(format_justfile(commands)),
+        ExportFormat::Markdown => Ok(format_markdown(commands)),
+    }
+}
+
+/// Writes the commands to `path` in `format`
+pub fn export_commands(
+    commands: &[Command],
+    format: ExportFormat,
+    path: &Path,
+) -> Result<(), CommandServiceError> {
+    std::fs::write(path, render(commands, format)?)?;
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use crate::model::command::Command;
+use crate::model::placeholder::{extract_named, segments, Segment};
+
+/// A recipe name `just` accepts, made out of the alias and the environment of the command
+fn recipe_name(command: &Command) -> String {
+    let name = match &command.environment {
+        Some(environment) => format!("{}-{environment}", command.alias),
+        None => command.alias.clone(),
+    };
+
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => name,
+        _ => format!("_{name}"),
+    }
+}
+
+/// The command as a recipe body: `{name}` placeholders become `{{name}}` interpolations and
+/// literal `{{` are escaped so `just` does not interpolate them
+fn recipe_body(command: &str) -> String {
+    segments(command)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.replace("{{", "{{ \"{{\" }}"),
+            Segment::Named(name) => format!("{{{{{name}}}}}"),
+        })
+        .collect()
+}
+
+/// Renders every command as a recipe named after its alias, with a parameter per placeholder
+pub fn format_justfile(commands: &[Command]) -> String {
+    let recipes: Vec<String> = commands
+        .iter()
+        .map(|command| {
+            let mut recipe = String::new();
+            if let Some(description) = command.rendered_description() {
+                for line in description.lines() {
+                    recipe.push_str(&format!("# {line}\n"));
+                }
+            }
+
+            let mut header = vec![recipe_name(command)];
+            header.extend(extract_named(&command.command));
+            recipe.push_str(&format!("{}:\n", header.join(" ")));
+
+            for line in recipe_body(&command.command).lines() {
+                recipe.push_str(&format!("    {line}\n"));
+            }
+
+            recipe
+        })
+        .collect();
+
+    recipes.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_justfile;
+    use crate::model::command::Command;
+
+    fn command(command: &str, alias: &str, description: Option<&str>) -> Command {
+        Command::new(
+            "".to_string(),
+            command.to_string(),
+            alias.to_string(),
+            description.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn test_recipe_per_command() {
+        let justfile = format_justfile(&[
+            command("git pull", "git_pull", Some("Pull the changes")),
+            command("ls -a", "ls.all", None),
+            Command {
+                environment: Some("prod".to_string()),
+                ..command("kubectl get pods", "pods", None)
+            },
+        ]);
+
+        assert_eq!(
+            justfile,
+            "# Pull the changes\ngit_pull:\n    git pull\n\n\
+             ls_all:\n    ls -a\n\n\
+             pods-prod:\n    kubectl get pods\n"
+        );
+    }
+
+    #[test]
+    fn test_placeholders_become_parameters() {
+        let justfile = format_justfile(&[command(
+            "kubectl logs {pod} -n {namespace} | grep {pod}",
+            "logs",
+            None,
+        )]);
+
+        assert_eq!(
+            justfile,
+            "logs pod namespace:\n    kubectl logs {{pod}} -n {{namespace}} | grep {{pod}}\n"
+        );
+    }
+
+    #[test]
+    fn test_literal_braces_are_escaped() {
+        let justfile =
+            format_justfile(&[command("docker ps --format '{{.Names}}'", "names", None)]);
+
+        assert_eq!(
+            justfile,
+            "names:\n    docker ps --format '{{ \"{{\" }}.Names}}'\n"
+        );
+    }
+}
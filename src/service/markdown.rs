@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+
+use crate::model::command::Command;
+
+/// Makes `text` safe to put in a table cell: pipes would end the cell and newlines the row
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', "<br>")
+}
+
+/// The command as inline code, fenced with enough backticks to hold the ones it contains
+fn code_cell(command: &str) -> String {
+    let fence = if command.contains('`') { "``" } else { "`" };
+
+    format!("{fence} {} {fence}", escape_cell(command))
+}
+
+/// Renders the commands as a cheatsheet with a section per executable, each holding a table of
+/// alias, command and description
+pub fn format_markdown(commands: &[Command]) -> String {
+    let mut per_executable: BTreeMap<&str, Vec<&Command>> = BTreeMap::new();
+    for command in commands {
+        per_executable
+            .entry(command.executable.as_str())
+            .or_default()
+            .push(command);
+    }
+
+    let sections: Vec<String> = per_executable
+        .into_iter()
+        .map(|(executable, commands)| {
+            let mut section = format!(
+                "## {}\n\n| Alias | Command | Description |\n| --- | --- | --- |\n",
+                escape_cell(executable)
+            );
+            for command in commands {
+                section.push_str(&format!(
+                    "| {} | {} | {} |\n",
+                    escape_cell(&command.alias),
+                    code_cell(&command.command),
+                    escape_cell(&command.rendered_description().unwrap_or_default())
+                ));
+            }
+
+            section
+        })
+        .collect();
+
+    format!("# Commands\n\n{}", sections.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_markdown;
+    use crate::model::command::Command;
+
+    #[test]
+    fn test_fields_are_escaped() {
+        let markdown = format_markdown(&[Command::new(
+            "ps".to_string(),
+            "ps aux | grep `whoami`".to_string(),
+            "my_procs".to_string(),
+            Some("Lists my processes\nwith | in it".to_string()),
+        )]);
+
+        assert!(markdown.contains(
+            "| my_procs | `` ps aux \\| grep `whoami` `` | Lists my processes<br>with \\| in it |\n"
+        ));
+    }
+}
@@ -1 +1,9 @@
+pub mod cheat;
 pub mod command_service;
+pub mod crypto;
+pub mod export;
+pub mod justfile;
+pub mod markdown;
+pub mod runner;
+pub mod search_index;
+pub mod shell_aliases;
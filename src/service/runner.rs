@@ -0,0 +1,254 @@
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::config::app_config::AppConfig;
+use crate::model::command::Command;
+
+/// How a command run through the shell ended
+#[derive(Debug, PartialEq, Eq)]
+pub enum RunOutcome {
+    Exited(ExitStatus),
+    /// Killed after running longer than the timeout
+    TimedOut,
+}
+
+/// How a run compares to the exit code expected of the command
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExitCheck {
+    Passed,
+    /// `actual` is `None` when the command was killed by a signal or timed out
+    Failed {
+        expected: i32,
+        actual: Option<i32>,
+    },
+}
+
+/// Compares the run against `expected`, nothing to compare when no exit code is expected
+pub fn check_exit(outcome: &RunOutcome, expected: Option<i32>) -> Option<ExitCheck> {
+    let expected = expected?;
+    let actual = match outcome {
+        RunOutcome::Exited(status) => status.code(),
+        RunOutcome::TimedOut => None,
+    };
+
+    Some(if actual == Some(expected) {
+        ExitCheck::Passed
+    } else {
+        ExitCheck::Failed { expected, actual }
+    })
+}
+
+/// Shell commands are run with when they do not name one
+pub const DEFAULT_SHELL: &str = "sh";
+
+/// The directory `command` is run in: its own, else the one configured for its executable.
+/// `None` runs it in the current directory.
+pub fn resolve_working_dir(command: &Command, config: &AppConfig) -> Option<PathBuf> {
+    command
+        .working_dir
+        .as_ref()
+        .or_else(|| config.working_dirs.get(&command.executable))
+        .map(PathBuf::from)
+}
+
+/// The process running `command` with `shell -c` in `working_dir`, `sh` when no shell is given
+pub fn shell_command(
+    command: &str,
+    shell: Option<&str>,
+    working_dir: Option<&Path>,
+) -> tokio::process::Command {
+    let mut process = tokio::process::Command::new(shell.unwrap_or(DEFAULT_SHELL));
+    process.arg("-c").arg(command);
+    if let Some(working_dir) = working_dir {
+        process.current_dir(working_dir);
+    }
+
+    process
+}
+
+/// Runs `command` through `shell` like `run_shell`, without a terminal, returning what it wrote
+/// to stdout. Nothing is returned of a command that timed out.
+pub async fn run_captured(
+    command: &str,
+    shell: Option<&str>,
+    working_dir: Option<&Path>,
+    timeout: Option<Duration>,
+) -> std::io::Result<(RunOutcome, String)> {
+    let child = shell_command(command, shell, working_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let output = match timeout {
+        None => child.wait_with_output().await?,
+        // Dropping the child on timeout kills it
+        Some(timeout) => match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(output) => output?,
+            Err(_) => {
+                warn!("{command} timed out after {timeout:?}, killing it");
+                return Ok((RunOutcome::TimedOut, String::new()));
+            }
+        },
+    };
+
+    Ok((
+        RunOutcome::Exited(output.status),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+    ))
+}
+
+/// Runs `command` with `shell -c` on the inherited stdio, killing it once `timeout` elapses
+pub async fn run_shell(
+    command: &str,
+    shell: Option<&str>,
+    working_dir: Option<&Path>,
+    timeout: Option<Duration>,
+) -> std::io::Result<RunOutcome> {
+    let mut child = shell_command(command, shell, working_dir)
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let Some(timeout) = timeout else {
+        return Ok(RunOutcome::Exited(child.wait().await?));
+    };
+
+    match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(status) => Ok(RunOutcome::Exited(status?)),
+        Err(_) => {
+            warn!("{command} timed out after {timeout:?}, killing it");
+            child.kill().await?;
+            Ok(RunOutcome::TimedOut)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    use super::{
+        check_exit, resolve_working_dir, run_captured, run_shell, shell_command, ExitCheck,
+        RunOutcome,
+    };
+    use crate::config::app_config::AppConfig;
+    use crate::model::command::Command;
+
+    #[test]
+    fn test_shell_command() {
+        let args = |shell: Option<&str>| {
+            let process = shell_command("echo $SHELL", shell, None);
+            let process = process.as_std();
+            let mut args = vec![process.get_program().to_string_lossy().to_string()];
+            args.extend(
+                process
+                    .get_args()
+                    .map(|arg| arg.to_string_lossy().to_string()),
+            );
+            args
+        };
+
+        assert_eq!(args(None), ["sh", "-c", "echo $SHELL"]);
+        assert_eq!(args(Some("bash")), ["bash", "-c", "echo $SHELL"]);
+        assert_eq!(args(Some("fish")), ["fish", "-c", "echo $SHELL"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_to_completion() {
+        let outcome = run_shell("exit 3", None, None, Some(Duration::from_secs(5)))
+            .await
+            .unwrap();
+
+        match outcome {
+            RunOutcome::Exited(status) => assert_eq!(status.code(), Some(3)),
+            RunOutcome::TimedOut => panic!("exit 3 timed out"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_captured() {
+        let (outcome, output) = run_captured("echo hello; echo oops >&2", None, None, None)
+            .await
+            .unwrap();
+
+        assert!(matches!(outcome, RunOutcome::Exited(status) if status.success()));
+        assert_eq!(output, "hello\n");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_the_command() {
+        let started = Instant::now();
+
+        let outcome = run_shell("sleep 5", None, None, Some(Duration::from_secs(1)))
+            .await
+            .unwrap();
+
+        assert_eq!(outcome, RunOutcome::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_working_dir_precedence() {
+        let mut config = AppConfig::default();
+        let mut build = Command::new(
+            "cargo".to_string(),
+            "cargo build".to_string(),
+            "build".to_string(),
+            None,
+        );
+
+        assert_eq!(resolve_working_dir(&build, &config), None);
+
+        config
+            .working_dirs
+            .insert("cargo".to_string(), "/srv/projects".to_string());
+        assert_eq!(
+            resolve_working_dir(&build, &config),
+            Some(PathBuf::from("/srv/projects"))
+        );
+
+        build.working_dir = Some("/srv/projects/app".to_string());
+        assert_eq!(
+            resolve_working_dir(&build, &config),
+            Some(PathBuf::from("/srv/projects/app"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_in_working_dir() {
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        let (_, output) = run_captured("pwd", None, Some(&dir), None).await.unwrap();
+
+        assert_eq!(output.trim_end(), dir.to_string_lossy());
+    }
+
+    #[tokio::test]
+    async fn test_expected_exit() {
+        let outcome = run_shell("exit 0", None, None, None).await.unwrap();
+        assert_eq!(check_exit(&outcome, Some(0)), Some(ExitCheck::Passed));
+        assert_eq!(check_exit(&outcome, None), None);
+
+        let outcome = run_shell("exit 2", None, None, None).await.unwrap();
+        assert_eq!(
+            check_exit(&outcome, Some(0)),
+            Some(ExitCheck::Failed {
+                expected: 0,
+                actual: Some(2)
+            })
+        );
+        assert_eq!(check_exit(&outcome, Some(2)), Some(ExitCheck::Passed));
+
+        assert_eq!(
+            check_exit(&RunOutcome::TimedOut, Some(0)),
+            Some(ExitCheck::Failed {
+                expected: 0,
+                actual: None
+            })
+        );
+    }
+}
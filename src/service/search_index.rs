@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use crate::model::command::Command;
+
+/// Length of the pieces the searchable text is cut into
+const GRAM_LEN: usize = 3;
+
+/// In-memory index answering the same case-insensitive substring queries as a scan over the
+/// alias, command and description of every command, without looking at every command.
+///
+/// Each text is cut into overlapping trigrams. A query can only match the commands holding
+/// every trigram of the query, so only those are checked. Queries shorter than a trigram
+/// check every command.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    commands: Vec<Command>,
+    /// The lower cased searchable fields of each command, by position in `commands`
+    texts: Vec<Vec<String>>,
+    /// The positions of the commands containing each trigram, in ascending order
+    postings: HashMap<String, Vec<usize>>,
+}
+
+fn grams(text: &str) -> impl Iterator<Item = String> + '_ {
+    let chars: Vec<char> = text.chars().collect();
+    (0..chars.len().saturating_sub(GRAM_LEN - 1))
+        .map(move |start| chars[start..start + GRAM_LEN].iter().collect())
+}
+
+impl SearchIndex {
+    pub fn build(commands: &[Command]) -> SearchIndex {
+        let mut index = SearchIndex::default();
+        for command in commands {
+            index.insert(command.clone());
+        }
+
+        index
+    }
+
+    pub fn insert(&mut self, command: Command) {
+        let id = self.commands.len();
+        let texts: Vec<String> = [
+            Some(&command.alias),
+            Some(&command.command),
+            command.description.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|text| text.to_lowercase())
+        .collect();
+
+        for text in &texts {
+            for gram in grams(text) {
+                let posting = self.postings.entry(gram).or_default();
+                // Ids only grow, so the last one is the only possible duplicate
+                if posting.last() != Some(&id) {
+                    posting.push(id);
+                }
+            }
+        }
+
+        self.commands.push(command);
+        self.texts.push(texts);
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// The commands whose alias, command or description contain `query`, ignoring case,
+    /// in the order they were inserted
+    pub fn query(&self, query: &str) -> Vec<&Command> {
+        let query = query.to_lowercase();
+
+        let mut candidates: Option<Vec<usize>> = None;
+        for gram in grams(&query) {
+            let Some(posting) = self.postings.get(&gram) else {
+                return Vec::new();
+            };
+
+            candidates = Some(match candidates {
+                None => posting.clone(),
+                Some(candidates) => candidates
+                    .into_iter()
+                    .filter(|id| posting.binary_search(id).is_ok())
+                    .collect(),
+            });
+        }
+
+        let candidates = candidates.unwrap_or_else(|| (0..self.len()).collect());
+
+        // Trigrams can be spread over several fields or out of order, the candidates are
+        // confirmed against the full query
+        candidates
+            .into_iter()
+            .filter(|&id| self.texts[id].iter().any(|text| text.contains(&query)))
+            .map(|id| &self.commands[id])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SearchIndex;
+    use crate::model::command::Command;
+
+    fn commands() -> Vec<Command> {
+        [
+            (
+                "git",
+                "git pull --rebase",
+                "git_pull",
+                Some("Fetch and rebase"),
+            ),
+            ("git", "git push", "git_push", None),
+            ("ls", "ls -a", "ls_all", Some("List every file")),
+            (
+                "kubectl",
+                "kubectl get pods",
+                "pods",
+                Some("Pods of the cluster"),
+            ),
+            (
+                "docker",
+                "docker ps -a",
+                "containers",
+                Some("All the containers"),
+            ),
+        ]
+        .into_iter()
+        .map(|(executable, command, alias, description)| {
+            Command::new(
+                executable.to_string(),
+                command.to_string(),
+                alias.to_string(),
+                description.map(str::to_string),
+            )
+        })
+        .collect()
+    }
+
+    fn scan<'a>(commands: &'a [Command], query: &str) -> Vec<&'a Command> {
+        let query = query.to_lowercase();
+        let matches = |text: &str| text.to_lowercase().contains(&query);
+
+        commands
+            .iter()
+            .filter(|command| {
+                matches(&command.alias)
+                    || matches(&command.command)
+                    || command.description.as_deref().is_some_and(matches)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_query_matches_scan() {
+        let commands = commands();
+        let index = SearchIndex::build(&commands);
+
+        for query in [
+            "", "g", "gi", "git", "GIT P", "push", "-a", "the", "pods", "reba", "zzz", "pull_x",
+            "l_a",
+        ] {
+            assert_eq!(index.query(query), scan(&commands, query), "{query}");
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut index = SearchIndex::build(&commands());
+        assert!(index.query("terraform").is_empty());
+
+        index.insert(Command::new(
+            "terraform".to_string(),
+            "terraform plan".to_string(),
+            "plan".to_string(),
+            None,
+        ));
+
+        assert_eq!(index.len(), 6);
+        assert_eq!(index.query("terraform")[0].alias, "plan");
+    }
+}
@@ -0,0 +1,118 @@
+/// An alias defined in a shell rc file, e.g. `alias gs='git status'`
+#[derive(Debug, PartialEq, Eq)]
+pub struct ShellAlias {
+    pub name: String,
+    pub command: String,
+}
+
+/// Characters shells accept in alias names
+fn is_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
+
+/// The value of an alias definition with its quotes removed and escapes resolved, as the shell
+/// would read it. Anything after the first unquoted blank, e.g. a comment, is ignored.
+/// `None` when a quote is left open.
+fn unquote(value: &str) -> Option<String> {
+    let mut unquoted = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => loop {
+                match chars.next()? {
+                    '\'' => break,
+                    c => unquoted.push(c),
+                }
+            },
+            '"' => loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => match chars.next()? {
+                        c @ ('"' | '\\' | '$' | '`') => unquoted.push(c),
+                        c => {
+                            unquoted.push('\\');
+                            unquoted.push(c);
+                        }
+                    },
+                    c => unquoted.push(c),
+                }
+            },
+            '\\' => unquoted.push(chars.next()?),
+            c if c.is_whitespace() => break,
+            c => unquoted.push(c),
+        }
+    }
+
+    Some(unquoted)
+}
+
+/// Parses the `alias name='command'` and bare `name=command` lines of a shell rc file, skipping
+/// comments, other shell code and aliases that can not be read
+pub fn parse_shell_aliases(text: &str) -> Vec<ShellAlias> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let definition = line.strip_prefix("alias ").unwrap_or(line).trim_start();
+            let (name, value) = definition.split_once('=')?;
+            if !is_alias_name(name) {
+                return None;
+            }
+
+            let command = unquote(value)?;
+            if command.trim().is_empty() {
+                return None;
+            }
+
+            Some(ShellAlias {
+                name: name.to_string(),
+                command: command.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_shell_aliases, ShellAlias};
+
+    fn alias(name: &str, command: &str) -> ShellAlias {
+        ShellAlias {
+            name: name.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_shell_aliases() {
+        let aliases = parse_shell_aliases(
+            "# Aliases of the team\n\
+             alias gs='git status'\n\
+             alias ll=\"ls -la\" # long listing\n\
+             export PATH=\"$HOME/bin:$PATH\"\n\
+             if [ -f ~/.bash_local ]; then\n\
+             \x20   . ~/.bash_local\n\
+             fi\n\
+             \x20 alias say='echo '\\''hi'\\'''\n\
+             alias quote=\"echo \\\"done\\\" \\$HOME\"\n\
+             k=kubectl\n\
+             alias broken='git log\n\
+             alias empty=''\n",
+        );
+
+        assert_eq!(
+            aliases,
+            vec![
+                alias("gs", "git status"),
+                alias("ll", "ls -la"),
+                alias("say", "echo 'hi'"),
+                alias("quote", "echo \"done\" $HOME"),
+                alias("k", "kubectl"),
+            ]
+        );
+    }
+}
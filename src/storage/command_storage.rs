@@ -1,13 +1,263 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use futures::{Stream, TryStreamExt};
+use sqlx::query::Query;
+use sqlx::sqlite::{SqliteArguments, SqliteConnectOptions, SqliteConnection, SqlitePoolOptions};
 use sqlx::{migrate::MigrateDatabase, Error as SqlxError, Sqlite, SqlitePool};
 use thiserror::Error;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::model::command::Command;
+use crate::model::command::{rewrite_references, Command, Label, Tags, DEFAULT_MAX_ALIAS_LEN};
 
 #[derive(Debug, Error)]
 pub enum CommandStorageError {
     #[error("Failed to open the connection to the db: {0}")]
     OpenConnection(#[from] SqlxError),
+
+    #[error("Failed to set the permissions of the db file: {0}")]
+    Permissions(#[from] std::io::Error),
+
+    #[error(
+        "The database {path} is corrupted ({details}), restore it from a backup with \
+        `command_organiser restore <backup>`"
+    )]
+    CorruptDatabase { path: String, details: String },
+}
+
+impl CommandStorageError {
+    /// True when a write was refused because it would duplicate a unique column or index
+    pub fn is_unique_violation(&self) -> bool {
+        match self {
+            CommandStorageError::OpenConnection(SqlxError::Database(db_error)) => {
+                db_error.code().as_deref() == Some(SQLITE_CONSTRAINT_UNIQUE)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Number of attempts made for writes that hit a busy/locked database
+const RETRY_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry, doubled after every failed attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(10);
+
+// SQLite primary result codes, the extended codes keep them in the lowest byte
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+const SQLITE_CORRUPT: i32 = 11;
+const SQLITE_NOTADB: i32 = 26;
+/// Extended result code of a UNIQUE constraint failure
+const SQLITE_CONSTRAINT_UNIQUE: &str = "2067";
+
+/// Longest wait for the released connections to be back in the pool before it is closed
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The primary SQLite result code behind a database error
+fn sqlite_code(error: &SqlxError) -> Option<i32> {
+    match error {
+        SqlxError::Database(db_error) => db_error
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .map(|code| code & 0xff),
+        _ => None,
+    }
+}
+
+fn is_transient(error: &SqlxError) -> bool {
+    matches!(sqlite_code(error), Some(SQLITE_BUSY | SQLITE_LOCKED))
+}
+
+fn is_corruption(error: &SqlxError) -> bool {
+    matches!(sqlite_code(error), Some(SQLITE_CORRUPT | SQLITE_NOTADB))
+}
+
+/// Escapes the `LIKE` wildcards of `text` so it is matched literally with `ESCAPE '\'`
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Runs the query produced by `f`, retrying it with exponential backoff while SQLite reports
+/// the database as busy or locked. Any other error, or the last transient one, is returned.
+pub async fn with_retry<F, Fut, T>(attempts: u32, mut f: F) -> Result<T, SqlxError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, SqlxError>>,
+{
+    let mut delay = RETRY_BASE_DELAY;
+    let mut attempt = 1;
+
+    loop {
+        match f().await {
+            Err(e) if attempt < attempts && is_transient(&e) => {
+                warn!("Database is busy (attempt {attempt}/{attempts}), retrying in {delay:?}");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Inserts `command`, stamping its creation
+fn insert_query(command: &Command) -> Query<'static, Sqlite, SqliteArguments<'static>> {
+    sqlx::query(
+        "INSERT INTO commands(executable, command, alias, description, tags, favorite, \
+        environment, pinned, label, expected_exit, os, examples, enabled, sensitive, \
+        shell, expires_at, title, working_dir, created_at) \
+        VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, \
+        CAST(strftime('%s', 'now') AS INTEGER));",
+    )
+    .bind(command.executable.clone())
+    .bind(command.command.clone())
+    .bind(command.alias.clone())
+    .bind(command.description.clone())
+    .bind(command.tags.to_string())
+    .bind(command.favorite)
+    .bind(command.environment.clone())
+    .bind(command.pinned)
+    .bind(command.label)
+    .bind(command.expected_exit)
+    .bind(command.os.clone())
+    .bind(command.examples.to_json())
+    .bind(command.enabled)
+    .bind(command.sensitive)
+    .bind(command.shell.clone())
+    .bind(command.expires_at.clone())
+    .bind(command.title.clone())
+    .bind(command.working_dir.clone())
+}
+
+/// Deletes the variant sharing the alias, environment and operating system of `command`
+fn delete_variant_query(command: &Command) -> Query<'static, Sqlite, SqliteArguments<'static>> {
+    sqlx::query(
+        "DELETE FROM commands WHERE alias=? AND IFNULL(environment, '')=IFNULL(?, '') \
+        AND IFNULL(os, '')=IFNULL(?, '');",
+    )
+    .bind(command.alias.clone())
+    .bind(command.environment.clone())
+    .bind(command.os.clone())
+}
+
+/// Column definitions of the commands table, shared by its creation and its rebuilds. The alias
+/// column is `max_alias_len` wide.
+fn commands_columns(max_alias_len: usize) -> String {
+    format!(
+        "command TEXT NOT NULL UNIQUE, \
+    executable VARCHAR(50) NOT NULL, \
+    alias VARCHAR({max_alias_len}) NOT NULL, \
+    description VARCHAR(300) NULL, \
+    tags TEXT NOT NULL DEFAULT '', \
+    favorite INTEGER NOT NULL DEFAULT 0, \
+    environment TEXT NULL, \
+    pinned INTEGER NOT NULL DEFAULT 0, \
+    created_at INTEGER NOT NULL DEFAULT 0, \
+    label TEXT NULL, \
+    use_count INTEGER NOT NULL DEFAULT 0, \
+    expected_exit INTEGER NULL, \
+    os TEXT NULL, \
+    examples TEXT NOT NULL DEFAULT '[]', \
+    enabled INTEGER NOT NULL DEFAULT 1, \
+    archived_at INTEGER NULL, \
+    sensitive INTEGER NOT NULL DEFAULT 0, \
+    shell TEXT NULL, \
+    expires_at TEXT NULL, \
+    title TEXT NULL, \
+    working_dir TEXT NULL"
+    )
+}
+
+/// Columns added after the first release, created on tables that predate them
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("tags", "TEXT NOT NULL DEFAULT ''"),
+    ("favorite", "INTEGER NOT NULL DEFAULT 0"),
+    ("environment", "TEXT NULL"),
+    ("pinned", "INTEGER NOT NULL DEFAULT 0"),
+    // Unix seconds, commands stored before the column existed are left at 0
+    ("created_at", "INTEGER NOT NULL DEFAULT 0"),
+    ("label", "TEXT NULL"),
+    ("use_count", "INTEGER NOT NULL DEFAULT 0"),
+    ("expected_exit", "INTEGER NULL"),
+    ("os", "TEXT NULL"),
+    ("examples", "TEXT NOT NULL DEFAULT '[]'"),
+    ("enabled", "INTEGER NOT NULL DEFAULT 1"),
+    // Unix seconds at which the command was archived, NULL unless it is
+    ("archived_at", "INTEGER NULL"),
+    ("sensitive", "INTEGER NOT NULL DEFAULT 0"),
+    ("shell", "TEXT NULL"),
+    // The last day the command is of use, as YYYY-MM-DD
+    ("expires_at", "TEXT NULL"),
+    ("title", "TEXT NULL"),
+    ("working_dir", "TEXT NULL"),
+];
+
+/// The `ORDER BY` clause of `OrderBy::Alias`, a macro so it can be spliced into queries that
+/// have to be `'static`
+macro_rules! alias_order {
+    () => {
+        "ORDER BY alias, IFNULL(environment, '')"
+    };
+}
+
+/// Orders in which all the commands can be listed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OrderBy {
+    #[default]
+    Alias,
+    Executable,
+    /// Oldest first
+    CreatedAt,
+    /// Most copied or run first
+    Usage,
+}
+
+impl OrderBy {
+    /// The name the order goes by on the command line and in the config, e.g. `created-at`
+    pub fn name(self) -> String {
+        clap::ValueEnum::to_possible_value(&self)
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    /// A fixed `ORDER BY` clause, every order ends on unique columns so ties are deterministic
+    fn clause(self) -> &'static str {
+        match self {
+            OrderBy::Alias => alias_order!(),
+            OrderBy::Executable => "ORDER BY executable, alias, IFNULL(environment, '')",
+            OrderBy::CreatedAt => "ORDER BY created_at, rowid",
+            OrderBy::Usage => "ORDER BY use_count DESC, alias, IFNULL(environment, '')",
+        }
+    }
+}
+
+/// The db file behind a sqlite url such as `sqlite://commands.db`
+pub fn db_file_path(db_url: &str) -> PathBuf {
+    PathBuf::from(db_url.strip_prefix("sqlite://").unwrap_or(db_url))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StorageOptions {
+    /// Unix permissions given to a newly created db file, `None` leaves them to the umask
+    pub file_mode: Option<u32>,
+    /// Width of the alias column, a narrower existing column is widened when the db is opened
+    pub max_alias_len: usize,
+}
+
+impl Default for StorageOptions {
+    fn default() -> Self {
+        // Only the owner can read the commands on multi-user machines
+        StorageOptions {
+            file_mode: Some(0o600),
+            max_alias_len: DEFAULT_MAX_ALIAS_LEN,
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -18,33 +268,113 @@ pub struct CommandStorageManager {
 impl CommandStorageManager {
     #[allow(dead_code)]
     pub async fn new(db_url: &str) -> Result<CommandStorageManager, CommandStorageError> {
-        let pool = CommandStorageManager::db_setup(db_url).await?;
+        CommandStorageManager::with_options(db_url, &StorageOptions::default()).await
+    }
+
+    pub async fn with_options(
+        db_url: &str,
+        options: &StorageOptions,
+    ) -> Result<CommandStorageManager, CommandStorageError> {
+        let pool = CommandStorageManager::db_setup(db_url, options).await?;
+
+        Ok(CommandStorageManager {
+            connection_pool: pool,
+        })
+    }
+
+    /// Opens the db at `db_url` for reading only, e.g. a backup or a db to merge. Its schema is
+    /// left as it is, the columns it predates read as their defaults.
+    pub async fn open_read_only(
+        db_url: &str,
+    ) -> Result<CommandStorageManager, CommandStorageError> {
+        let options = SqliteConnectOptions::from_str(db_url)?.read_only(true);
+        let pool = SqlitePoolOptions::new()
+            .after_connect(|connection, _| {
+                Box::pin(
+                    async move { CommandStorageManager::shadow_missing_columns(connection).await },
+                )
+            })
+            .connect_with(options)
+            .await?;
 
         Ok(CommandStorageManager {
             connection_pool: pool,
         })
     }
 
+    /// Shadows a commands table lacking some of the `ADDED_COLUMNS` with a temporary view that
+    /// fills them with their defaults. The temporary schema is looked up before the main one,
+    /// so the queries reach the view without changing the read only db.
+    async fn shadow_missing_columns(connection: &mut SqliteConnection) -> Result<(), SqlxError> {
+        let existing: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM pragma_table_info('commands');")
+                .fetch_all(&mut *connection)
+                .await?;
+        let defaults: Vec<String> = ADDED_COLUMNS
+            .iter()
+            .filter(|(column, _)| !existing.iter().any(|name| name == column))
+            .map(|(column, definition)| {
+                let default = definition
+                    .split_once("DEFAULT ")
+                    .map_or("NULL", |(_, default)| default);
+                format!("{default} AS {column}")
+            })
+            .collect();
+
+        if !defaults.is_empty() {
+            sqlx::query(&format!(
+                "CREATE TEMP VIEW commands AS SELECT *, {} FROM main.commands;",
+                defaults.join(", ")
+            ))
+            .execute(connection)
+            .await?;
+        }
+
+        Ok(())
+    }
+
     #[allow(dead_code)]
-    pub async fn db_setup(db_url: &str) -> Result<SqlitePool, CommandStorageError> {
+    pub async fn db_setup(
+        db_url: &str,
+        options: &StorageOptions,
+    ) -> Result<SqlitePool, CommandStorageError> {
         // Create the db file
         if !Sqlite::database_exists(db_url).await.unwrap_or(false) {
             info!("Creating database: {db_url}");
             Sqlite::create_database(db_url).await?;
+            CommandStorageManager::set_file_mode(db_url, options)?;
         } else {
             info!("Database: {db_url} already exists");
         }
 
         // create the db connection pool
         let db = SqlitePool::connect(db_url).await?;
+        CommandStorageManager::check_integrity(db_url, &db).await?;
 
         // Create the command tables
-        let _query_result = sqlx::query(
-            "CREATE TABLE IF NOT EXISTS commands \
-            (command VARCHAR(250) NOT NULL UNIQUE, \
-            executable VARCHAR(50) NOT NULL, \
-            alias VARCHAR(20) NOT NULL UNIQUE, \
-            description VARCHAR(300) NULL);",
+        let columns = commands_columns(options.max_alias_len);
+        sqlx::query(&format!("CREATE TABLE IF NOT EXISTS commands ({columns});"))
+            .execute(&db)
+            .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+        )
+        .execute(&db)
+        .await?;
+
+        // Bring tables created by older versions up to date
+        for (column, definition) in ADDED_COLUMNS {
+            CommandStorageManager::add_column_if_missing(&db, column, definition).await?;
+        }
+        CommandStorageManager::drop_alias_uniqueness(&db, &columns).await?;
+        CommandStorageManager::widen_alias_column(&db, &columns, options.max_alias_len).await?;
+        CommandStorageManager::normalize_executables(&db).await?;
+
+        // An alias is unique per environment and operating system, the defaults included
+        CommandStorageManager::drop_index_if_exists(&db, "commands_alias_environment").await?;
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS commands_alias_environment_os \
+            ON commands(alias, IFNULL(environment, ''), IFNULL(os, ''));",
         )
         .execute(&db)
         .await?;
@@ -52,65 +382,874 @@ impl CommandStorageManager {
         Ok(db)
     }
 
+    /// Fails with `CorruptDatabase` unless SQLite finds the db file sound
+    pub async fn check_integrity(db_url: &str, db: &SqlitePool) -> Result<(), CommandStorageError> {
+        let corrupt = |details: String| CommandStorageError::CorruptDatabase {
+            path: db_file_path(db_url).display().to_string(),
+            details,
+        };
+
+        let problems: Vec<(String,)> = match sqlx::query_as("PRAGMA integrity_check;")
+            .fetch_all(db)
+            .await
+        {
+            Ok(problems) => problems,
+            Err(e) if is_corruption(&e) => return Err(corrupt(e.to_string())),
+            Err(e) => return Err(e.into()),
+        };
+
+        match problems.as_slice() {
+            [(status,)] if status == "ok" => Ok(()),
+            _ => Err(corrupt(
+                problems
+                    .into_iter()
+                    .map(|(problem,)| problem)
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            )),
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_file_mode(db_url: &str, options: &StorageOptions) -> Result<(), CommandStorageError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = db_file_path(db_url);
+        if let (Some(mode), true) = (options.file_mode, path.is_file()) {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn set_file_mode(_db_url: &str, _options: &StorageOptions) -> Result<(), CommandStorageError> {
+        Ok(())
+    }
+
+    /// Older versions kept the executable casing, so `Git` and `git` ended up apart.
+    /// Returns the number of commands that were renamed.
+    pub async fn normalize_executables(db: &SqlitePool) -> Result<u64, CommandStorageError> {
+        let misfiled: Option<(i64,)> =
+            sqlx::query_as("SELECT 1 FROM commands WHERE executable != LOWER(executable) LIMIT 1;")
+                .fetch_optional(db)
+                .await?;
+
+        // Opening a db must not need the write lock once it is normalised
+        if misfiled.is_none() {
+            return Ok(0);
+        }
+
+        let query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query(
+                "UPDATE commands SET executable=LOWER(executable) \
+                WHERE executable != LOWER(executable);",
+            )
+            .execute(db)
+        })
+        .await?;
+
+        Ok(query_result.rows_affected())
+    }
+
+    /// Older versions declared the alias column UNIQUE, which forbids environment variants.
+    /// SQLite can not drop a column constraint so the table is rebuilt without it.
+    async fn drop_alias_uniqueness(
+        db: &SqlitePool,
+        columns: &str,
+    ) -> Result<(), CommandStorageError> {
+        let unique_indexes = sqlx::query_scalar::<_, String>(
+            "SELECT name FROM pragma_index_list('commands') WHERE \"unique\" = 1",
+        )
+        .fetch_all(db)
+        .await?;
+
+        let mut alias_unique = false;
+        for index in unique_indexes {
+            let columns =
+                sqlx::query_scalar::<_, Option<String>>("SELECT name FROM pragma_index_info(?)")
+                    .bind(&index)
+                    .fetch_all(db)
+                    .await?;
+
+            alias_unique |= columns == vec![Some("alias".to_string())];
+        }
+
+        if !alias_unique {
+            return Ok(());
+        }
+
+        info!("Rebuilding the commands table to allow environment variants of an alias");
+        CommandStorageManager::rebuild_commands_table(db, columns).await
+    }
+
+    /// Older versions declared a narrower alias column. SQLite does not enforce the width, but
+    /// the declared one is kept in line with the configured limit, so the table is rebuilt with
+    /// the wider column. A wider column is left alone.
+    async fn widen_alias_column(
+        db: &SqlitePool,
+        columns: &str,
+        max_alias_len: usize,
+    ) -> Result<(), CommandStorageError> {
+        let declared = sqlx::query_scalar::<_, String>(
+            "SELECT type FROM pragma_table_info('commands') WHERE name = 'alias'",
+        )
+        .fetch_optional(db)
+        .await?;
+        let width = declared.as_deref().and_then(|declared| {
+            declared
+                .trim_start_matches("VARCHAR(")
+                .trim_end_matches(')')
+                .parse::<usize>()
+                .ok()
+        });
+
+        match width {
+            Some(width) if width < max_alias_len => {
+                info!("Widening the alias column from {width} to {max_alias_len} characters");
+                CommandStorageManager::rebuild_commands_table(db, columns).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Copies the commands into a new table made of `columns`, which replaces the old one
+    async fn rebuild_commands_table(
+        db: &SqlitePool,
+        columns: &str,
+    ) -> Result<(), CommandStorageError> {
+        let names = columns
+            .split(',')
+            .filter_map(|definition| definition.split_whitespace().next())
+            .collect::<Vec<&str>>()
+            .join(", ");
+
+        let mut transaction = db.begin().await?;
+        sqlx::query(&format!("CREATE TABLE commands_migrated ({columns});"))
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query(&format!(
+            "INSERT INTO commands_migrated ({names}) SELECT {names} FROM commands;"
+        ))
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query("DROP TABLE commands;")
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query("ALTER TABLE commands_migrated RENAME TO commands;")
+            .execute(&mut transaction)
+            .await?;
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Drops an index older versions created, only taking the write lock when it is there
+    async fn drop_index_if_exists(db: &SqlitePool, index: &str) -> Result<(), CommandStorageError> {
+        let existing: Option<(String,)> =
+            sqlx::query_as("SELECT name FROM sqlite_master WHERE type='index' AND name=?;")
+                .bind(index)
+                .fetch_optional(db)
+                .await?;
+
+        if existing.is_some() {
+            info!("Dropping the index {index}");
+            sqlx::query(&format!("DROP INDEX {index};"))
+                .execute(db)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn add_column_if_missing(
+        db: &SqlitePool,
+        column: &str,
+        definition: &str,
+    ) -> Result<(), CommandStorageError> {
+        let columns =
+            sqlx::query_scalar::<_, String>("SELECT name FROM pragma_table_info('commands')")
+                .fetch_all(db)
+                .await?;
+
+        if !columns.iter().any(|c| c == column) {
+            info!("Adding the missing column {column} to the commands table");
+            sqlx::query(&format!(
+                "ALTER TABLE commands ADD COLUMN {column} {definition};"
+            ))
+            .execute(db)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// The enabled commands ordered by alias
     pub async fn get_all_commands(&self) -> Result<Vec<Command>, CommandStorageError> {
-        let commands = sqlx::query_as::<_, Command>("SELECT * FROM commands")
-            .fetch_all(&self.connection_pool)
+        self.get_all_commands_ordered(OrderBy::Alias, false).await
+    }
+
+    /// The enabled commands ordered by alias, read from the db one row at a time instead of
+    /// being collected first
+    pub fn stream_all_commands(
+        &self,
+    ) -> impl Stream<Item = Result<Command, CommandStorageError>> + '_ {
+        sqlx::query_as::<_, Command>(concat!(
+            "SELECT * FROM commands WHERE enabled=1 ",
+            alias_order!()
+        ))
+        .fetch(&self.connection_pool)
+        .map_err(CommandStorageError::from)
+    }
+
+    /// Checkpoints the write-ahead log into the db file and closes every connection of the pool.
+    /// Closing the last connection to a file-backed db also removes the `-wal` and `-shm` files,
+    /// which would otherwise linger until the next open. The explicit checkpoint leaves the log
+    /// empty even when another process keeps the db open.
+    pub async fn close(self) {
+        if let Err(e) = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE);")
+            .execute(&self.connection_pool)
+            .await
+        {
+            warn!("Failed to checkpoint the write-ahead log: {e}");
+        }
+
+        // Released connections return to the pool on a spawned task and the pool only closes
+        // the idle ones, a connection still on its way back would be closed later on
+        let deadline = Instant::now() + CLOSE_TIMEOUT;
+        while self.connection_pool.num_idle() < self.connection_pool.size() as usize
+            && Instant::now() < deadline
+        {
+            tokio::time::sleep(RETRY_BASE_DELAY).await;
+        }
+        self.connection_pool.close().await;
+    }
+
+    /// Every enabled command, the disabled ones too when `include_disabled` is set
+    pub async fn get_all_commands_ordered(
+        &self,
+        order_by: OrderBy,
+        include_disabled: bool,
+    ) -> Result<Vec<Command>, CommandStorageError> {
+        let filter = if include_disabled {
+            ""
+        } else {
+            "WHERE enabled=1"
+        };
+        let commands = sqlx::query_as::<_, Command>(&format!(
+            "SELECT * FROM commands {filter} {}",
+            order_by.clause()
+        ))
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(commands)
+    }
+
+    /// Disables every variant of `alias` that is enabled and enables the others, returning the
+    /// number of rows changed. An archived variant is enabled and taken out of the archive.
+    pub async fn toggle_enabled(&self, alias: &str) -> Result<u64, CommandStorageError> {
+        let query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query(
+                "UPDATE commands SET enabled = 1 - enabled, archived_at = NULL WHERE alias=?;",
+            )
+            .bind(alias)
+            .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(query_result.rows_affected())
+    }
+
+    /// Hides every variant of `alias` and stamps it as archived from now on, or restores it
+    /// when `archived` is false. Returns the number of rows changed.
+    pub async fn set_archived(
+        &self,
+        alias: &str,
+        archived: bool,
+    ) -> Result<u64, CommandStorageError> {
+        let query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query(
+                "UPDATE commands SET enabled = NOT ?, archived_at = CASE WHEN ? \
+                THEN CAST(strftime('%s', 'now') AS INTEGER) ELSE NULL END WHERE alias=?;",
+            )
+            .bind(archived)
+            .bind(archived)
+            .bind(alias)
+            .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(query_result.rows_affected())
+    }
+
+    /// Deletes the commands archived before `cutoff`, in Unix seconds, returning how many
+    pub async fn purge_archived_before(&self, cutoff: i64) -> Result<u64, CommandStorageError> {
+        let query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("DELETE FROM commands WHERE archived_at IS NOT NULL AND archived_at < ?;")
+                .bind(cutoff)
+                .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(query_result.rows_affected())
+    }
+
+    /// The value stored under `key` in the settings of the db
+    pub async fn get_setting(&self, key: &str) -> Result<Option<String>, CommandStorageError> {
+        let value = sqlx::query_scalar::<_, String>("SELECT value FROM settings WHERE key=?")
+            .bind(key)
+            .fetch_optional(&self.connection_pool)
             .await?;
 
-        Ok(commands.into_iter().collect())
+        Ok(value)
+    }
+
+    pub async fn set_setting(&self, key: &str, value: &str) -> Result<(), CommandStorageError> {
+        let _query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("INSERT OR REPLACE INTO settings (key, value) VALUES (?, ?);")
+                .bind(key)
+                .bind(value)
+                .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Any one of the sensitive commands, `None` when there are none
+    pub async fn first_sensitive(&self) -> Result<Option<Command>, CommandStorageError> {
+        let command =
+            sqlx::query_as::<_, Command>("SELECT * FROM commands WHERE sensitive=1 LIMIT 1")
+                .fetch_optional(&self.connection_pool)
+                .await?;
+
+        Ok(command)
+    }
+
+    /// Counts one more use of `command`, for the `Usage` order
+    pub async fn record_use(&self, command: &str) -> Result<(), CommandStorageError> {
+        let _query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("UPDATE commands SET use_count=use_count+1 WHERE command=?;")
+                .bind(command)
+                .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(())
     }
 
+    /// Commands filed under `executable`, ignoring case, ordered by alias
     pub async fn get_commands_by_executable(
         &self,
         executable: String,
     ) -> Result<Vec<Command>, CommandStorageError> {
-        let commands = sqlx::query_as::<_, Command>("SELECT * FROM commands where executable=?")
-            .bind(executable)
-            .fetch_all(&self.connection_pool)
-            .await?;
+        let commands = sqlx::query_as::<_, Command>(concat!(
+            "SELECT * FROM commands where executable=? COLLATE NOCASE ",
+            alias_order!()
+        ))
+        .bind(executable)
+        .fetch_all(&self.connection_pool)
+        .await?;
 
         Ok(commands.into_iter().collect())
     }
 
-    pub async fn get_command(&self, command: Command) -> Result<Command, CommandStorageError> {
-        let command = sqlx::query_as::<_, Command>("SELECT * FROM commands where command=?")
-            .bind(command.command)
+    /// Commands whose executable starts with `prefix`, ignoring case, ordered by executable
+    /// and alias. `%` and `_` in the prefix are matched literally.
+    pub async fn get_commands_by_executable_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<Command>, CommandStorageError> {
+        let pattern = format!("{}%", escape_like(prefix));
+        let commands = sqlx::query_as::<_, Command>(
+            "SELECT * FROM commands where executable LIKE ? ESCAPE '\\' \
+            ORDER BY executable, alias, IFNULL(environment, '')",
+        )
+        .bind(pattern)
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(commands)
+    }
+
+    pub async fn get_command_by_alias(&self, alias: &str) -> Result<Command, CommandStorageError> {
+        let command = sqlx::query_as::<_, Command>("SELECT * FROM commands where alias=?")
+            .bind(alias)
             .fetch_one(&self.connection_pool)
             .await?;
 
         Ok(command)
     }
 
-    pub async fn insert_command(&self, command: Command) -> Result<(), CommandStorageError> {
-        let query_result = sqlx::query(
-            "INSERT INTO commands(executable, command, alias, description) VALUES(?, ?, ?, ?);",
+    /// The variants of `alias` in `environment`, one per operating system
+    pub async fn get_by_alias_and_env(
+        &self,
+        alias: &str,
+        environment: Option<&str>,
+    ) -> Result<Vec<Command>, CommandStorageError> {
+        let commands = sqlx::query_as::<_, Command>(
+            "SELECT * FROM commands where alias=? AND IFNULL(environment, '')=IFNULL(?, '')",
+        )
+        .bind(alias)
+        .bind(environment)
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(commands)
+    }
+
+    /// Commands whose alias, command or description contain `query`, at most `limit` of them
+    /// after skipping the first `offset`. `%` and `_` in the query are matched literally.
+    pub async fn search_commands(
+        &self,
+        query: &str,
+        limit: Option<u32>,
+        offset: Option<u32>,
+    ) -> Result<Vec<Command>, CommandStorageError> {
+        let pattern = format!("%{}%", escape_like(query));
+        // A negative limit means no limit to SQLite
+        let limit = limit.map_or(-1, i64::from);
+        let commands = sqlx::query_as::<_, Command>(
+            "SELECT * FROM commands \
+            where (alias LIKE ? ESCAPE '\\' OR command LIKE ? ESCAPE '\\' \
+            OR description LIKE ? ESCAPE '\\') AND enabled=1 \
+            ORDER BY executable, alias, IFNULL(environment, '') LIMIT ? OFFSET ?",
+        )
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(limit)
+        .bind(offset.unwrap_or_default())
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(commands)
+    }
+
+    /// The `limit` most recently created commands, newest first
+    pub async fn get_recent_commands(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<Command>, CommandStorageError> {
+        let commands = sqlx::query_as::<_, Command>(
+            "SELECT * FROM commands WHERE enabled=1 ORDER BY created_at DESC, rowid DESC LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(commands)
+    }
+
+    /// Number of commands `search_commands` finds for `query` without a limit
+    pub async fn count_search_matches(&self, query: &str) -> Result<i64, CommandStorageError> {
+        let pattern = format!("%{}%", escape_like(query));
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM commands \
+            where (alias LIKE ? ESCAPE '\\' OR command LIKE ? ESCAPE '\\' \
+            OR description LIKE ? ESCAPE '\\') AND enabled=1",
         )
-        .bind(command.executable)
-        .bind(command.command)
-        .bind(command.alias)
-        .bind(command.description)
-        .execute(&self.connection_pool)
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(&pattern)
+        .fetch_one(&self.connection_pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Checks whether a command with the same command string or the same alias is stored
+    pub async fn command_exists(
+        &self,
+        command: &str,
+        alias: &str,
+    ) -> Result<bool, CommandStorageError> {
+        let exists = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM commands where command=? OR alias=?)",
+        )
+        .bind(command)
+        .bind(alias)
+        .fetch_one(&self.connection_pool)
+        .await?;
+
+        Ok(exists)
+    }
+
+    pub async fn get_command_by_command_or_alias(
+        &self,
+        command: &str,
+        alias: &str,
+    ) -> Result<Command, CommandStorageError> {
+        let command =
+            sqlx::query_as::<_, Command>("SELECT * FROM commands where command=? OR alias=?")
+                .bind(command)
+                .bind(alias)
+                .fetch_one(&self.connection_pool)
+                .await?;
+
+        Ok(command)
+    }
+
+    /// Gets the row matching both the command string and the alias of `command`, if any
+    pub async fn get_command(
+        &self,
+        command: Command,
+    ) -> Result<Option<Command>, CommandStorageError> {
+        let command =
+            sqlx::query_as::<_, Command>("SELECT * FROM commands where command=? AND alias=?")
+                .bind(command.command)
+                .bind(command.alias)
+                .fetch_optional(&self.connection_pool)
+                .await?;
+
+        Ok(command)
+    }
+
+    /// Inserts `command`, returning the number of rows added
+    pub async fn insert_command(&self, command: Command) -> Result<u64, CommandStorageError> {
+        let query_result = with_retry(RETRY_ATTEMPTS, || {
+            insert_query(&command).execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(query_result.rows_affected())
+    }
+
+    /// Stores `command` in place of the variant stored for its alias, environment and operating
+    /// system. Both happen in one transaction, so the stored variant is kept when `command` can
+    /// not be inserted.
+    pub async fn replace_variant(&self, command: &Command) -> Result<(), CommandStorageError> {
+        with_retry(RETRY_ATTEMPTS, || {
+            self.replace_variant_in_transaction(command)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn replace_variant_in_transaction(&self, command: &Command) -> Result<(), SqlxError> {
+        let mut transaction = self.connection_pool.begin().await?;
+        delete_variant_query(command)
+            .execute(&mut transaction)
+            .await?;
+        insert_query(command).execute(&mut transaction).await?;
+
+        transaction.commit().await
+    }
+
+    pub async fn update_description(
+        &self,
+        command: &str,
+        description: Option<&str>,
+    ) -> Result<(), CommandStorageError> {
+        let _query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("UPDATE commands SET description=? WHERE command=?;")
+                .bind(description)
+                .bind(command)
+                .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Replaces the command string of the stored `command`, along with its executable
+    pub async fn update_command(
+        &self,
+        command: &str,
+        updated: &Command,
+    ) -> Result<(), CommandStorageError> {
+        let _query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("UPDATE commands SET command=?, executable=? WHERE command=?;")
+                .bind(&updated.command)
+                .bind(&updated.executable)
+                .bind(command)
+                .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_tags(&self, command: &str, tags: &Tags) -> Result<(), CommandStorageError> {
+        let _query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("UPDATE commands SET tags=? WHERE command=?;")
+                .bind(tags.to_string())
+                .bind(command)
+                .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Moves every variant of `alias` to `new_alias` and points the `@alias` references of the
+    /// other commands to it, in one transaction. Returns the number of rows renamed. Sealed
+    /// commands can not be read here, so their references are left as they are.
+    pub async fn rename_alias(
+        &self,
+        alias: &str,
+        new_alias: &str,
+    ) -> Result<u64, CommandStorageError> {
+        let renamed = with_retry(RETRY_ATTEMPTS, || {
+            self.rename_alias_in_transaction(alias, new_alias)
+        })
+        .await?;
+
+        Ok(renamed)
+    }
+
+    async fn rename_alias_in_transaction(
+        &self,
+        alias: &str,
+        new_alias: &str,
+    ) -> Result<u64, SqlxError> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        let renamed = sqlx::query("UPDATE commands SET alias=? WHERE alias=?;")
+            .bind(new_alias)
+            .bind(alias)
+            .execute(&mut transaction)
+            .await?
+            .rows_affected();
+        // References to an alias that is not stored are left alone
+        if renamed == 0 {
+            return Ok(0);
+        }
+
+        let referencing: Vec<(String,)> =
+            sqlx::query_as("SELECT command FROM commands WHERE instr(command, ?) > 0;")
+                .bind(format!("@{alias}"))
+                .fetch_all(&mut transaction)
+                .await?;
+        for (command,) in referencing {
+            let rewritten = rewrite_references(&command, alias, new_alias);
+            if rewritten != command {
+                sqlx::query("UPDATE commands SET command=? WHERE command=?;")
+                    .bind(rewritten)
+                    .bind(command)
+                    .execute(&mut transaction)
+                    .await?;
+            }
+        }
+
+        transaction.commit().await?;
+
+        Ok(renamed)
+    }
+
+    /// Pins every environment variant of `alias` to the top of its tab, or unpins them
+    pub async fn set_pinned(&self, alias: &str, pinned: bool) -> Result<(), CommandStorageError> {
+        let _query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("UPDATE commands SET pinned=? WHERE alias=?;")
+                .bind(pinned)
+                .bind(alias)
+                .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_label(
+        &self,
+        alias: &str,
+        label: Option<Label>,
+    ) -> Result<(), CommandStorageError> {
+        let _query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("UPDATE commands SET label=? WHERE alias=?;")
+                .bind(label)
+                .bind(alias)
+                .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_expected_exit(
+        &self,
+        command: &str,
+        expected_exit: Option<i32>,
+    ) -> Result<(), CommandStorageError> {
+        let _query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("UPDATE commands SET expected_exit=? WHERE command=?;")
+                .bind(expected_exit)
+                .bind(command)
+                .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_executable(
+        &self,
+        command: &str,
+        executable: &str,
+    ) -> Result<(), CommandStorageError> {
+        let _query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("UPDATE commands SET executable=? WHERE command=?;")
+                .bind(executable)
+                .bind(command)
+                .execute(&self.connection_pool)
+        })
         .await?;
 
         Ok(())
     }
 
-    pub async fn delete_command(&self, command: Command) -> Result<(), CommandStorageError> {
-        let query_result = sqlx::query("DELETE FROM commands WHERE command=?;")
-            .bind(command.command)
+    /// Writes a consistent copy of the whole database to `path`, which must not exist yet
+    pub async fn backup_to(&self, path: &str) -> Result<(), CommandStorageError> {
+        let _query_result = sqlx::query("VACUUM INTO ?")
+            .bind(path)
             .execute(&self.connection_pool)
             .await?;
 
         Ok(())
     }
+
+    /// Size of the database in bytes, its free pages included
+    pub async fn size(&self) -> Result<u64, CommandStorageError> {
+        let (size,): (i64,) = sqlx::query_as(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size();",
+        )
+        .fetch_one(&self.connection_pool)
+        .await?;
+
+        Ok(size.try_into().unwrap_or_default())
+    }
+
+    /// Rebuilds the database to give the pages freed by deletes back to the file system
+    pub async fn vacuum(&self) -> Result<(), CommandStorageError> {
+        sqlx::query("VACUUM;")
+            .execute(&self.connection_pool)
+            .await?;
+        // The rebuilt pages sit in the write-ahead log until it is checkpointed
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE);")
+            .execute(&self.connection_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Deletes the variant of `command` for its environment and operating system
+    pub async fn delete_variant(&self, command: &Command) -> Result<(), CommandStorageError> {
+        let _query_result = with_retry(RETRY_ATTEMPTS, || {
+            delete_variant_query(command).execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Deletes the row storing the command string of `command`, returning the number of rows
+    /// deleted: zero when there was none
+    pub async fn delete_command(&self, command: Command) -> Result<u64, CommandStorageError> {
+        let query_result = with_retry(RETRY_ATTEMPTS, || {
+            sqlx::query("DELETE FROM commands WHERE command=?;")
+                .bind(command.command.clone())
+                .execute(&self.connection_pool)
+        })
+        .await?;
+
+        Ok(query_result.rows_affected())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+    use std::error::Error as StdError;
+
+    use futures::TryStreamExt;
     use serial_test::serial;
+    use sqlx::error::DatabaseError;
+    use sqlx::Error as SqlxError;
+
+    use sqlx::{migrate::MigrateDatabase, Sqlite, SqlitePool};
+
+    use super::{with_retry, CommandStorageError, CommandStorageManager, OrderBy};
+    use crate::model::command::{Command, DEFAULT_MAX_ALIAS_LEN};
+
+    #[derive(Debug)]
+    struct BusyError;
+
+    impl std::fmt::Display for BusyError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "database is locked")
+        }
+    }
+
+    impl StdError for BusyError {}
+
+    impl DatabaseError for BusyError {
+        fn message(&self) -> &str {
+            "database is locked"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some("5".into())
+        }
 
-    use super::CommandStorageManager;
-    use crate::model::command::Command;
+        fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+            self
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_busy() {
+        let mut calls = 0;
+
+        let res = with_retry(3, || {
+            calls += 1;
+            let current = calls;
+            async move {
+                if current <= 2 {
+                    Err(SqlxError::Database(Box::new(BusyError)))
+                } else {
+                    Ok(current)
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(res.unwrap(), 3);
+        assert_eq!(calls, 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let mut calls = 0;
+
+        let res: Result<(), SqlxError> = with_retry(2, || {
+            calls += 1;
+            async { Err(SqlxError::Database(Box::new(BusyError))) }
+        })
+        .await;
+
+        assert!(res.is_err());
+        assert_eq!(calls, 2);
+
+        let mut calls = 0;
+        let res: Result<(), SqlxError> = with_retry(5, || {
+            calls += 1;
+            async { Err(SqlxError::RowNotFound) }
+        })
+        .await;
+
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+    }
 
     #[tokio::test]
     #[serial]
@@ -124,9 +1263,10 @@ mod tests {
             command: "git pull".to_string(),
             alias: "git_pull".to_string(),
             description: None,
+            ..Default::default()
         };
 
-        manager.insert_command(command.clone()).await.unwrap();
+        assert_eq!(manager.insert_command(command.clone()).await.unwrap(), 1);
 
         let commands = manager
             .get_commands_by_executable(command.executable.clone())
@@ -137,18 +1277,20 @@ mod tests {
 
         assert_eq!(commands.len(), 1);
 
-        manager.delete_command(command).await.unwrap();
+        assert_eq!(manager.delete_command(command.clone()).await.unwrap(), 1);
+        assert_eq!(manager.delete_command(command).await.unwrap(), 0);
 
         let command = Command {
             executable: "ssh".to_string(),
             command: "ssh --version".to_string(),
             alias: "ssh_version".to_string(),
             description: None,
+            ..Default::default()
         };
 
         manager.insert_command(command.clone()).await.unwrap();
 
-        let command = manager.get_command(command).await.unwrap();
+        let command = manager.get_command(command).await.unwrap().unwrap();
         println!("Single command: {command:?}");
 
         assert_eq!(command.executable, "ssh".to_string());
@@ -157,4 +1299,409 @@ mod tests {
         let _ = std::fs::remove_file("sqlite.db-shm");
         let _ = std::fs::remove_file("sqlite.db-wal");
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_alias_uniqueness_migration() {
+        // A table as created by the first release
+        {
+            Sqlite::create_database("sqlite://migration.db")
+                .await
+                .unwrap();
+            let pool = SqlitePool::connect("sqlite://migration.db").await.unwrap();
+            sqlx::query(
+                "CREATE TABLE commands \
+                (command VARCHAR(250) NOT NULL UNIQUE, \
+                executable VARCHAR(50) NOT NULL, \
+                alias VARCHAR(20) NOT NULL UNIQUE, \
+                description VARCHAR(300) NULL);",
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            sqlx::query(
+                "INSERT INTO commands VALUES('kubectl get pods', 'kubectl', 'pods', NULL);",
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            pool.close().await;
+        }
+
+        let manager = CommandStorageManager::new("sqlite://migration.db")
+            .await
+            .unwrap();
+
+        let variant = Command {
+            executable: "kubectl".to_string(),
+            command: "kubectl --context prod get pods".to_string(),
+            alias: "pods".to_string(),
+            environment: Some("prod".to_string()),
+            ..Default::default()
+        };
+        manager.insert_command(variant).await.unwrap();
+
+        let commands = manager.get_all_commands().await.unwrap();
+        assert_eq!(commands.len(), 2);
+        assert!(manager.get_by_alias_and_env("pods", None).await.is_ok());
+
+        let _ = std::fs::remove_file("migration.db");
+        let _ = std::fs::remove_file("migration.db-shm");
+        let _ = std::fs::remove_file("migration.db-wal");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_open_read_only() {
+        // A table as created by the first release
+        {
+            Sqlite::create_database("sqlite://migration.db")
+                .await
+                .unwrap();
+            let pool = SqlitePool::connect("sqlite://migration.db").await.unwrap();
+            sqlx::query(
+                "CREATE TABLE commands \
+                (command VARCHAR(250) NOT NULL UNIQUE, \
+                executable VARCHAR(50) NOT NULL, \
+                alias VARCHAR(20) NOT NULL UNIQUE, \
+                description VARCHAR(300) NULL);",
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            sqlx::query("INSERT INTO commands VALUES('git status', 'git', 'status', NULL);")
+                .execute(&pool)
+                .await
+                .unwrap();
+            pool.close().await;
+        }
+
+        let manager = CommandStorageManager::open_read_only("sqlite://migration.db")
+            .await
+            .unwrap();
+        let commands = manager
+            .get_all_commands_ordered(OrderBy::Alias, true)
+            .await
+            .unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].alias, "status");
+        assert!(commands[0].enabled);
+        assert!(manager
+            .insert_command(Command {
+                executable: "git".to_string(),
+                command: "git pull".to_string(),
+                alias: "pull".to_string(),
+                ..Default::default()
+            })
+            .await
+            .is_err());
+        manager.close().await;
+
+        // The schema of the db is left as it was
+        let pool = SqlitePool::connect("sqlite://migration.db").await.unwrap();
+        let columns: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM pragma_table_info('commands');")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(columns, ["command", "executable", "alias", "description"]);
+        pool.close().await;
+
+        let _ = std::fs::remove_file("migration.db");
+        let _ = std::fs::remove_file("migration.db-shm");
+        let _ = std::fs::remove_file("migration.db-wal");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_alias_column_widening() {
+        // A table from before the alias limit was configurable
+        {
+            Sqlite::create_database("sqlite://migration.db")
+                .await
+                .unwrap();
+            let pool = SqlitePool::connect("sqlite://migration.db").await.unwrap();
+            sqlx::query(
+                "CREATE TABLE commands \
+                (command VARCHAR(250) NOT NULL UNIQUE, \
+                executable VARCHAR(50) NOT NULL, \
+                alias VARCHAR(20) NOT NULL, \
+                description VARCHAR(300) NULL);",
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            sqlx::query("INSERT INTO commands VALUES('git status', 'git', 'status', NULL);")
+                .execute(&pool)
+                .await
+                .unwrap();
+            pool.close().await;
+        }
+
+        let manager = CommandStorageManager::new("sqlite://migration.db")
+            .await
+            .unwrap();
+
+        let declared = sqlx::query_scalar::<_, String>(
+            "SELECT type FROM pragma_table_info('commands') WHERE name = 'alias'",
+        )
+        .fetch_one(&manager.connection_pool)
+        .await
+        .unwrap();
+        assert_eq!(declared, format!("VARCHAR({DEFAULT_MAX_ALIAS_LEN})"));
+
+        let alias = "a".repeat(40);
+        let command = Command {
+            executable: "git".to_string(),
+            command: "git log --oneline".to_string(),
+            alias: alias.clone(),
+            ..Default::default()
+        };
+        manager.insert_command(command).await.unwrap();
+
+        assert!(manager.get_by_alias_and_env(&alias, None).await.is_ok());
+        assert!(manager.get_by_alias_and_env("status", None).await.is_ok());
+
+        let _ = std::fs::remove_file("migration.db");
+        let _ = std::fs::remove_file("migration.db-shm");
+        let _ = std::fs::remove_file("migration.db-wal");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    #[serial]
+    async fn test_db_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _manager = CommandStorageManager::new("sqlite://permissions.db")
+            .await
+            .unwrap();
+
+        let mode = std::fs::metadata("permissions.db")
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let _ = std::fs::remove_file("permissions.db");
+        let _ = std::fs::remove_file("permissions.db-shm");
+        let _ = std::fs::remove_file("permissions.db-wal");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_executable_case_insensitive() {
+        let manager = CommandStorageManager::new("sqlite://test.sqlite")
+            .await
+            .unwrap();
+
+        for (executable, command, alias) in
+            [("Git", "Git pull", "pull"), ("git", "git push", "push")]
+        {
+            let command = Command {
+                executable: executable.to_string(),
+                command: command.to_string(),
+                alias: alias.to_string(),
+                ..Default::default()
+            };
+            manager.insert_command(command).await.unwrap();
+        }
+
+        let commands = manager
+            .get_commands_by_executable("GIT".to_string())
+            .await
+            .unwrap();
+        assert_eq!(commands.len(), 2);
+
+        let renamed = CommandStorageManager::normalize_executables(&manager.connection_pool)
+            .await
+            .unwrap();
+        assert_eq!(renamed, 1);
+
+        let commands = manager.get_all_commands().await.unwrap();
+        assert!(commands.iter().all(|command| command.executable == "git"));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_integrity_check() {
+        let manager = CommandStorageManager::new("sqlite://test.sqlite")
+            .await
+            .unwrap();
+        CommandStorageManager::check_integrity("test.sqlite", &manager.connection_pool)
+            .await
+            .unwrap();
+
+        for i in 0..200 {
+            let command = Command {
+                executable: "echo".to_string(),
+                command: format!("echo {i} {}", "x".repeat(200)),
+                alias: format!("echo_{i}"),
+                ..Default::default()
+            };
+            manager.insert_command(command).await.unwrap();
+        }
+        // Move everything out of the WAL into the db file before cutting it in half
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE);")
+            .execute(&manager.connection_pool)
+            .await
+            .unwrap();
+        manager.connection_pool.close().await;
+
+        let db = std::fs::read("test.sqlite").unwrap();
+        std::fs::write("test.sqlite", &db[..db.len() / 2]).unwrap();
+
+        let res = CommandStorageManager::new("sqlite://test.sqlite").await;
+        assert!(matches!(
+            res,
+            Err(CommandStorageError::CorruptDatabase { .. })
+        ));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+        let _ = std::fs::remove_file("test.sqlite-shm");
+        let _ = std::fs::remove_file("test.sqlite-wal");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_get_all_commands_ordered() {
+        let manager = CommandStorageManager::new("test.sqlite").await.unwrap();
+        for (executable, command, alias) in [
+            ("ls", "ls -a", "all"),
+            ("git", "git pull", "pull"),
+            ("git", "git add .", "stage"),
+        ] {
+            manager
+                .insert_command(Command::new(
+                    executable.to_string(),
+                    command.to_string(),
+                    alias.to_string(),
+                    None,
+                ))
+                .await
+                .unwrap();
+        }
+        manager.record_use("git add .").await.unwrap();
+
+        let aliases = |commands: Vec<Command>| -> Vec<String> {
+            commands.into_iter().map(|command| command.alias).collect()
+        };
+
+        let by_alias = manager.get_all_commands().await.unwrap();
+        assert_eq!(aliases(by_alias), vec!["all", "pull", "stage"]);
+
+        let by_executable = manager
+            .get_all_commands_ordered(OrderBy::Executable, false)
+            .await
+            .unwrap();
+        assert_eq!(aliases(by_executable), vec!["pull", "stage", "all"]);
+
+        let by_creation = manager
+            .get_all_commands_ordered(OrderBy::CreatedAt, false)
+            .await
+            .unwrap();
+        assert_eq!(aliases(by_creation), vec!["all", "pull", "stage"]);
+
+        let by_usage = manager
+            .get_all_commands_ordered(OrderBy::Usage, false)
+            .await
+            .unwrap();
+        assert_eq!(aliases(by_usage), vec!["stage", "all", "pull"]);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_stream_all_commands() {
+        let manager = CommandStorageManager::new("test.sqlite").await.unwrap();
+        for (command, alias) in [
+            ("git pull", "pull"),
+            ("ls -a", "all"),
+            ("git add .", "stage"),
+        ] {
+            manager
+                .insert_command(Command::new(
+                    command.split(' ').next().unwrap().to_string(),
+                    command.to_string(),
+                    alias.to_string(),
+                    None,
+                ))
+                .await
+                .unwrap();
+        }
+        manager.toggle_enabled("stage").await.unwrap();
+
+        let streamed: Vec<Command> = manager.stream_all_commands().try_collect().await.unwrap();
+        assert_eq!(streamed, manager.get_all_commands().await.unwrap());
+        assert_eq!(streamed.len(), 2);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_get_commands_by_executable_prefix() {
+        let manager = CommandStorageManager::new("test.sqlite").await.unwrap();
+        for (executable, command, alias) in [
+            ("git", "git pull", "pull"),
+            ("github-cli", "github-cli pr list", "prs"),
+            ("digit", "digit 7", "seven"),
+            ("git_lfs", "git_lfs pull", "lfs"),
+        ] {
+            manager
+                .insert_command(Command::new(
+                    executable.to_string(),
+                    command.to_string(),
+                    alias.to_string(),
+                    None,
+                ))
+                .await
+                .unwrap();
+        }
+
+        let aliases = |commands: Vec<Command>| -> Vec<String> {
+            commands.into_iter().map(|command| command.alias).collect()
+        };
+
+        let git = manager.get_commands_by_executable_prefix("git").await;
+        assert_eq!(aliases(git.unwrap()), vec!["pull", "lfs", "prs"]);
+
+        let literal = manager.get_commands_by_executable_prefix("git_").await;
+        assert_eq!(aliases(literal.unwrap()), vec!["lfs"]);
+
+        let wildcard = manager.get_commands_by_executable_prefix("%").await;
+        assert!(wildcard.unwrap().is_empty());
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_close_checkpoints_the_wal() {
+        let manager = CommandStorageManager::new("close.sqlite").await.unwrap();
+        manager
+            .insert_command(Command::new(
+                "git".to_string(),
+                "git pull".to_string(),
+                "git_pull".to_string(),
+                None,
+            ))
+            .await
+            .unwrap();
+        assert!(std::path::Path::new("close.sqlite-wal").exists());
+
+        manager.close().await;
+        assert!(!std::path::Path::new("close.sqlite-wal").exists());
+        assert!(!std::path::Path::new("close.sqlite-shm").exists());
+
+        // The command made it into the db file itself
+        let manager = CommandStorageManager::new("close.sqlite").await.unwrap();
+        assert_eq!(manager.get_all_commands().await.unwrap().len(), 1);
+        manager.close().await;
+
+        std::fs::remove_file("close.sqlite").unwrap();
+    }
 }
@@ -0,0 +1,330 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::KeyCode;
+
+use crate::config::keymap::KeyMap;
+
+/// Input mode of the TUI, deciding how key presses are interpreted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Normal,
+    /// Choosing which environment variant of an alias to copy
+    PickEnvironment,
+    /// Typing a query matched against the commands of every tab
+    GlobalSearch,
+    /// Typing a tag to add to the selected commands
+    TagInput,
+    /// Typing the path the visible commands are exported to
+    ExportInput,
+    /// Filling the fields of a form
+    Form,
+    /// Confirming the copy of a dangerous command
+    ConfirmDanger,
+    /// Confirming that the edits of the open form are thrown away
+    ConfirmDiscard,
+    /// Typing the first letters of the tabs to jump to
+    JumpToTab,
+    /// Editing the alias of the selected command in place
+    RenameInput,
+}
+
+/// A state transition requested by the user
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    NextTab,
+    PreviousTab,
+    NextCommand,
+    PreviousCommand,
+    /// Select the first command of the tab
+    JumpToTop,
+    CycleGroup,
+    /// Jump to the tabs by their first letter until another key is pressed
+    StartJump,
+    StartSearch,
+    /// A character typed into the search query
+    Input(char),
+    DeleteInput,
+    ScrollDown,
+    ScrollUp,
+    Copy,
+    CopyWithSudo,
+    /// Copy the selected command with the absolute path of its executable
+    CopyResolved,
+    Delete,
+    Undo,
+    TogglePin,
+    /// Disable the highlighted command, hiding it, or enable it again
+    ToggleEnabled,
+    /// List the disabled commands too, or hide them again
+    ToggleShowDisabled,
+    /// Add the highlighted command to the multi-selection, or remove it
+    ToggleSelect,
+    StartTagging,
+    /// Edit the alias of the highlighted command in place
+    StartRename,
+    EditDescription,
+    Duplicate,
+    EditInEditor,
+    CycleLabel,
+    /// Expand or collapse the examples under the description
+    ToggleExamples,
+    /// List the commands by their command string instead of their alias, or back
+    ToggleListDisplay,
+    Run,
+    AppendOutput,
+    /// Ask where to export the commands in view
+    StartExport,
+    Confirm,
+    Cancel,
+    Quit,
+    None,
+}
+
+/// What the event loop has to do after an action was applied
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Quit,
+    /// Copy the selected command, then close the TUI if `exit` is set
+    Copy {
+        exit: bool,
+    },
+    /// Same as `Copy`, with the command prefixed by `sudo`
+    CopyWithSudo {
+        exit: bool,
+    },
+    /// Same as `Copy`, with the executable replaced by its absolute path
+    CopyResolved {
+        exit: bool,
+    },
+    /// Delete the selected command from the db
+    Delete,
+    /// Revert the last change through the db
+    Undo,
+    /// Pin or unpin the selected command through the db
+    TogglePin,
+    /// Disable or enable the selected command through the db
+    ToggleEnabled,
+    /// Add the typed tag to the selected commands through the db
+    ApplyTag,
+    /// Give the selected command the typed alias through the db
+    Rename,
+    /// Save the validated form through the db
+    SubmitForm,
+    /// Edit the selected command in `$EDITOR` with the terminal suspended
+    EditInEditor,
+    /// Give the selected command the next color label through the db
+    CycleLabel,
+    /// Run the selected command and capture its output
+    Run,
+    /// Add the output of the last run to the description of its command through the db
+    AppendOutput,
+    /// Write the commands in view to the typed path
+    Export,
+}
+
+/// The normal mode follows the configured keymap, the popups keep fixed keys
+pub fn map_key(key: KeyCode, mode: Mode, keymap: &KeyMap) -> Action {
+    match mode {
+        Mode::Normal => keymap.action(key),
+        Mode::PickEnvironment => match key {
+            KeyCode::Down => Action::NextCommand,
+            KeyCode::Up => Action::PreviousCommand,
+            KeyCode::Enter => Action::Copy,
+            KeyCode::Esc | KeyCode::Char('q') => Action::Cancel,
+            _ => Action::None,
+        },
+        Mode::ConfirmDanger | Mode::ConfirmDiscard => match key {
+            KeyCode::Char('y') | KeyCode::Char('Y') => Action::Confirm,
+            _ => Action::Cancel,
+        },
+        Mode::Form => match key {
+            KeyCode::Tab | KeyCode::Down => Action::NextCommand,
+            KeyCode::BackTab | KeyCode::Up => Action::PreviousCommand,
+            KeyCode::Enter => Action::Copy,
+            KeyCode::Esc => Action::Cancel,
+            KeyCode::Backspace => Action::DeleteInput,
+            KeyCode::Char(c) => Action::Input(c),
+            _ => Action::None,
+        },
+        Mode::JumpToTab => match key {
+            KeyCode::Char(c) => Action::Input(c),
+            _ => Action::Cancel,
+        },
+        Mode::TagInput | Mode::ExportInput | Mode::RenameInput => match key {
+            KeyCode::Enter => Action::Copy,
+            KeyCode::Esc => Action::Cancel,
+            KeyCode::Backspace => Action::DeleteInput,
+            KeyCode::Char(c) => Action::Input(c),
+            _ => Action::None,
+        },
+        Mode::GlobalSearch => match key {
+            KeyCode::Tab => Action::StartExport,
+            KeyCode::Down => Action::NextCommand,
+            KeyCode::Up => Action::PreviousCommand,
+            KeyCode::Enter => Action::Copy,
+            KeyCode::Esc => Action::Cancel,
+            KeyCode::Backspace => Action::DeleteInput,
+            KeyCode::Char(c) => Action::Input(c),
+            _ => Action::None,
+        },
+    }
+}
+
+/// Two-key sequences of the normal mode and the action each performs, e.g. `gg`
+pub const CHORDS: &[(char, char, Action)] =
+    &[('g', 'g', Action::JumpToTop), ('d', 'd', Action::Delete)];
+
+/// How long the first key of a chord waits for the second one
+pub const CHORD_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The first key of a chord while the second one is awaited
+#[derive(Debug, Default)]
+pub struct ChordState {
+    pending: Option<(char, Instant)>,
+}
+
+impl ChordState {
+    /// Like `map_key`, except that the first key of a chord is held back and yields
+    /// `Action::None` until the next key completes the chord. Any other key drops the pending
+    /// one and is mapped on its own.
+    pub fn map_key(&mut self, key: KeyCode, mode: Mode, keymap: &KeyMap, now: Instant) -> Action {
+        if mode != Mode::Normal {
+            self.pending = None;
+            return map_key(key, mode, keymap);
+        }
+
+        if let Some((first, started)) = self.pending.take() {
+            let chord = CHORDS
+                .iter()
+                .find(|(start, second, _)| *start == first && key == KeyCode::Char(*second));
+            if let Some((_, _, action)) = chord {
+                if now.duration_since(started) < CHORD_TIMEOUT {
+                    return *action;
+                }
+            }
+        }
+
+        match key {
+            KeyCode::Char(c) if CHORDS.iter().any(|(start, _, _)| *start == c) => {
+                self.pending = Some((c, now));
+                Action::None
+            }
+            _ => map_key(key, mode, keymap),
+        }
+    }
+
+    /// The action bound to the pending key on its own once no second key came in time
+    pub fn expire(&mut self, now: Instant, keymap: &KeyMap) -> Option<Action> {
+        let (first, started) = self.pending?;
+        if now.duration_since(started) < CHORD_TIMEOUT {
+            return None;
+        }
+
+        self.pending = None;
+        Some(keymap.action(KeyCode::Char(first)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crossterm::event::KeyCode;
+
+    use super::{map_key, Action, ChordState, Mode, CHORD_TIMEOUT};
+    use crate::config::keymap::KeyMap;
+
+    #[test]
+    fn test_map_navigation_keys() {
+        let keymap = KeyMap::default();
+        assert_eq!(
+            map_key(KeyCode::Up, Mode::Normal, &keymap),
+            Action::PreviousCommand
+        );
+        assert_eq!(
+            map_key(KeyCode::Down, Mode::Normal, &keymap),
+            Action::NextCommand
+        );
+        assert_eq!(
+            map_key(KeyCode::Left, Mode::Normal, &keymap),
+            Action::PreviousTab
+        );
+        assert_eq!(
+            map_key(KeyCode::Right, Mode::Normal, &keymap),
+            Action::NextTab
+        );
+    }
+
+    #[test]
+    fn test_map_copy_and_quit_keys() {
+        let keymap = KeyMap::default();
+        assert_eq!(map_key(KeyCode::Enter, Mode::Normal, &keymap), Action::Copy);
+        assert_eq!(
+            map_key(KeyCode::Char('q'), Mode::Normal, &keymap),
+            Action::Quit
+        );
+        assert_eq!(
+            map_key(KeyCode::Char('u'), Mode::Normal, &keymap),
+            Action::Undo
+        );
+        assert_eq!(
+            map_key(KeyCode::Char('z'), Mode::Normal, &keymap),
+            Action::None
+        );
+    }
+
+    #[test]
+    fn test_chords() {
+        let keymap = KeyMap::default();
+        let mut chords = ChordState::default();
+        let now = Instant::now();
+        let mut feed = |key: KeyCode| chords.map_key(key, Mode::Normal, &keymap, now);
+
+        assert_eq!(feed(KeyCode::Char('g')), Action::None);
+        assert_eq!(feed(KeyCode::Char('g')), Action::JumpToTop);
+        assert_eq!(feed(KeyCode::Char('d')), Action::None);
+        assert_eq!(feed(KeyCode::Char('d')), Action::Delete);
+
+        // Another key resets the pending chord and is mapped on its own
+        assert_eq!(feed(KeyCode::Char('g')), Action::None);
+        assert_eq!(feed(KeyCode::Down), Action::NextCommand);
+        assert_eq!(feed(KeyCode::Char('q')), Action::Quit);
+        assert_eq!(chords.expire(now + CHORD_TIMEOUT, &keymap), None);
+
+        // A lone first key does what it is bound to once the chord timed out
+        assert_eq!(
+            chords.map_key(KeyCode::Char('g'), Mode::Normal, &keymap, now),
+            Action::None
+        );
+        assert_eq!(chords.expire(now, &keymap), None);
+        assert_eq!(
+            chords.expire(now + CHORD_TIMEOUT, &keymap),
+            Some(Action::CycleGroup)
+        );
+        assert_eq!(chords.expire(now + CHORD_TIMEOUT, &keymap), None);
+
+        // The popups type the keys of the chords
+        assert_eq!(
+            chords.map_key(KeyCode::Char('g'), Mode::Form, &keymap, now),
+            Action::Input('g')
+        );
+    }
+
+    #[test]
+    fn test_map_search_keys() {
+        let keymap = KeyMap::default();
+        assert_eq!(
+            map_key(KeyCode::Char('G'), Mode::Normal, &keymap),
+            Action::StartSearch
+        );
+        assert_eq!(
+            map_key(KeyCode::Char('q'), Mode::GlobalSearch, &keymap),
+            Action::Input('q')
+        );
+        assert_eq!(
+            map_key(KeyCode::Esc, Mode::GlobalSearch, &keymap),
+            Action::Cancel
+        );
+    }
+}
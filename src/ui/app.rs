@@ -1,10 +1,24 @@
-use clipboard::{ClipboardContext, ClipboardProvider};
 use ratatui::widgets::ListState;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
-use crate::model::command::Command;
-use crate::service::command_service::{CommandService, CommandServiceError};
+use super::action::{Action, Mode, Outcome};
+use super::clipboard::Clipboard;
+use super::form::{Form, FormKind};
+use crate::cli::read_passphrase;
+use crate::config::app_config::AppConfig;
+use crate::model::command::{
+    rewrite_references, select_for_os, Command, Label, MAX_DESCRIPTION_LEN,
+};
+use crate::model::danger::is_dangerous;
+use crate::model::group::classify_group;
+use crate::model::placeholder::{expand_automatic, today};
+use crate::service::command_service::{check_new_alias, CommandService, CommandServiceError};
+use crate::service::export::export_commands;
+use crate::service::search_index::SearchIndex;
+use crate::storage::command_storage::CommandStorageError;
 
 pub struct TabState {
     pub titles: Vec<String>,
@@ -16,16 +30,45 @@ impl TabState {
         TabState { titles, index: 0 }
     }
     pub fn next(&mut self) {
+        if self.titles.is_empty() {
+            return;
+        }
         self.index = (self.index + 1) % self.titles.len();
     }
 
     pub fn previous(&mut self) {
+        if self.titles.is_empty() {
+            return;
+        }
         if self.index > 0 {
             self.index -= 1;
         } else {
             self.index = self.titles.len() - 1;
         }
     }
+
+    /// Moves to the first tab starting with `c`, ignoring case. When the current tab starts
+    /// with it already, moves to the next one that does so repeated presses cycle through them.
+    pub fn jump_to_prefix(&mut self, c: char) {
+        let starts_with = |title: &String| {
+            title
+                .chars()
+                .next()
+                .is_some_and(|first| first.to_lowercase().eq(c.to_lowercase()))
+        };
+
+        let start = match self.titles.get(self.index) {
+            Some(current) if starts_with(current) => self.index + 1,
+            _ => 0,
+        };
+        let len = self.titles.len();
+        if let Some(index) = (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&index| starts_with(&self.titles[index]))
+        {
+            self.index = index;
+        }
+    }
 }
 
 pub struct StatefulList<T> {
@@ -41,14 +84,17 @@ impl<T> StatefulList<T> {
         }
     }
 
-    pub fn next(&mut self, selected_executable_tab: &str) {
+    /// Selects the next item, going back to the first one past the end when `wrap` is set
+    pub fn next(&mut self, selected_executable_tab: &str, wrap: bool) {
         let i = match self.state.selected() {
             Some(i) => {
                 let items = &self.items[selected_executable_tab];
-                if i >= items.len() - 1 {
+                if i + 1 < items.len() {
+                    i + 1
+                } else if wrap {
                     0
                 } else {
-                    i + 1
+                    items.len() - 1
                 }
             }
             None => 0,
@@ -56,14 +102,17 @@ impl<T> StatefulList<T> {
         self.state.select(Some(i));
     }
 
-    pub fn previous(&mut self, selected_executable_tab: &str) {
+    /// Selects the previous item, going to the last one before the start when `wrap` is set
+    pub fn previous(&mut self, selected_executable_tab: &str, wrap: bool) {
         let i = match self.state.selected() {
             Some(i) => {
                 let items = &self.items[selected_executable_tab];
-                if i == 0 {
+                if i > 0 {
+                    i - 1
+                } else if wrap {
                     items.len() - 1
                 } else {
-                    i - 1
+                    0
                 }
             }
             None => 0,
@@ -72,16 +121,304 @@ impl<T> StatefulList<T> {
     }
 
     pub fn selected_index(&self) -> usize {
-        match self.state.selected() {
-            Some(i) => i,
-            None => 0,
+        self.state.selected().unwrap_or_default()
+    }
+}
+
+/// Tab used for commands without any tag when grouping by tag
+pub const UNTAGGED_GROUP: &str = "untagged";
+pub const FAVORITE_GROUP: &str = "favorites";
+pub const NOT_FAVORITE_GROUP: &str = "others";
+/// Tab of the commands whose executable has no group in the config
+pub const UNGROUPED_GROUP: &str = "ungrouped";
+/// Tab listing the latest commands across all executables, after the executable tabs
+pub const RECENT_GROUP: &str = "recently added";
+/// Number of commands listed in the recently added tab
+pub const RECENT_LIMIT: u32 = 10;
+
+/// Criteria used to split the commands into tabs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GroupBy {
+    #[default]
+    Executable,
+    Tag,
+    Favorite,
+    /// The coarse group of the executable, e.g. `vcs` for git
+    ExecutableGroup,
+}
+
+impl GroupBy {
+    pub fn next(self) -> GroupBy {
+        match self {
+            GroupBy::Executable => GroupBy::Tag,
+            GroupBy::Tag => GroupBy::Favorite,
+            GroupBy::Favorite => GroupBy::ExecutableGroup,
+            GroupBy::ExecutableGroup => GroupBy::Executable,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            GroupBy::Executable => "Executables",
+            GroupBy::Tag => "Tags",
+            GroupBy::Favorite => "Favorites",
+            GroupBy::ExecutableGroup => "Groups",
+        }
+    }
+
+    /// Short name shown in the view summary
+    pub fn name(self) -> &'static str {
+        match self {
+            GroupBy::Executable => "executable",
+            GroupBy::Tag => "tag",
+            GroupBy::Favorite => "favorite",
+            GroupBy::ExecutableGroup => "group",
+        }
+    }
+}
+
+/// What the list next to the description names each command by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListDisplay {
+    #[default]
+    Alias,
+    Command,
+}
+
+impl ListDisplay {
+    pub fn toggle(self) -> ListDisplay {
+        match self {
+            ListDisplay::Alias => ListDisplay::Command,
+            ListDisplay::Command => ListDisplay::Alias,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            ListDisplay::Alias => "Alias list",
+            ListDisplay::Command => "Command list",
+        }
+    }
+}
+
+/// The environment variants of an alias the user is choosing from
+pub struct EnvironmentPicker {
+    pub variants: Vec<Command>,
+    pub index: usize,
+    /// The picked variant gets copied with `sudo`
+    pub sudo: bool,
+}
+
+impl EnvironmentPicker {
+    pub fn selected(&self) -> &Command {
+        &self.variants[self.index]
+    }
+}
+
+/// A copy held back until the user confirms it, and the mode to return to
+pub struct PendingCopy {
+    pub outcome: Outcome,
+    pub command: String,
+    previous_mode: Mode,
+}
+
+/// The commands that were in view when the export was asked for, and where they go
+pub struct ExportInput {
+    pub path: String,
+    pub commands: Vec<Command>,
+    previous_mode: Mode,
+}
+
+/// The alias being typed in place of the one of the selected command
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenameInput {
+    pub alias: String,
+    pub input: String,
+    /// Why the last commit was refused, e.g. the alias being taken, shown until the next edit
+    pub error: Option<String>,
+}
+
+/// What the last command run from the TUI wrote to stdout
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapturedOutput {
+    pub command: Command,
+    pub output: String,
+}
+
+/// Most characters of an output added to a description
+pub const OUTPUT_NOTE_LIMIT: usize = 200;
+
+/// `text` cut down to `max` characters, the last one being an ellipsis when it was cut
+pub fn truncate_with_ellipsis(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Most results listed by the global search, the rest is only counted
+pub const SEARCH_LIMIT: usize = 100;
+
+/// Most commands listed in the related pane
+pub const RELATED_LIMIT: usize = 5;
+
+/// The query typed in the global search and the result it selects
+#[derive(Debug, Default)]
+pub struct GlobalSearch {
+    pub query: String,
+    pub index: usize,
+    /// Number of commands matching the query, kept up to date as it is typed
+    pub matches: usize,
+}
+
+pub fn with_sudo(command: &str) -> String {
+    let trimmed = command.trim_start();
+    if trimmed == "sudo" || trimmed.starts_with("sudo ") {
+        command.to_string()
+    } else {
+        format!("sudo {command}")
+    }
+}
+
+/// The exact string copying or running `command` hands over, `sudo` included
+pub fn build_execution_preview(command: &Command, sudo: bool) -> String {
+    if sudo {
+        with_sudo(&command.command)
+    } else {
+        command.command.clone()
+    }
+}
+
+/// What a copy put on the clipboard
+#[derive(Clone, Debug, PartialEq)]
+pub struct CopyOutcome {
+    pub copied_text: String,
+    /// Whether placeholders were filled in from the built-ins or the environment variables of
+    /// the same name
+    pub expanded: bool,
+    /// The executable that could not be resolved to a path, the command was copied as is
+    pub unresolved: Option<String>,
+}
+
+/// How long the popup showing the copied command stays up
+pub const COPY_PREVIEW_DURATION: Duration = Duration::from_secs(1);
+
+/// The string that was just copied, shown until it expires or a key is pressed
+#[derive(Clone, Debug, PartialEq)]
+pub struct CopyPreview {
+    pub text: String,
+    pub shown_at: Instant,
+}
+
+impl CopyPreview {
+    /// Time left before the popup goes away on its own
+    pub fn remaining(&self, now: Instant) -> Duration {
+        COPY_PREVIEW_DURATION.saturating_sub(now.duration_since(self.shown_at))
+    }
+}
+
+/// How often the UI is redrawn while no key is pressed
+pub const TICK_RATE: Duration = Duration::from_millis(250);
+
+/// Ticks a status message stays up before it goes away on its own
+pub const STATUS_TICKS: u32 = 16;
+
+/// Frames of the spinner shown while a command runs
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// Counts down the ticks left until the status message is dismissed. A new message starts the
+/// count down again.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatusState {
+    shown: Option<String>,
+    ticks_left: u32,
+}
+
+impl StatusState {
+    /// Advances by one tick, clearing `status` once it has been up for `STATUS_TICKS`
+    pub fn tick(&mut self, status: &mut Option<String>) {
+        if *status != self.shown {
+            self.shown = status.clone();
+            self.ticks_left = STATUS_TICKS;
+            return;
+        }
+
+        self.ticks_left = self.ticks_left.saturating_sub(1);
+        if self.ticks_left == 0 && status.is_some() {
+            *status = None;
+            self.shown = None;
         }
     }
 }
 
+/// Most changes kept around for undo, older ones are forgotten
+pub const UNDO_LIMIT: usize = 20;
+
+/// The inverse of a change made from the TUI
+#[derive(Clone, Debug, PartialEq)]
+pub enum UndoEntry {
+    /// Store a deleted command again
+    Reinsert(Command),
+    /// Put back the description the command had before an edit
+    RestoreDescription(Command),
+    /// Delete a command added through a form
+    Remove(Command),
+    /// Put back the command string replaced by `edited`
+    RestoreCommand { previous: Command, edited: String },
+}
+
 pub struct App {
     pub commands: StatefulList<Command>,
     pub tabs: TabState,
+    pub group_by: GroupBy,
+    pub list_display: ListDisplay,
+    /// Number of lines the command pane is scrolled by
+    pub command_scroll: u16,
+    /// Whether the examples of the selected command are listed under its description
+    pub show_examples: bool,
+    /// Whether the disabled commands are listed along with the enabled ones
+    pub show_disabled: bool,
+    /// Feedback shown to the user, e.g. after copying a command
+    pub status: Option<String>,
+    status_state: StatusState,
+    /// Ticks since the app started, drives the spinner
+    pub ticks: u64,
+    /// Alias of the command being run, shown with a spinner until it exits
+    pub running: Option<String>,
+    /// The date commands expire against, as `YYYY-MM-DD`
+    pub today: String,
+    pub config: AppConfig,
+    pub mode: Mode,
+    pub environment_picker: Option<EnvironmentPicker>,
+    pub search: Option<GlobalSearch>,
+    /// Aliases picked with multi-select, acted upon together
+    pub selected_aliases: HashSet<String>,
+    /// The tag being typed for the selected commands
+    pub tag_input: Option<String>,
+    pub rename_input: Option<RenameInput>,
+    pub export_input: Option<ExportInput>,
+    pub form: Option<Form>,
+    /// Set once a field of the open form was edited, leaving it then asks first
+    pub dirty: bool,
+    /// The last copied string, briefly shown in a popup
+    pub copy_preview: Option<CopyPreview>,
+    /// The dangerous command waiting for a confirmation
+    pub pending_copy: Option<PendingCopy>,
+    /// Output of the last command run, until it is added to its description
+    pub last_output: Option<CapturedOutput>,
+    /// Aliases of the commands that failed the shell syntax check
+    pub invalid_aliases: HashSet<String>,
+    /// Changes that can be undone, the latest last
+    pub undo_stack: Vec<UndoEntry>,
+    command_service: Option<CommandService>,
+    all_commands: Vec<Command>,
+    /// The latest created commands, newest first
+    recent_commands: Vec<Command>,
+    /// Serves the global search while typing, rebuilt whenever the commands change
+    search_index: SearchIndex,
 }
 
 #[derive(Debug, Error)]
@@ -93,56 +430,2385 @@ pub enum ApplicationError {
     ClipBoardError(#[from] Box<dyn std::error::Error>),
 }
 
+impl ApplicationError {
+    /// True when the app could not start because the db file is corrupted
+    pub fn is_corrupt_database(&self) -> bool {
+        matches!(
+            self,
+            ApplicationError::CommandService(CommandServiceError::StorageManagerConstruction(
+                CommandStorageError::CorruptDatabase { .. }
+            ))
+        )
+    }
+}
+
 impl App {
-    pub async fn new() -> Result<App, ApplicationError> {
-        let command_service = CommandService::new("commands.db").await?;
-        let db_commands = command_service.get_all_commands().await?;
+    /// An app on the db at `db_path`, the one the CLI resolved from `--db-file`
+    pub async fn new(db_path: &str, config: AppConfig) -> Result<App, ApplicationError> {
+        let mut command_service =
+            CommandService::with_options(db_path, &config.storage_options()).await?;
+        command_service
+            .set_default_description_template(config.default_description_template.clone());
+        command_service
+            .unlock_when_needed(false, read_passphrase)
+            .await?;
+
+        App::from_service(command_service, config).await
+    }
+
+    /// An app showing the commands of `command_service`, and making its changes there
+    pub async fn from_service(
+        command_service: CommandService,
+        config: AppConfig,
+    ) -> Result<App, ApplicationError> {
+        let db_commands = command_service
+            .get_all_commands_ordered(config.sort, true)
+            .await?;
+
+        let mut app = App::from_commands(db_commands);
+        app.config = config;
+        app.command_service = Some(command_service);
+        app.reload_recent().await?;
+        app.regroup();
+        app.check_syntax();
+
+        Ok(app)
+    }
+
+    pub fn from_commands(commands: Vec<Command>) -> App {
+        let mut app = App {
+            commands: StatefulList::with_items(HashMap::new()),
+            tabs: TabState::new(Vec::new()),
+            group_by: GroupBy::default(),
+            list_display: ListDisplay::default(),
+            command_scroll: 0,
+            show_examples: false,
+            show_disabled: false,
+            status: None,
+            status_state: StatusState::default(),
+            ticks: 0,
+            running: None,
+            today: today(),
+            config: AppConfig::default(),
+            mode: Mode::default(),
+            environment_picker: None,
+            search: None,
+            selected_aliases: HashSet::new(),
+            tag_input: None,
+            rename_input: None,
+            export_input: None,
+            form: None,
+            dirty: false,
+            pending_copy: None,
+            last_output: None,
+            copy_preview: None,
+            invalid_aliases: HashSet::new(),
+            undo_stack: Vec::new(),
+            command_service: None,
+            all_commands: commands,
+            recent_commands: Vec::new(),
+            search_index: SearchIndex::default(),
+        };
+        app.regroup();
+
+        app
+    }
 
+    /// Splits the commands into tabs according to the active grouping. A command with several
+    /// tags shows up under each of them. Environment variants of an alias are listed once, the
+    /// variant is picked when copying. Pinned commands lead each tab. Disabled commands are
+    /// left out unless they were asked for.
+    pub fn grouped_commands(&self) -> HashMap<String, Vec<Command>> {
         let mut commands: HashMap<String, Vec<Command>> = HashMap::new();
-        db_commands.into_iter().for_each(|command| {
-            let entry = commands
-                .entry(command.clone().executable)
-                .or_insert(Vec::new());
-            entry.push(command)
+
+        for command in self.visible_commands() {
+            let groups = match self.group_by {
+                GroupBy::Executable => vec![command.executable.clone()],
+                GroupBy::Tag if command.tags.is_empty() => vec![UNTAGGED_GROUP.to_string()],
+                GroupBy::Tag => command.tags.iter().cloned().collect(),
+                GroupBy::Favorite if command.favorite => vec![FAVORITE_GROUP.to_string()],
+                GroupBy::Favorite => vec![NOT_FAVORITE_GROUP.to_string()],
+                GroupBy::ExecutableGroup => {
+                    vec![
+                        classify_group(&command.executable, &self.config.executable_groups)
+                            .unwrap_or_else(|| UNGROUPED_GROUP.to_string()),
+                    ]
+                }
+            };
+
+            for group in groups {
+                let entry = commands.entry(group).or_default();
+                if !entry.iter().any(|listed| listed.alias == command.alias) {
+                    entry.push(command.clone());
+                }
+            }
+        }
+
+        for entry in commands.values_mut() {
+            entry.sort_by_key(|command| !command.pinned);
+        }
+
+        if self.group_by == GroupBy::Executable && !self.recent_commands.is_empty() {
+            commands.insert(RECENT_GROUP.to_string(), self.recent_commands.clone());
+        }
+
+        commands
+    }
+
+    /// The commands that can be listed, searched and copied
+    fn visible_commands(&self) -> impl Iterator<Item = &Command> {
+        self.all_commands
+            .iter()
+            .filter(|command| command.enabled || self.show_disabled)
+    }
+
+    /// The grouping, order and search filter in effect, e.g. `group:executable sort:alias`
+    pub fn view_state_summary(&self) -> String {
+        let mut summary = format!(
+            "group:{} sort:{}",
+            self.group_by.name(),
+            self.config.sort.name()
+        );
+
+        if let Some(search) = self
+            .search
+            .as_ref()
+            .filter(|search| !search.query.is_empty())
+        {
+            summary.push_str(&format!(" filter:{:?}", search.query));
+        }
+
+        summary
+    }
+
+    /// Fetches the recently added commands again after commands were stored or deleted
+    async fn reload_recent(&mut self) -> Result<(), ApplicationError> {
+        if let Some(command_service) = &self.command_service {
+            self.recent_commands = command_service.get_recent_commands(RECENT_LIMIT).await?;
+        }
+
+        Ok(())
+    }
+
+    fn regroup(&mut self) {
+        let visible: Vec<Command> = self.visible_commands().cloned().collect();
+        self.search_index = SearchIndex::build(&visible);
+
+        let commands = self.grouped_commands();
+        let mut titles: Vec<String> = commands.keys().cloned().collect();
+        // The recently added tab closes the alphabetical ones
+        titles.sort_by_key(|title| (title == RECENT_GROUP, title.clone()));
+
+        self.tabs = TabState::new(titles);
+        self.commands = StatefulList::with_items(commands);
+    }
+
+    /// Regroups after the commands changed, staying on the current tab when it still exists
+    fn refresh(&mut self) {
+        let tab = self.tabs.titles.get(self.tabs.index).cloned();
+        self.regroup();
+
+        if let Some(index) = tab.and_then(|tab| self.tabs.titles.iter().position(|t| *t == tab)) {
+            self.tabs.index = index;
+        }
+        self.reset_selection();
+        self.check_syntax();
+    }
+
+    fn push_undo(&mut self, entry: UndoEntry) {
+        self.undo_stack.push(entry);
+        if self.undo_stack.len() > UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    /// Deletes every environment variant of the selected command, keeping them for undo
+    pub async fn delete_selected(&mut self) -> Result<(), ApplicationError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        for variant in self.variants_of(&self.get_selected_command().alias) {
+            if let Some(command_service) = &self.command_service {
+                command_service
+                    .delete_command(
+                        &variant.command,
+                        &variant.alias,
+                        variant.description.clone(),
+                    )
+                    .await?;
+            }
+
+            self.all_commands
+                .retain(|command| command.command != variant.command);
+            self.push_undo(UndoEntry::Reinsert(variant));
+        }
+        self.reload_recent().await?;
+        self.refresh();
+
+        Ok(())
+    }
+
+    /// Disables every variant of the selected command, hiding it, or enables it again
+    pub async fn toggle_enabled(&mut self) -> Result<(), ApplicationError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let selected = self.get_selected_command();
+        let enabled = !selected.enabled;
+        if let Some(command_service) = &self.command_service {
+            command_service.toggle_enabled(&selected.alias).await?;
+        }
+
+        for command in self
+            .all_commands
+            .iter_mut()
+            .filter(|command| command.alias == selected.alias)
+        {
+            command.enabled = enabled;
+        }
+        self.reload_recent().await?;
+        self.refresh();
+        self.status = Some(if enabled {
+            format!("Enabled {}", selected.alias)
+        } else {
+            format!("Disabled {}", selected.alias)
         });
 
-        let tabs = TabState::new(commands.keys().cloned().collect());
+        Ok(())
+    }
 
-        // get titles from the db
-        Ok(App {
-            commands: StatefulList::with_items(commands),
-            tabs,
-        })
+    /// Pins the selected command to the top of its tab, or unpins it, keeping it selected
+    pub async fn toggle_pin(&mut self) -> Result<(), ApplicationError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let selected = self.get_selected_command();
+        let pinned = !selected.pinned;
+        if let Some(command_service) = &self.command_service {
+            command_service.set_pinned(&selected.alias, pinned).await?;
+        }
+
+        for command in self
+            .all_commands
+            .iter_mut()
+            .filter(|command| command.alias == selected.alias)
+        {
+            command.pinned = pinned;
+        }
+        self.refresh();
+
+        let tab = self.get_selected_executable();
+        let index = self.commands.items[&tab]
+            .iter()
+            .position(|command| command.alias == selected.alias);
+        self.commands.state.select(index);
+
+        Ok(())
     }
 
-    pub fn executables(&self) -> Vec<String> {
-        self.commands.items.keys().cloned().collect()
+    /// Closes the db behind the app on a clean exit
+    pub async fn close(self) {
+        if let Some(command_service) = self.command_service {
+            command_service.close().await;
+        }
     }
 
-    pub fn get_by_executable(&self, executable: &str) -> Vec<Command> {
-        self.commands.items[executable].clone()
+    /// Counts a use of the copied `command` in the db
+    pub async fn record_use(&self, command: &Command) -> Result<(), ApplicationError> {
+        if let Some(command_service) = &self.command_service {
+            command_service.record_use(command).await?;
+        }
+
+        Ok(())
     }
 
-    pub fn get_selected_command(&self) -> Command {
-        let executable = self.get_selected_executable();
-        let selected_command_index = self.commands.selected_index();
+    /// Gives every variant of the selected command the next color label, keeping it selected
+    pub async fn cycle_label(&mut self) -> Result<(), ApplicationError> {
+        if self.is_empty() {
+            return Ok(());
+        }
 
-        // This should not fail
-        self.commands.items[&executable]
-            .get(selected_command_index)
-            .unwrap()
-            .clone()
+        let selected = self.get_selected_command();
+        let label = Label::cycle(selected.label);
+        if let Some(command_service) = &self.command_service {
+            command_service.set_label(&selected.alias, label).await?;
+        }
+
+        for command in self
+            .all_commands
+            .iter_mut()
+            .filter(|command| command.alias == selected.alias)
+        {
+            command.label = label;
+        }
+
+        let index = self.commands.state.selected();
+        self.refresh();
+        self.commands.state.select(index);
+
+        Ok(())
     }
 
-    pub fn get_selected_executable(&self) -> String {
-        self.tabs.titles.get(self.tabs.index).unwrap().clone()
+    /// Reverts the last change, returns false when there was nothing to undo
+    pub async fn undo(&mut self) -> Result<bool, ApplicationError> {
+        let Some(entry) = self.undo_stack.pop() else {
+            return Ok(false);
+        };
+
+        match entry {
+            UndoEntry::Reinsert(command) => {
+                if let Some(command_service) = &self.command_service {
+                    command_service.restore_command(command.clone()).await?;
+                }
+                self.all_commands.push(command);
+            }
+            UndoEntry::RestoreDescription(previous) => {
+                if let Some(command_service) = &self.command_service {
+                    command_service
+                        .update_description(&previous, previous.description.clone())
+                        .await?;
+                }
+                self.replace_command(&previous.clone(), previous);
+            }
+            UndoEntry::RestoreCommand { previous, edited } => {
+                let current = Command {
+                    command: edited,
+                    ..previous.clone()
+                };
+                if let Some(command_service) = &self.command_service {
+                    command_service
+                        .update_command(&current, &previous.command)
+                        .await?;
+                }
+                self.replace_command(&current, previous);
+            }
+            UndoEntry::Remove(command) => {
+                if let Some(command_service) = &self.command_service {
+                    command_service
+                        .delete_command(
+                            &command.command,
+                            &command.alias,
+                            command.description.clone(),
+                        )
+                        .await?;
+                }
+                self.all_commands
+                    .retain(|loaded| loaded.command != command.command);
+            }
+        }
+        self.reload_recent().await?;
+        self.refresh();
+
+        Ok(true)
+    }
+
+    pub fn cycle_group_by(&mut self) {
+        self.group_by = self.group_by.next();
+        self.regroup();
+    }
+
+    /// Applies the state transition of `action`. Side effects like the clipboard are left to
+    /// the event loop through the returned outcome.
+    pub fn apply(&mut self, action: Action) -> Option<Outcome> {
+        match self.mode {
+            Mode::PickEnvironment => return self.apply_to_picker(action),
+            Mode::GlobalSearch => return self.apply_to_search(action),
+            Mode::TagInput => return self.apply_to_tag_input(action),
+            Mode::RenameInput => return self.apply_to_rename_input(action),
+            Mode::ExportInput => return self.apply_to_export_input(action),
+            Mode::Form => return self.apply_to_form(action),
+            Mode::ConfirmDanger => return self.apply_to_confirmation(action),
+            Mode::ConfirmDiscard => return self.apply_to_discard_confirmation(action),
+            Mode::JumpToTab => return self.apply_to_jump(action),
+            Mode::Normal => {}
+        }
+
+        match action {
+            Action::Quit => return Some(Outcome::Quit),
+            Action::Copy | Action::CopyWithSudo | Action::CopyResolved if self.is_empty() => {}
+            Action::Copy | Action::CopyWithSudo => {
+                let sudo = action == Action::CopyWithSudo;
+                let variants = self.variants_of(&self.get_selected_command().alias);
+                if variants.len() > 1 {
+                    self.environment_picker = Some(EnvironmentPicker {
+                        variants,
+                        index: 0,
+                        sudo,
+                    });
+                    self.mode = Mode::PickEnvironment;
+                    return None;
+                }
+
+                return self.copy_outcome(sudo);
+            }
+            Action::CopyResolved => {
+                let outcome = Outcome::CopyResolved {
+                    exit: self.config.exit_on_copy,
+                };
+                let command = self.command_to_copy().command;
+                return self.confirm_if_dangerous(outcome, command);
+            }
+            Action::JumpToTop if self.is_empty() => {}
+            Action::JumpToTop => {
+                self.commands.state.select(Some(0));
+                self.command_scroll = 0;
+            }
+            Action::Delete if self.is_empty() => {}
+            Action::Delete => return Some(Outcome::Delete),
+            Action::Run if self.is_empty() => {}
+            Action::Run => {
+                let command = self.command_to_copy().command;
+                return self.confirm_if_dangerous(Outcome::Run, command);
+            }
+            Action::AppendOutput if self.last_output.is_none() => {
+                self.status = Some("Run a command with r first".to_string());
+            }
+            Action::AppendOutput => return Some(Outcome::AppendOutput),
+            Action::StartExport => self.start_export(),
+            Action::Undo if self.undo_stack.is_empty() => {
+                self.status = Some("Nothing to undo".to_string());
+            }
+            Action::Undo => return Some(Outcome::Undo),
+            Action::TogglePin if self.is_empty() => {}
+            Action::TogglePin => return Some(Outcome::TogglePin),
+            Action::ToggleEnabled if self.is_empty() => {}
+            Action::ToggleEnabled => return Some(Outcome::ToggleEnabled),
+            Action::ToggleSelect
+            | Action::StartTagging
+            | Action::StartRename
+            | Action::EditDescription
+            | Action::Duplicate
+                if self.is_empty() => {}
+            Action::Duplicate => self.duplicate_selected(),
+            Action::CycleLabel if self.is_empty() => {}
+            Action::CycleLabel => return Some(Outcome::CycleLabel),
+            Action::EditInEditor if self.is_empty() => {}
+            Action::EditInEditor => return Some(Outcome::EditInEditor),
+            Action::EditDescription => {
+                self.form = Some(Form::edit_description(
+                    self.get_selected_command(),
+                    self.config.max_alias_len,
+                ));
+                self.mode = Mode::Form;
+            }
+            Action::ToggleSelect => {
+                let alias = self.get_selected_command().alias;
+                if !self.selected_aliases.remove(&alias) {
+                    self.selected_aliases.insert(alias);
+                }
+            }
+            Action::StartRename => {
+                let alias = self.get_selected_command().alias;
+                self.rename_input = Some(RenameInput {
+                    input: alias.clone(),
+                    alias,
+                    error: None,
+                });
+                self.mode = Mode::RenameInput;
+            }
+            Action::StartTagging => {
+                // Without a multi-selection the highlighted command gets tagged
+                if self.selected_aliases.is_empty() {
+                    self.selected_aliases
+                        .insert(self.get_selected_command().alias);
+                }
+                self.tag_input = Some(String::new());
+                self.mode = Mode::TagInput;
+            }
+            Action::NextTab => {
+                self.tabs.next();
+                self.reset_selection();
+            }
+            Action::PreviousTab => {
+                self.tabs.previous();
+                self.reset_selection();
+            }
+            Action::StartJump => self.mode = Mode::JumpToTab,
+            Action::CycleGroup => {
+                self.cycle_group_by();
+                self.reset_selection();
+            }
+            Action::StartSearch => {
+                self.search = Some(GlobalSearch {
+                    matches: self.global_search("").len(),
+                    ..GlobalSearch::default()
+                });
+                self.mode = Mode::GlobalSearch;
+            }
+            Action::NextCommand => {
+                if let Some(tab) = self.tabs.titles.get(self.tabs.index) {
+                    self.commands.next(tab, self.config.wrap_navigation);
+                    self.command_scroll = 0;
+                }
+            }
+            Action::PreviousCommand => {
+                if let Some(tab) = self.tabs.titles.get(self.tabs.index) {
+                    self.commands.previous(tab, self.config.wrap_navigation);
+                    self.command_scroll = 0;
+                }
+            }
+            Action::ScrollDown => self.command_scroll = self.command_scroll.saturating_add(1),
+            Action::ScrollUp => self.command_scroll = self.command_scroll.saturating_sub(1),
+            Action::ToggleExamples => self.show_examples = !self.show_examples,
+            Action::ToggleListDisplay => self.list_display = self.list_display.toggle(),
+            Action::ToggleShowDisabled => {
+                self.show_disabled = !self.show_disabled;
+                self.refresh();
+            }
+            Action::Input(_)
+            | Action::DeleteInput
+            | Action::Confirm
+            | Action::Cancel
+            | Action::None => {}
+        }
+
+        None
     }
 
-    pub fn save_command_to_clipboard(&self) -> Result<(), ApplicationError> {
-        let command = self.get_selected_command().command.to_owned();
-        let mut clipboard_context: ClipboardContext = ClipboardProvider::new()?;
-        clipboard_context.set_contents(command.clone());
+    /// Stores `edited` as the command string of the selected command
+    pub async fn update_selected_command(&mut self, edited: &str) -> Result<(), ApplicationError> {
+        let previous = self.get_selected_command();
+        if edited == previous.command {
+            self.status = Some("The command is unchanged".to_string());
+            return Ok(());
+        }
+
+        let updated = match &self.command_service {
+            Some(command_service) => command_service.update_command(&previous, edited).await?,
+            None => Command {
+                command: edited.to_string(),
+                ..previous.clone()
+            },
+        };
+
+        self.replace_command(&previous, updated);
+        self.push_undo(UndoEntry::RestoreCommand {
+            previous,
+            edited: edited.to_string(),
+        });
+        self.refresh();
+        self.status = Some("Command updated".to_string());
 
         Ok(())
     }
+
+    /// Opens the add form filled in with the selected command, the alias cleared
+    pub fn duplicate_selected(&mut self) {
+        if self.is_empty() {
+            return;
+        }
+
+        self.form = Some(Form::duplicate(
+            &self.get_selected_command(),
+            self.config.max_alias_len,
+        ));
+        self.mode = Mode::Form;
+    }
+
+    fn apply_to_form(&mut self, action: Action) -> Option<Outcome> {
+        let form = self.form.as_mut()?;
+
+        match action {
+            Action::Input(c) => {
+                form.input(c);
+                self.dirty = true;
+            }
+            Action::DeleteInput => {
+                form.delete_input();
+                self.dirty = true;
+            }
+            Action::NextCommand => form.next_field(),
+            Action::PreviousCommand => form.previous_field(),
+            Action::Copy => match form.submit() {
+                Some(_) => return Some(Outcome::SubmitForm),
+                None => self.status = form.error.clone(),
+            },
+            Action::Cancel | Action::Quit if self.dirty => self.mode = Mode::ConfirmDiscard,
+            Action::Cancel | Action::Quit => self.close_form(),
+            _ => {}
+        }
+
+        None
+    }
+
+    /// Closes the form once the discard of its edits is confirmed, back to editing otherwise
+    fn apply_to_discard_confirmation(&mut self, action: Action) -> Option<Outcome> {
+        match action {
+            Action::Confirm => self.close_form(),
+            _ => self.mode = Mode::Form,
+        }
+
+        None
+    }
+
+    fn close_form(&mut self) {
+        self.form = None;
+        self.dirty = false;
+        self.mode = Mode::Normal;
+    }
+
+    /// Saves the submitted form through the service
+    pub async fn submit_form(&mut self) -> Result<(), ApplicationError> {
+        let Some(form) = &self.form else {
+            return Ok(());
+        };
+        let Ok(command) = form.command() else {
+            return Ok(());
+        };
+
+        match form.kind.clone() {
+            FormKind::EditDescription(previous) => {
+                if let Some(command_service) = &self.command_service {
+                    command_service
+                        .update_description(&previous, command.description.clone())
+                        .await?;
+                }
+
+                self.replace_command(&previous, command);
+                self.push_undo(UndoEntry::RestoreDescription(*previous));
+            }
+            FormKind::AddCommand => {
+                let mut command = command;
+                if let Some(command_service) = &self.command_service {
+                    let stored = command_service
+                        .insert_command(
+                            &command.command,
+                            &command.alias,
+                            command.description.clone(),
+                        )
+                        .await?;
+                    // The description may come from the default template
+                    command.description = stored.description;
+                }
+
+                self.all_commands.push(command.clone());
+                self.push_undo(UndoEntry::Remove(command));
+            }
+        }
+        self.close_form();
+        self.reload_recent().await?;
+        self.refresh();
+
+        Ok(())
+    }
+
+    /// Adds the output of the last run to the description of the command that was run, capped
+    /// so the description stays within its limit
+    pub async fn append_last_output_to_notes(&mut self) -> Result<(), ApplicationError> {
+        let Some(CapturedOutput { command, output }) = self.last_output.take() else {
+            return Ok(());
+        };
+
+        let output = truncate_with_ellipsis(output.trim_end(), OUTPUT_NOTE_LIMIT);
+        let notes = match command.description.as_deref() {
+            Some(description) if !description.is_empty() => {
+                format!("{description}\nOutput: {output}")
+            }
+            _ => format!("Output: {output}"),
+        };
+        let description = truncate_with_ellipsis(&notes, MAX_DESCRIPTION_LEN);
+
+        if let Some(command_service) = &self.command_service {
+            command_service
+                .update_description(&command, Some(description.clone()))
+                .await?;
+        }
+
+        let updated = Command {
+            description: Some(description),
+            ..command.clone()
+        };
+        self.replace_command(&command, updated);
+        self.push_undo(UndoEntry::RestoreDescription(command));
+
+        let index = self.commands.state.selected();
+        self.refresh();
+        self.commands.state.select(index);
+        self.status = Some("Added the output to the description".to_string());
+
+        Ok(())
+    }
+
+    /// Swaps the stored `previous` command for `command` in the loaded commands
+    fn replace_command(&mut self, previous: &Command, command: Command) {
+        if let Some(loaded) = self
+            .all_commands
+            .iter_mut()
+            .find(|loaded| loaded.command == previous.command)
+        {
+            *loaded = command;
+        }
+    }
+
+    /// Every letter typed jumps to a tab starting with it, any other key ends the jump
+    fn apply_to_jump(&mut self, action: Action) -> Option<Outcome> {
+        match action {
+            Action::Input(c) => {
+                let previous = self.tabs.index;
+                self.tabs.jump_to_prefix(c);
+                if self.tabs.index != previous {
+                    self.reset_selection();
+                }
+            }
+            _ => self.mode = Mode::Normal,
+        }
+
+        None
+    }
+
+    fn apply_to_tag_input(&mut self, action: Action) -> Option<Outcome> {
+        let tag = self.tag_input.as_mut()?;
+
+        match action {
+            // Tags are stored comma separated
+            Action::Input(',') => {}
+            Action::Input(c) => tag.push(c),
+            Action::DeleteInput => {
+                tag.pop();
+            }
+            Action::Copy if !tag.trim().is_empty() => return Some(Outcome::ApplyTag),
+            Action::Cancel => self.close_tag_input(),
+            _ => {}
+        }
+
+        None
+    }
+
+    /// The commands on screen: every match of the global search while it is open, the
+    /// commands of the current tab otherwise
+    pub fn current_view(&self) -> Vec<Command> {
+        match &self.search {
+            Some(search) => self.global_search(&search.query),
+            None if self.is_empty() => Vec::new(),
+            None => self.get_by_executable(&self.get_selected_executable()),
+        }
+    }
+
+    /// Asks for the path the commands in view are exported to
+    fn start_export(&mut self) {
+        let commands = self.current_view();
+        if commands.is_empty() {
+            self.status = Some("Nothing to export".to_string());
+            return;
+        }
+
+        self.export_input = Some(ExportInput {
+            path: self.config.export_format.default_file_name().to_string(),
+            commands,
+            previous_mode: self.mode,
+        });
+        self.mode = Mode::ExportInput;
+    }
+
+    fn apply_to_export_input(&mut self, action: Action) -> Option<Outcome> {
+        let input = self.export_input.as_mut()?;
+
+        match action {
+            Action::Input(c) => input.path.push(c),
+            Action::DeleteInput => {
+                input.path.pop();
+            }
+            Action::Copy if !input.path.trim().is_empty() => return Some(Outcome::Export),
+            Action::Cancel => {
+                self.close_export_input();
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    fn close_export_input(&mut self) -> Option<ExportInput> {
+        let input = self.export_input.take()?;
+        self.mode = input.previous_mode;
+
+        Some(input)
+    }
+
+    /// Writes the commands that were in view to the typed path in the configured format
+    pub fn export_view(&mut self) -> Result<(), ApplicationError> {
+        let Some(input) = self.close_export_input() else {
+            return Ok(());
+        };
+
+        let path = input.path.trim();
+        export_commands(&input.commands, self.config.export_format, Path::new(path))?;
+        self.status = Some(format!(
+            "Exported {} commands to {path}",
+            input.commands.len()
+        ));
+
+        Ok(())
+    }
+
+    fn apply_to_rename_input(&mut self, action: Action) -> Option<Outcome> {
+        let rename = self.rename_input.as_mut()?;
+
+        match action {
+            Action::Input(c) => {
+                rename.input.push(c);
+                rename.error = None;
+            }
+            Action::DeleteInput => {
+                rename.input.pop();
+                rename.error = None;
+            }
+            Action::Copy if rename.input == rename.alias => self.close_rename_input(),
+            Action::Copy => return Some(Outcome::Rename),
+            Action::Cancel => self.close_rename_input(),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn close_rename_input(&mut self) {
+        self.rename_input = None;
+        self.mode = Mode::Normal;
+    }
+
+    /// Gives every variant of the selected alias the typed one. A refused alias, e.g. one that
+    /// is taken, keeps the edit open with the reason shown next to it.
+    pub async fn rename_selected(&mut self) -> Result<(), ApplicationError> {
+        let Some(rename) = self.rename_input.clone() else {
+            return Ok(());
+        };
+        let new_alias = rename.input.trim().to_string();
+
+        let renamed = match &self.command_service {
+            Some(command_service) => {
+                command_service
+                    .rename_alias(&rename.alias, &new_alias)
+                    .await
+            }
+            None => check_new_alias(
+                &new_alias,
+                self.all_commands.iter(),
+                self.config.max_alias_len,
+            ),
+        };
+        match renamed {
+            Ok(()) => {}
+            Err(e @ (CommandServiceError::AliasTaken(_) | CommandServiceError::Invalid(_))) => {
+                if let Some(rename) = &mut self.rename_input {
+                    rename.error = Some(e.to_string());
+                }
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        for command in self.all_commands.iter_mut() {
+            if command.alias == rename.alias {
+                command.alias = new_alias.clone();
+            }
+            // The db leaves the references of sealed commands alone
+            if !command.sensitive {
+                command.command = rewrite_references(&command.command, &rename.alias, &new_alias);
+            }
+        }
+        if self.selected_aliases.remove(&rename.alias) {
+            self.selected_aliases.insert(new_alias.clone());
+        }
+        self.close_rename_input();
+        self.reload_recent().await?;
+        self.refresh();
+        self.select_alias(&new_alias);
+        self.status = Some(format!("Renamed {} to {new_alias}", rename.alias));
+
+        Ok(())
+    }
+
+    /// Highlights `alias` when it is listed in the current tab
+    fn select_alias(&mut self, alias: &str) {
+        let Some(tab) = self.tabs.titles.get(self.tabs.index) else {
+            return;
+        };
+        let index = self
+            .commands
+            .items
+            .get(tab)
+            .and_then(|commands| commands.iter().position(|command| command.alias == alias));
+        if index.is_some() {
+            self.commands.state.select(index);
+        }
+    }
+
+    fn close_tag_input(&mut self) {
+        self.tag_input = None;
+        self.mode = Mode::Normal;
+    }
+
+    /// Adds the typed tag to every selected command, then clears the selection
+    pub async fn apply_tag(&mut self) -> Result<(), ApplicationError> {
+        let Some(tag) = self.tag_input.take() else {
+            return Ok(());
+        };
+        let tag = tag.trim().to_string();
+        let aliases: Vec<String> = self.selected_aliases.drain().collect();
+        self.close_tag_input();
+
+        if let Some(command_service) = &self.command_service {
+            command_service.add_tag_to_many(&aliases, &tag).await?;
+        }
+
+        for command in self
+            .all_commands
+            .iter_mut()
+            .filter(|command| aliases.contains(&command.alias))
+        {
+            if !command.tags.iter().any(|t| *t == tag) {
+                command.tags.0.push(tag.clone());
+            }
+        }
+        self.refresh();
+        self.status = Some(format!("Tagged {} commands with {tag}", aliases.len()));
+
+        Ok(())
+    }
+
+    fn apply_to_search(&mut self, action: Action) -> Option<Outcome> {
+        let results = self.search_results().len();
+        let search = self.search.as_mut()?;
+
+        match action {
+            Action::Input(c) => {
+                search.query.push(c);
+                search.index = 0;
+                self.count_search_matches();
+            }
+            Action::DeleteInput => {
+                search.query.pop();
+                search.index = 0;
+                self.count_search_matches();
+            }
+            Action::NextCommand if results > 0 => search.index = (search.index + 1) % results,
+            Action::PreviousCommand if results > 0 => {
+                search.index = search.index.checked_sub(1).unwrap_or(results - 1)
+            }
+            Action::Copy | Action::CopyWithSudo if results > 0 => {
+                return self.copy_outcome(action == Action::CopyWithSudo);
+            }
+            Action::StartExport => self.start_export(),
+            Action::Cancel => self.close_search(),
+            _ => {}
+        }
+
+        None
+    }
+
+    fn count_search_matches(&mut self) {
+        let matches = match &self.search {
+            Some(search) => self.global_search(&search.query).len(),
+            None => return,
+        };
+
+        if let Some(search) = self.search.as_mut() {
+            search.matches = matches;
+        }
+    }
+
+    fn close_search(&mut self) {
+        self.search = None;
+        self.mode = Mode::Normal;
+    }
+
+    /// Every command whose alias, command or description contains `query`, ignoring case,
+    /// ordered by executable then alias. Environment variants are listed separately.
+    pub fn global_search(&self, query: &str) -> Vec<Command> {
+        let mut results: Vec<Command> = self
+            .search_index
+            .query(query)
+            .into_iter()
+            .cloned()
+            .collect();
+        results.sort_by(|a, b| {
+            (&a.executable, &a.alias, &a.environment).cmp(&(
+                &b.executable,
+                &b.alias,
+                &b.environment,
+            ))
+        });
+
+        results
+    }
+
+    /// The executables having at least one command matching `query`
+    pub fn matching_executables(&self, query: &str) -> HashSet<String> {
+        self.search_index
+            .query(query)
+            .into_iter()
+            .map(|command| command.executable.clone())
+            .collect()
+    }
+
+    /// Up to `limit` other commands sharing tags or the executable with `command`. Those sharing
+    /// the most tags come first, then those of the same executable, then by alias.
+    pub fn related_commands(&self, command: &Command, limit: usize) -> Vec<Command> {
+        let mut related: Vec<(usize, bool, &Command)> = self
+            .visible_commands()
+            .filter(|other| {
+                other.alias != command.alias || other.environment != command.environment
+            })
+            .map(|other| {
+                let shared_tags = other
+                    .tags
+                    .iter()
+                    .filter(|tag| command.tags.iter().any(|own| own == *tag))
+                    .count();
+                (shared_tags, other.executable == command.executable, other)
+            })
+            .filter(|(shared_tags, same_executable, _)| *shared_tags > 0 || *same_executable)
+            .collect();
+        related.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then(b.1.cmp(&a.1))
+                .then((&a.2.alias, &a.2.environment).cmp(&(&b.2.alias, &b.2.environment)))
+        });
+
+        related
+            .into_iter()
+            .take(limit)
+            .map(|(_, _, other)| other.clone())
+            .collect()
+    }
+
+    /// The first `SEARCH_LIMIT` results of the query typed in the global search, along with
+    /// the total number of matches. Empty when the search is closed.
+    pub fn search_results_with_total(&self) -> (Vec<Command>, usize) {
+        let Some(search) = &self.search else {
+            return (Vec::new(), 0);
+        };
+
+        let mut results = self.global_search(&search.query);
+        let total = results.len();
+        results.truncate(SEARCH_LIMIT);
+
+        (results, total)
+    }
+
+    pub fn search_results(&self) -> Vec<Command> {
+        self.search_results_with_total().0
+    }
+
+    fn apply_to_picker(&mut self, action: Action) -> Option<Outcome> {
+        let picker = self.environment_picker.as_mut()?;
+
+        match action {
+            Action::NextCommand => picker.index = (picker.index + 1) % picker.variants.len(),
+            Action::PreviousCommand => {
+                picker.index = picker
+                    .index
+                    .checked_sub(1)
+                    .unwrap_or(picker.variants.len() - 1)
+            }
+            Action::Copy => {
+                let sudo = picker.sudo;
+                return self.copy_outcome(sudo);
+            }
+            Action::Cancel | Action::Quit => self.close_environment_picker(),
+            _ => {}
+        }
+
+        None
+    }
+
+    /// The copy to perform, held back for a confirmation when the command looks dangerous
+    fn copy_outcome(&mut self, sudo: bool) -> Option<Outcome> {
+        let exit = self.config.exit_on_copy;
+        let outcome = if sudo {
+            Outcome::CopyWithSudo { exit }
+        } else {
+            Outcome::Copy { exit }
+        };
+
+        let command = self.command_to_copy().command;
+        self.confirm_if_dangerous(outcome, command)
+    }
+
+    /// `outcome` right away, or once confirmed when `command` looks dangerous
+    fn confirm_if_dangerous(&mut self, outcome: Outcome, command: String) -> Option<Outcome> {
+        if !is_dangerous(&command, &self.config.dangerous_patterns) {
+            return Some(outcome);
+        }
+
+        self.pending_copy = Some(PendingCopy {
+            outcome,
+            command,
+            previous_mode: self.mode,
+        });
+        self.mode = Mode::ConfirmDanger;
+
+        None
+    }
+
+    fn apply_to_confirmation(&mut self, action: Action) -> Option<Outcome> {
+        let pending = self.pending_copy.take()?;
+        self.mode = pending.previous_mode;
+
+        match action {
+            Action::Confirm => Some(pending.outcome),
+            _ => {
+                self.status = Some(match pending.outcome {
+                    Outcome::Run => "Run cancelled".to_string(),
+                    _ => "Copy cancelled".to_string(),
+                });
+                None
+            }
+        }
+    }
+
+    fn close_environment_picker(&mut self) {
+        self.environment_picker = None;
+        self.mode = Mode::Normal;
+    }
+
+    /// Every stored variant of `alias`, the default environment first
+    pub fn variants_of(&self, alias: &str) -> Vec<Command> {
+        let mut environments: Vec<Option<String>> = self
+            .visible_commands()
+            .filter(|command| command.alias == alias)
+            .map(|command| command.environment.clone())
+            .collect();
+        environments.sort();
+        environments.dedup();
+
+        // One variant per environment, the one for this operating system
+        environments
+            .into_iter()
+            .filter_map(|environment| {
+                let candidates: Vec<Command> = self
+                    .visible_commands()
+                    .filter(|command| command.alias == alias && command.environment == environment)
+                    .cloned()
+                    .collect();
+                select_for_os(&candidates, std::env::consts::OS).cloned()
+            })
+            .collect()
+    }
+
+    /// The command the next copy acts on: the picked variant, the selected search result or
+    /// the selected command
+    pub fn command_to_copy(&self) -> Command {
+        if let Some(picker) = &self.environment_picker {
+            return picker.selected().clone();
+        }
+
+        match &self.search {
+            Some(search) => self.search_results()[search.index].clone(),
+            None => {
+                let selected = self.get_selected_command();
+                self.variants_of(&selected.alias)
+                    .into_iter()
+                    .find(|variant| variant.environment == selected.environment)
+                    .unwrap_or(selected)
+            }
+        }
+    }
+
+    // If commands list state remains selected, changing tabs will crash
+    // the app because we still have some unwraps
+    fn reset_selection(&mut self) {
+        self.commands.state = ListState::default();
+        self.command_scroll = 0;
+    }
+
+    /// Runs the shell syntax check on every command to flag the broken ones in the list
+    pub fn check_syntax(&mut self) {
+        self.invalid_aliases = self
+            .all_commands
+            .iter()
+            .filter(|command| command.syntax_check().is_err())
+            .map(|command| command.alias.clone())
+            .collect();
+    }
+
+    /// True when there is no command to show
+    pub fn is_empty(&self) -> bool {
+        self.tabs.titles.is_empty()
+    }
+
+    pub fn executables(&self) -> Vec<String> {
+        self.tabs.titles.clone()
+    }
+
+    pub fn get_by_executable(&self, executable: &str) -> Vec<Command> {
+        self.commands.items[executable].clone()
+    }
+
+    pub fn get_selected_command(&self) -> Command {
+        let executable = self.get_selected_executable();
+        let selected_command_index = self.commands.selected_index();
+
+        // This should not fail
+        self.commands.items[&executable]
+            .get(selected_command_index)
+            .unwrap()
+            .clone()
+    }
+
+    pub fn get_selected_executable(&self) -> String {
+        self.tabs.titles.get(self.tabs.index).unwrap().clone()
+    }
+
+    pub fn save_command_to_clipboard(
+        &mut self,
+        clipboard: &mut dyn Clipboard,
+    ) -> Result<CopyOutcome, ApplicationError> {
+        let command = build_execution_preview(&self.command_to_copy(), false);
+        self.copy(clipboard, &command)
+    }
+
+    fn copy(
+        &mut self,
+        clipboard: &mut dyn Clipboard,
+        command: &str,
+    ) -> Result<CopyOutcome, ApplicationError> {
+        let (copied_text, expanded) = expand_automatic(command);
+        self.close_environment_picker();
+        self.close_search();
+
+        // A pasted newline makes the shell run the command right away
+        let contents = if self.config.copy_trailing_newline {
+            format!("{copied_text}\n")
+        } else {
+            copied_text.clone()
+        };
+        clipboard.set_contents(contents)?;
+        // Only an app that stays open gets to show the preview
+        if !self.config.exit_on_copy {
+            self.copy_preview = Some(CopyPreview {
+                text: copied_text.clone(),
+                shown_at: Instant::now(),
+            });
+        }
+
+        Ok(CopyOutcome {
+            copied_text,
+            expanded,
+            unresolved: None,
+        })
+    }
+
+    /// Moves the time based state along: expires the status and the copy preview and turns the
+    /// spinner
+    pub fn tick(&mut self, now: Instant) {
+        self.ticks = self.ticks.wrapping_add(1);
+        self.status_state.tick(&mut self.status);
+        self.today = today();
+        self.expire_copy_preview(now);
+    }
+
+    /// The current frame of the spinner
+    pub fn spinner(&self) -> char {
+        SPINNER[(self.ticks % SPINNER.len() as u64) as usize]
+    }
+
+    /// Hides the copy preview once its time is up
+    pub fn expire_copy_preview(&mut self, now: Instant) {
+        if let Some(preview) = &self.copy_preview {
+            if preview.remaining(now).is_zero() {
+                self.copy_preview = None;
+            }
+        }
+    }
+
+    /// Copies the command prefixed with `sudo`, unless it already starts with it
+    pub fn copy_with_sudo(
+        &mut self,
+        clipboard: &mut dyn Clipboard,
+    ) -> Result<CopyOutcome, ApplicationError> {
+        let command = build_execution_preview(&self.command_to_copy(), true);
+        self.copy(clipboard, &command)
+    }
+
+    /// Copies the command with its executable replaced by the absolute path `resolve` finds for
+    /// it, or as is when it finds none
+    pub fn copy_resolved(
+        &mut self,
+        clipboard: &mut dyn Clipboard,
+        resolve: impl Fn(&Command) -> Option<PathBuf>,
+    ) -> Result<CopyOutcome, ApplicationError> {
+        let command = self.command_to_copy();
+        match command.with_resolved_executable(resolve) {
+            Some(resolved) => self.copy(clipboard, &resolved),
+            None => Ok(CopyOutcome {
+                unresolved: Some(command.executable),
+                ..self.copy(clipboard, &command.command)?
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::path::PathBuf;
+
+    use serial_test::serial;
+
+    use super::{
+        build_execution_preview, App, CapturedOutput, CopyOutcome, GroupBy, StatefulList,
+        StatusState, TabState, UndoEntry, COPY_PREVIEW_DURATION, RECENT_GROUP, RELATED_LIMIT,
+        SEARCH_LIMIT, STATUS_TICKS, UNDO_LIMIT, UNGROUPED_GROUP, UNTAGGED_GROUP,
+    };
+    use crate::config::app_config::AppConfig;
+    use crate::model::command::MAX_DESCRIPTION_LEN;
+    use crate::model::command::{Command, Tags};
+    use crate::service::command_service::CommandService;
+    use crate::service::export::ExportFormat;
+    use crate::storage::command_storage::OrderBy;
+    use crate::ui::action::{Action, Mode, Outcome};
+    use crate::ui::clipboard::MemoryClipboard;
+    use crate::ui::form::FormKind;
+
+    fn fixture() -> Vec<Command> {
+        vec![
+            Command {
+                executable: "git".to_string(),
+                command: "git pull".to_string(),
+                alias: "git_pull".to_string(),
+                tags: Tags(vec!["vcs".to_string(), "daily".to_string()]),
+                ..Default::default()
+            },
+            Command {
+                executable: "git".to_string(),
+                command: "git push".to_string(),
+                alias: "git_push".to_string(),
+                tags: Tags(vec!["vcs".to_string()]),
+                ..Default::default()
+            },
+            Command {
+                executable: "ls".to_string(),
+                command: "ls -a".to_string(),
+                alias: "ls_all".to_string(),
+                tags: Tags(vec!["daily".to_string()]),
+                favorite: true,
+                ..Default::default()
+            },
+            Command {
+                executable: "ssh".to_string(),
+                command: "ssh --version".to_string(),
+                alias: "ssh_version".to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_list_wraps_at_the_ends() {
+        let items = HashMap::from([("git".to_string(), vec!["pull", "push", "status"])]);
+        let mut list = StatefulList::with_items(items);
+
+        list.next("git", true);
+        list.previous("git", true);
+        assert_eq!(list.selected_index(), 2);
+        list.next("git", true);
+        assert_eq!(list.selected_index(), 0);
+    }
+
+    #[test]
+    fn test_list_stops_at_the_ends() {
+        let items = HashMap::from([("git".to_string(), vec!["pull", "push", "status"])]);
+        let mut list = StatefulList::with_items(items);
+
+        list.next("git", false);
+        list.previous("git", false);
+        assert_eq!(list.selected_index(), 0);
+        for _ in 0..3 {
+            list.next("git", false);
+        }
+        assert_eq!(list.selected_index(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rename_in_place() {
+        let mut app = App::from_commands(fixture());
+        app.apply(Action::NextCommand);
+        app.apply(Action::StartRename);
+        assert_eq!(app.mode, Mode::RenameInput);
+        assert_eq!(app.rename_input.as_ref().unwrap().input, "git_pull");
+
+        // Committing the alias unchanged just closes the edit
+        assert_eq!(app.apply(Action::Copy), None);
+        assert_eq!(app.mode, Mode::Normal);
+
+        app.apply(Action::StartRename);
+        for _ in 0..4 {
+            app.apply(Action::DeleteInput);
+        }
+        for c in "push".chars() {
+            app.apply(Action::Input(c));
+        }
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::Rename));
+        app.rename_selected().await.unwrap();
+
+        // The taken alias is refused without losing the edit
+        let rename = app.rename_input.clone().unwrap();
+        assert_eq!(app.mode, Mode::RenameInput);
+        assert_eq!(rename.input, "git_push");
+        assert!(rename.error.unwrap().contains("taken"));
+
+        app.apply(Action::Input('2'));
+        assert_eq!(app.rename_input.as_ref().unwrap().error, None);
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::Rename));
+        app.rename_selected().await.unwrap();
+
+        assert_eq!(app.mode, Mode::Normal);
+        assert!(app.rename_input.is_none());
+        assert_eq!(app.get_selected_command().alias, "git_push2");
+        assert_eq!(app.get_selected_command().command, "git pull");
+    }
+
+    #[test]
+    fn test_jump_to_prefix() {
+        let titles = ["awk", "git", "Grep", "ls"].map(str::to_string).to_vec();
+        let mut tabs = TabState::new(titles);
+
+        tabs.jump_to_prefix('g');
+        assert_eq!(tabs.index, 1);
+        tabs.jump_to_prefix('g');
+        assert_eq!(tabs.index, 2);
+        tabs.jump_to_prefix('G');
+        assert_eq!(tabs.index, 1);
+
+        tabs.jump_to_prefix('l');
+        assert_eq!(tabs.index, 3);
+        tabs.jump_to_prefix('g');
+        assert_eq!(tabs.index, 1);
+
+        tabs.jump_to_prefix('z');
+        assert_eq!(tabs.index, 1);
+    }
+
+    #[test]
+    fn test_jump_mode() {
+        let mut app = App::from_commands(fixture());
+        app.apply(Action::StartJump);
+        assert_eq!(app.mode, Mode::JumpToTab);
+
+        app.apply(Action::Input('l'));
+        assert_eq!(app.get_selected_executable(), "ls");
+        app.apply(Action::Cancel);
+        assert_eq!(app.mode, Mode::Normal);
+        assert_eq!(app.get_selected_executable(), "ls");
+    }
+
+    #[test]
+    fn test_group_by_executable() {
+        let app = App::from_commands(fixture());
+
+        let grouped = app.grouped_commands();
+        assert_eq!(grouped.len(), 3);
+        assert_eq!(grouped["git"].len(), 2);
+        assert_eq!(grouped["ls"].len(), 1);
+        assert_eq!(grouped["ssh"].len(), 1);
+        assert_eq!(app.tabs.titles, vec!["git", "ls", "ssh"]);
+    }
+
+    #[test]
+    fn test_group_by_tag() {
+        let mut app = App::from_commands(fixture());
+        app.cycle_group_by();
+        assert_eq!(app.group_by, GroupBy::Tag);
+
+        let grouped = app.grouped_commands();
+        assert_eq!(grouped.len(), 3);
+        assert_eq!(grouped["vcs"].len(), 2);
+        assert_eq!(grouped["daily"].len(), 2);
+        assert_eq!(grouped[UNTAGGED_GROUP][0].alias, "ssh_version");
+        assert_eq!(app.tabs.titles, vec!["daily", "untagged", "vcs"]);
+    }
+
+    #[test]
+    fn test_group_by_favorite() {
+        let mut app = App::from_commands(fixture());
+        app.cycle_group_by();
+        app.cycle_group_by();
+        assert_eq!(app.group_by, GroupBy::Favorite);
+
+        let grouped = app.grouped_commands();
+        assert_eq!(grouped["favorites"].len(), 1);
+        assert_eq!(grouped["others"].len(), 3);
+    }
+
+    #[test]
+    fn test_view_state_summary() {
+        let mut app = App::from_commands(fixture());
+        assert_eq!(app.view_state_summary(), "group:executable sort:alias");
+
+        app.config.sort = OrderBy::Usage;
+        app.apply(Action::CycleGroup);
+        app.apply(Action::StartSearch);
+        for c in "git".chars() {
+            app.apply(Action::Input(c));
+        }
+        assert_eq!(
+            app.view_state_summary(),
+            "group:tag sort:usage filter:\"git\""
+        );
+    }
+
+    #[test]
+    fn test_group_by_executable_group() {
+        let mut app = App::from_commands(fixture());
+        for _ in 0..3 {
+            app.cycle_group_by();
+        }
+        assert_eq!(app.group_by, GroupBy::ExecutableGroup);
+
+        let grouped = app.grouped_commands();
+        assert_eq!(grouped["vcs"].len(), 2);
+        assert_eq!(grouped["network"][0].alias, "ssh_version");
+        assert_eq!(grouped[UNGROUPED_GROUP][0].alias, "ls_all");
+        assert_eq!(app.tabs.titles, vec!["network", "ungrouped", "vcs"]);
+
+        app.cycle_group_by();
+        assert_eq!(app.group_by, GroupBy::Executable);
+    }
+
+    #[test]
+    fn test_apply_navigation() {
+        let mut app = App::from_commands(fixture());
+        assert_eq!(app.get_selected_executable(), "git");
+
+        assert_eq!(app.apply(Action::NextCommand), None);
+        assert_eq!(app.apply(Action::NextCommand), None);
+        assert_eq!(app.get_selected_command().alias, "git_push");
+
+        assert_eq!(app.apply(Action::PreviousCommand), None);
+        assert_eq!(app.get_selected_command().alias, "git_pull");
+
+        assert_eq!(app.apply(Action::NextTab), None);
+        assert_eq!(app.get_selected_executable(), "ls");
+        assert_eq!(app.commands.state.selected(), None);
+
+        assert_eq!(app.apply(Action::PreviousTab), None);
+        assert_eq!(app.apply(Action::PreviousTab), None);
+        assert_eq!(app.get_selected_executable(), "ssh");
+    }
+
+    #[test]
+    fn test_apply_copy_and_quit() {
+        let mut app = App::from_commands(fixture());
+
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::Copy { exit: true }));
+        assert_eq!(app.apply(Action::Quit), Some(Outcome::Quit));
+
+        app.config.exit_on_copy = false;
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::Copy { exit: false }));
+    }
+
+    #[test]
+    fn test_environment_picker() {
+        let mut commands = fixture();
+        for environment in ["staging", "prod"] {
+            commands.push(Command {
+                executable: "kubectl".to_string(),
+                command: format!("kubectl --context {environment} get pods"),
+                alias: "pods".to_string(),
+                environment: Some(environment.to_string()),
+                ..Default::default()
+            });
+        }
+        let mut app = App::from_commands(commands);
+
+        // Both variants are listed once under the kubectl tab
+        assert_eq!(app.grouped_commands()["kubectl"].len(), 1);
+
+        app.apply(Action::NextTab);
+        assert_eq!(app.get_selected_executable(), "kubectl");
+
+        assert_eq!(app.apply(Action::Copy), None);
+        assert_eq!(app.mode, Mode::PickEnvironment);
+        assert_eq!(app.command_to_copy().environment, Some("prod".to_string()));
+
+        app.apply(Action::NextCommand);
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::Copy { exit: true }));
+        assert_eq!(
+            app.command_to_copy().command,
+            "kubectl --context staging get pods".to_string()
+        );
+
+        app.apply(Action::Cancel);
+        assert_eq!(app.mode, Mode::Normal);
+        assert!(app.environment_picker.is_none());
+    }
+
+    #[test]
+    fn test_check_syntax() {
+        let mut commands = fixture();
+        commands.push(Command::new(
+            "echo".to_string(),
+            "echo \"unbalanced".to_string(),
+            "broken".to_string(),
+            None,
+        ));
+        let mut app = App::from_commands(commands);
+
+        app.check_syntax();
+
+        assert_eq!(app.invalid_aliases.len(), 1);
+        assert!(app.invalid_aliases.contains("broken"));
+    }
+
+    #[test]
+    fn test_copy_trailing_newline() {
+        let mut app = App::from_commands(fixture());
+        let mut clipboard = MemoryClipboard::default();
+
+        app.save_command_to_clipboard(&mut clipboard).unwrap();
+        app.config.copy_trailing_newline = true;
+        app.save_command_to_clipboard(&mut clipboard).unwrap();
+
+        assert_eq!(clipboard.contents, ["git pull", "git pull\n"]);
+    }
+
+    #[test]
+    fn test_copy_with_sudo() {
+        let mut app = App::from_commands(vec![
+            Command::new("ls".to_string(), "ls".to_string(), "ls".to_string(), None),
+            Command::new(
+                "ls".to_string(),
+                "sudo ls".to_string(),
+                "sudo_ls".to_string(),
+                None,
+            ),
+        ]);
+        let mut clipboard = MemoryClipboard::default();
+
+        assert_eq!(
+            app.apply(Action::CopyWithSudo),
+            Some(Outcome::CopyWithSudo { exit: true })
+        );
+        app.copy_with_sudo(&mut clipboard).unwrap();
+
+        app.apply(Action::NextCommand);
+        app.apply(Action::NextCommand);
+        app.copy_with_sudo(&mut clipboard).unwrap();
+
+        assert_eq!(clipboard.contents, vec!["sudo ls", "sudo ls"]);
+    }
+
+    #[test]
+    fn test_copy_resolved() {
+        let mut app = App::from_commands(vec![
+            Command::new(
+                "git".to_string(),
+                "git pull".to_string(),
+                "git_pull".to_string(),
+                None,
+            ),
+            Command::new(
+                "zz".to_string(),
+                "zz --fast".to_string(),
+                "zz_fast".to_string(),
+                None,
+            ),
+        ]);
+        let resolve = |command: &Command| -> Option<PathBuf> {
+            (command.executable == "git").then(|| PathBuf::from("/usr/bin/git"))
+        };
+        let mut clipboard = MemoryClipboard::default();
+
+        assert_eq!(
+            app.apply(Action::CopyResolved),
+            Some(Outcome::CopyResolved { exit: true })
+        );
+        let copy = app.copy_resolved(&mut clipboard, resolve).unwrap();
+        assert_eq!(copy.copied_text, "/usr/bin/git pull");
+        assert_eq!(copy.unresolved, None);
+
+        app.apply(Action::NextTab);
+        let copy = app.copy_resolved(&mut clipboard, resolve).unwrap();
+        assert_eq!(copy.copied_text, "zz --fast");
+        assert_eq!(copy.unresolved.as_deref(), Some("zz"));
+
+        assert_eq!(clipboard.contents, vec!["/usr/bin/git pull", "zz --fast"]);
+    }
+
+    #[test]
+    fn test_copy_outcome_reports_expansion() {
+        // Environment variables are not placeholders
+        std::env::set_var("COPY_OUTCOME_TEST_HOST", "db1");
+        let mut app = App::from_commands(vec![
+            Command::new(
+                "ssh".to_string(),
+                "ssh -F {pwd}/ssh_config db1".to_string(),
+                "ssh_db".to_string(),
+                None,
+            ),
+            Command::new(
+                "ssh".to_string(),
+                "ssh {COPY_OUTCOME_TEST_HOST}".to_string(),
+                "ssh_other".to_string(),
+                None,
+            ),
+        ]);
+        let mut clipboard = MemoryClipboard::default();
+        let pwd = std::env::current_dir().unwrap().display().to_string();
+
+        assert_eq!(
+            app.save_command_to_clipboard(&mut clipboard).unwrap(),
+            CopyOutcome {
+                copied_text: format!("ssh -F {pwd}/ssh_config db1"),
+                expanded: true,
+                unresolved: None,
+            }
+        );
+
+        app.apply(Action::NextCommand);
+        app.apply(Action::NextCommand);
+        assert_eq!(
+            app.save_command_to_clipboard(&mut clipboard).unwrap(),
+            CopyOutcome {
+                copied_text: "ssh {COPY_OUTCOME_TEST_HOST}".to_string(),
+                expanded: false,
+                unresolved: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_status_expires_after_ticks() {
+        let mut state = StatusState::default();
+        let mut status = Some("Copied!".to_string());
+
+        state.tick(&mut status);
+        for _ in 1..STATUS_TICKS {
+            state.tick(&mut status);
+        }
+        assert_eq!(status.as_deref(), Some("Copied!"));
+
+        // A new message gets the full time again
+        status = Some("Command updated".to_string());
+        state.tick(&mut status);
+        for _ in 1..STATUS_TICKS {
+            state.tick(&mut status);
+            assert!(status.is_some());
+        }
+        state.tick(&mut status);
+        assert!(status.is_none());
+
+        state.tick(&mut status);
+        assert!(status.is_none());
+    }
+
+    #[test]
+    fn test_copy_preview() {
+        let mut app = App::from_commands(fixture());
+        let mut clipboard = MemoryClipboard::default();
+        app.apply(Action::NextCommand);
+
+        app.copy_with_sudo(&mut clipboard).unwrap();
+        assert!(app.copy_preview.is_none());
+
+        app.config.exit_on_copy = false;
+        app.copy_with_sudo(&mut clipboard).unwrap();
+        let preview = app.copy_preview.clone().unwrap();
+        assert_eq!(preview.text, clipboard.contents[1]);
+        assert_eq!(
+            preview.text,
+            build_execution_preview(&app.get_selected_command(), true)
+        );
+
+        app.expire_copy_preview(preview.shown_at);
+        assert!(app.copy_preview.is_some());
+        app.expire_copy_preview(preview.shown_at + COPY_PREVIEW_DURATION);
+        assert!(app.copy_preview.is_none());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_mixed_case_executables_share_a_tab() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+
+        let _ = service
+            .insert_command("Git status", "git_status", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+
+        let app = App::from_commands(service.get_all_commands().await.unwrap());
+        assert_eq!(app.executables(), vec!["git".to_string()]);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_delete_then_undo() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        let _ = service
+            .insert_command("ls -a", "ls_all", None)
+            .await
+            .unwrap();
+
+        let mut app = App::from_service(service, AppConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(app.apply(Action::Delete), Some(Outcome::Delete));
+        app.delete_selected().await.unwrap();
+        assert_eq!(app.executables(), vec!["ls", RECENT_GROUP]);
+
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        assert!(service.get_command_by_alias("git_pull").await.is_err());
+
+        assert_eq!(app.apply(Action::Undo), Some(Outcome::Undo));
+        assert!(app.undo().await.unwrap());
+        assert_eq!(app.executables(), vec!["git", "ls", RECENT_GROUP]);
+
+        let restored = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(restored.command, "git pull");
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_undo_empty_stack() {
+        let mut app = App::from_commands(fixture());
+
+        assert_eq!(app.apply(Action::Undo), None);
+        assert_eq!(app.status.as_deref(), Some("Nothing to undo"));
+        assert!(!app.undo().await.unwrap());
+        assert_eq!(app.grouped_commands().len(), 3);
+    }
+
+    #[test]
+    fn test_undo_stack_is_bounded() {
+        let mut app = App::from_commands(Vec::new());
+        for _ in 0..UNDO_LIMIT + 5 {
+            app.push_undo(UndoEntry::Reinsert(fixture()[0].clone()));
+        }
+
+        assert_eq!(app.undo_stack.len(), UNDO_LIMIT);
+    }
+
+    #[test]
+    fn test_global_search() {
+        let app = App::from_commands(fixture());
+
+        let results = app.global_search("-A");
+        let aliases: Vec<&str> = results.iter().map(|c| c.alias.as_str()).collect();
+        assert_eq!(aliases, vec!["ls_all"]);
+
+        // Matches in two executables come back in a single flat list
+        let results = app.global_search("sh");
+        let found: Vec<(&str, &str)> = results
+            .iter()
+            .map(|c| (c.executable.as_str(), c.alias.as_str()))
+            .collect();
+        assert_eq!(found, vec![("git", "git_push"), ("ssh", "ssh_version")]);
+    }
+
+    #[test]
+    fn test_related_commands() {
+        let tagged = |executable: &str, alias: &str, tags: &[&str]| Command {
+            executable: executable.to_string(),
+            command: format!("{executable} {alias}"),
+            alias: alias.to_string(),
+            tags: Tags(tags.iter().map(|tag| tag.to_string()).collect()),
+            ..Default::default()
+        };
+        let app = App::from_commands(vec![
+            tagged("kubectl", "pods", &["k8s", "prod"]),
+            tagged("kubectl", "nodes", &[]),
+            tagged("helm", "releases", &["k8s", "prod"]),
+            tagged("stern", "logs", &["k8s"]),
+            tagged("kubectl", "contexts", &["k8s"]),
+            tagged("git", "status", &["vcs"]),
+        ]);
+        let selected = app
+            .all_commands
+            .iter()
+            .find(|command| command.alias == "pods")
+            .unwrap()
+            .clone();
+
+        let related = app.related_commands(&selected, RELATED_LIMIT);
+        let aliases: Vec<&str> = related.iter().map(|c| c.alias.as_str()).collect();
+        assert_eq!(aliases, vec!["releases", "contexts", "logs", "nodes"]);
+
+        let related = app.related_commands(&selected, 2);
+        assert_eq!(related.len(), 2);
+
+        let status = tagged("git", "status", &["vcs"]);
+        assert!(app.related_commands(&status, RELATED_LIMIT).is_empty());
+    }
+
+    #[test]
+    fn test_matching_executables() {
+        let app = App::from_commands(fixture());
+
+        assert_eq!(
+            app.matching_executables("sh"),
+            HashSet::from(["git".to_string(), "ssh".to_string()])
+        );
+        assert_eq!(
+            app.matching_executables("-A"),
+            HashSet::from(["ls".to_string()])
+        );
+        assert!(app.matching_executables("nothing matches").is_empty());
+    }
+
+    #[test]
+    fn test_export_current_view() {
+        let mut app = App::from_commands(fixture());
+        let aliases = |commands: &[Command]| -> Vec<String> {
+            commands.iter().map(|c| c.alias.clone()).collect()
+        };
+        assert_eq!(aliases(&app.current_view()), ["git_pull", "git_push"]);
+
+        app.apply(Action::StartSearch);
+        for c in "sh".chars() {
+            app.apply(Action::Input(c));
+        }
+        assert_eq!(aliases(&app.current_view()), ["git_push", "ssh_version"]);
+
+        app.apply(Action::StartExport);
+        assert_eq!(app.mode, Mode::ExportInput);
+        let path = std::env::temp_dir().join("command_organiser_view.json");
+        app.config.export_format = ExportFormat::Json;
+        app.export_input.as_mut().unwrap().path = path.display().to_string();
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::Export));
+
+        app.export_view().unwrap();
+        assert_eq!(app.mode, Mode::GlobalSearch);
+        let exported: Vec<Command> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(aliases(&exported), ["git_push", "ssh_version"]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_apply_global_search() {
+        let mut app = App::from_commands(fixture());
+        let mut clipboard = MemoryClipboard::default();
+
+        assert_eq!(app.apply(Action::StartSearch), None);
+        assert_eq!(app.mode, Mode::GlobalSearch);
+        for c in "shx".chars() {
+            app.apply(Action::Input(c));
+        }
+        assert!(app.search_results().is_empty());
+        assert_eq!(app.apply(Action::Copy), None);
+
+        app.apply(Action::DeleteInput);
+        app.apply(Action::NextCommand);
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::Copy { exit: true }));
+
+        app.save_command_to_clipboard(&mut clipboard).unwrap();
+        assert_eq!(clipboard.contents, vec!["ssh --version".to_string()]);
+        assert_eq!(app.mode, Mode::Normal);
+        assert!(app.search.is_none());
+    }
+
+    #[test]
+    fn test_pinned_commands_lead_their_tab() {
+        let mut commands = fixture();
+        commands[1].pinned = true;
+
+        let app = App::from_commands(commands);
+        let grouped = app.grouped_commands();
+        let git: Vec<&str> = grouped["git"]
+            .iter()
+            .map(|command| command.alias.as_str())
+            .collect();
+        assert_eq!(git, vec!["git_push", "git_pull"]);
+    }
+
+    #[tokio::test]
+    async fn test_append_last_output_to_notes() {
+        let mut app = App::from_commands(fixture());
+        assert_eq!(app.apply(Action::AppendOutput), None);
+        assert_eq!(app.apply(Action::Run), Some(Outcome::Run));
+
+        let mut command = app.get_selected_command();
+        command.description = Some("Fetch and merge".to_string());
+        app.last_output = Some(CapturedOutput {
+            command: command.clone(),
+            output: "Already up to date.\n".to_string(),
+        });
+        app.append_last_output_to_notes().await.unwrap();
+        assert_eq!(
+            app.get_selected_command().description.as_deref(),
+            Some("Fetch and merge\nOutput: Already up to date.")
+        );
+        assert!(app.last_output.is_none());
+
+        app.last_output = Some(CapturedOutput {
+            command: app.get_selected_command(),
+            output: "x".repeat(10_000),
+        });
+        app.append_last_output_to_notes().await.unwrap();
+        let description = app.get_selected_command().description.unwrap();
+        assert!(description.ends_with('…'));
+        assert!(description.starts_with("Fetch and merge\nOutput: Already up to date."));
+        assert!(description.chars().count() <= MAX_DESCRIPTION_LEN);
+    }
+
+    #[tokio::test]
+    async fn test_toggle_pin() {
+        let mut app = App::from_commands(fixture());
+        app.apply(Action::NextCommand);
+        app.apply(Action::NextCommand);
+        assert_eq!(app.apply(Action::TogglePin), Some(Outcome::TogglePin));
+
+        app.toggle_pin().await.unwrap();
+        assert_eq!(app.get_by_executable("git")[0].alias, "git_push");
+        assert_eq!(app.get_selected_command().alias, "git_push");
+
+        app.toggle_pin().await.unwrap();
+        assert_eq!(app.get_by_executable("git")[0].alias, "git_pull");
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_new_reads_given_db() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        service.close().await;
+
+        let app = App::new("test.sqlite", AppConfig::default()).await.unwrap();
+        assert_eq!(app.get_by_executable("git")[0].alias, "git_pull");
+        app.close().await;
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_toggle_enabled() {
+        let mut app = App::from_commands(fixture());
+        app.apply(Action::NextCommand);
+        assert_eq!(
+            app.apply(Action::ToggleEnabled),
+            Some(Outcome::ToggleEnabled)
+        );
+
+        app.toggle_enabled().await.unwrap();
+        let aliases = |app: &App| -> Vec<String> {
+            app.get_by_executable("git")
+                .into_iter()
+                .map(|command| command.alias)
+                .collect()
+        };
+        assert_eq!(aliases(&app), ["git_push"]);
+        assert!(app.global_search("pull").is_empty());
+
+        app.apply(Action::ToggleShowDisabled);
+        assert_eq!(aliases(&app), ["git_pull", "git_push"]);
+        assert!(!app.get_by_executable("git")[0].enabled);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_tag_selected_commands() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        for (command, alias) in [
+            ("git pull", "git_pull"),
+            ("git push", "git_push"),
+            ("ls -a", "ls_all"),
+            ("ssh --version", "ssh_version"),
+        ] {
+            let _ = service.insert_command(command, alias, None).await.unwrap();
+        }
+        let mut app = App::from_service(service, AppConfig::default())
+            .await
+            .unwrap();
+
+        // Select both git commands and ls_all, toggling git_push off and on again
+        app.apply(Action::NextCommand);
+        app.apply(Action::ToggleSelect);
+        app.apply(Action::NextCommand);
+        app.apply(Action::ToggleSelect);
+        app.apply(Action::ToggleSelect);
+        app.apply(Action::ToggleSelect);
+        app.apply(Action::NextTab);
+        app.apply(Action::NextCommand);
+        app.apply(Action::ToggleSelect);
+        assert_eq!(app.selected_aliases.len(), 3);
+
+        assert_eq!(app.apply(Action::StartTagging), None);
+        assert_eq!(app.mode, Mode::TagInput);
+        for c in "daily".chars() {
+            app.apply(Action::Input(c));
+        }
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::ApplyTag));
+        app.apply_tag().await.unwrap();
+
+        assert_eq!(app.mode, Mode::Normal);
+        assert!(app.selected_aliases.is_empty());
+
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        for alias in ["git_pull", "git_push", "ls_all"] {
+            let command = service.get_command_by_alias(alias).await.unwrap();
+            assert_eq!(command.tags.0, vec!["daily".to_string()]);
+        }
+        assert!(service
+            .get_command_by_alias("ssh_version")
+            .await
+            .unwrap()
+            .tags
+            .is_empty());
+
+        app.cycle_group_by();
+        assert_eq!(app.grouped_commands()["daily"].len(), 3);
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[test]
+    fn test_global_search_limit() {
+        let commands = (0..SEARCH_LIMIT + 20)
+            .map(|i| Command {
+                executable: "echo".to_string(),
+                command: format!("echo {i}"),
+                alias: format!("echo_{i}"),
+                ..Default::default()
+            })
+            .collect();
+        let mut app = App::from_commands(commands);
+
+        app.apply(Action::StartSearch);
+        app.apply(Action::Input('e'));
+
+        let (results, total) = app.search_results_with_total();
+        assert_eq!(results.len(), SEARCH_LIMIT);
+        assert_eq!(total, SEARCH_LIMIT + 20);
+    }
+
+    #[test]
+    fn test_edit_description_rejects_long_input() {
+        let mut app = App::from_commands(fixture());
+        app.apply(Action::NextCommand);
+
+        assert_eq!(app.apply(Action::EditDescription), None);
+        assert_eq!(app.mode, Mode::Form);
+        for _ in 0..301 {
+            app.apply(Action::Input('d'));
+        }
+
+        assert_eq!(app.apply(Action::Copy), None);
+        let form = app.form.as_ref().unwrap();
+        assert!(form.error.as_ref().unwrap().contains("description"));
+        assert_eq!(app.status, form.error);
+
+        app.apply(Action::DeleteInput);
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::SubmitForm));
+    }
+
+    #[test]
+    fn test_leaving_dirty_form_asks_first() {
+        let mut app = App::from_commands(fixture());
+
+        app.apply(Action::EditDescription);
+        assert!(!app.dirty);
+        assert_eq!(app.apply(Action::Cancel), None);
+        assert_eq!(app.mode, Mode::Normal);
+        assert!(app.form.is_none());
+
+        app.apply(Action::EditDescription);
+        app.apply(Action::Input('x'));
+        assert!(app.dirty);
+        assert_eq!(app.apply(Action::Cancel), None);
+        assert_eq!(app.mode, Mode::ConfirmDiscard);
+        assert!(app.form.is_some());
+
+        // Anything but y goes back to the form with the edits kept
+        app.apply(Action::Cancel);
+        assert_eq!(app.mode, Mode::Form);
+        assert!(app.dirty);
+        assert!(app.form.as_ref().unwrap().fields[0].value.ends_with('x'));
+
+        app.apply(Action::Quit);
+        assert_eq!(app.mode, Mode::ConfirmDiscard);
+        app.apply(Action::Confirm);
+        assert_eq!(app.mode, Mode::Normal);
+        assert!(app.form.is_none());
+        assert!(!app.dirty);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_edit_description_then_undo() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", Some("Pull".to_string()))
+            .await
+            .unwrap();
+        let mut app = App::from_service(service, AppConfig::default())
+            .await
+            .unwrap();
+
+        app.apply(Action::NextCommand);
+        app.apply(Action::EditDescription);
+        for c in " the changes".chars() {
+            app.apply(Action::Input(c));
+        }
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::SubmitForm));
+        app.submit_form().await.unwrap();
+        assert_eq!(app.mode, Mode::Normal);
+
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let stored = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(stored.description.as_deref(), Some("Pull the changes"));
+
+        assert!(app.undo().await.unwrap());
+        let stored = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(stored.description.as_deref(), Some("Pull"));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_selected() {
+        let mut app = App::from_commands(fixture());
+        app.apply(Action::NextCommand);
+        let selected = app.get_selected_command();
+
+        app.duplicate_selected();
+        assert_eq!(app.mode, Mode::Form);
+
+        let form = app.form.as_ref().unwrap();
+        assert_eq!(form.kind, FormKind::AddCommand);
+        assert_eq!(form.value("command"), Some(selected.command.as_str()));
+        assert_eq!(
+            form.value("description"),
+            Some(selected.description.clone().unwrap_or_default().as_str())
+        );
+        assert_eq!(form.value("alias"), Some(""));
+
+        // The alias has to be typed before the duplicate can be saved
+        assert_eq!(app.apply(Action::Copy), None);
+        assert!(app.status.as_ref().unwrap().contains("alias"));
+
+        for c in "variant".chars() {
+            app.apply(Action::Input(c));
+        }
+        assert_eq!(app.apply(Action::Copy), Some(Outcome::SubmitForm));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_recently_added_tab() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        for (command, alias) in [
+            ("git pull", "git_pull"),
+            ("ls -a", "ls_all"),
+            ("git push", "git_push"),
+        ] {
+            let _ = service.insert_command(command, alias, None).await.unwrap();
+        }
+        let mut app = App::from_service(service, AppConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(app.tabs.titles, vec!["git", "ls", RECENT_GROUP]);
+        let recent: Vec<String> = app.commands.items[RECENT_GROUP]
+            .iter()
+            .map(|command| command.alias.clone())
+            .collect();
+        assert_eq!(recent, vec!["git_push", "ls_all", "git_pull"]);
+
+        // Deleting the newest command drops it from the tab
+        app.apply(Action::PreviousTab);
+        app.apply(Action::NextCommand);
+        app.delete_selected().await.unwrap();
+        assert_eq!(app.commands.items[RECENT_GROUP][0].alias, "ls_all");
+
+        app.cycle_group_by();
+        assert!(!app.tabs.titles.contains(&RECENT_GROUP.to_string()));
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_update_selected_command_then_undo() {
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let _ = service
+            .insert_command("git pull", "git_pull", None)
+            .await
+            .unwrap();
+        let mut app = App::from_service(service, AppConfig::default())
+            .await
+            .unwrap();
+
+        app.apply(Action::NextCommand);
+        assert_eq!(app.apply(Action::EditInEditor), Some(Outcome::EditInEditor));
+        app.update_selected_command("git pull --rebase")
+            .await
+            .unwrap();
+        assert_eq!(app.get_selected_command().command, "git pull --rebase");
+
+        let service = CommandService::new("test.sqlite").await.unwrap();
+        let stored = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(stored.command, "git pull --rebase");
+
+        assert!(app.undo().await.unwrap());
+        let stored = service.get_command_by_alias("git_pull").await.unwrap();
+        assert_eq!(stored.command, "git pull");
+
+        std::fs::remove_file("test.sqlite").unwrap();
+    }
+
+    #[test]
+    fn test_dangerous_copy_needs_confirmation() {
+        let mut commands = fixture();
+        commands[0].command = "rm -rf build".to_string();
+        let mut app = App::from_commands(commands.clone());
+        let dangerous = commands[0].alias.clone();
+
+        // Select the dangerous command in its tab
+        while app.get_selected_command().alias != dangerous {
+            app.apply(Action::NextTab);
+            app.apply(Action::NextCommand);
+        }
+
+        assert_eq!(app.apply(Action::Copy), None);
+        assert_eq!(app.mode, Mode::ConfirmDanger);
+        assert_eq!(app.apply(Action::Cancel), None);
+        assert_eq!(app.mode, Mode::Normal);
+        assert_eq!(app.status.as_deref(), Some("Copy cancelled"));
+
+        app.apply(Action::Copy);
+        assert_eq!(
+            app.apply(Action::Confirm),
+            Some(Outcome::Copy { exit: true })
+        );
+        assert!(app.pending_copy.is_none());
+    }
+
+    #[test]
+    fn test_search_match_count() {
+        let mut app = App::from_commands(fixture());
+        let all = app.global_search("").len();
+
+        app.apply(Action::StartSearch);
+        assert_eq!(app.search.as_ref().unwrap().matches, all);
+
+        for c in "zzz".chars() {
+            app.apply(Action::Input(c));
+        }
+        assert_eq!(app.search.as_ref().unwrap().matches, 0);
+
+        for _ in 0..3 {
+            app.apply(Action::DeleteInput);
+        }
+        assert_eq!(app.search.as_ref().unwrap().matches, all);
+    }
 }
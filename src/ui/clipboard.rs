@@ -0,0 +1,214 @@
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clipboard::{ClipboardContext, ClipboardProvider};
+use serde::Deserialize;
+
+/// Destination of the copied commands
+pub trait Clipboard {
+    fn set_contents(&mut self, contents: String) -> Result<(), Box<dyn Error>>;
+}
+
+/// The clipboard of the desktop session
+pub struct SystemClipboard;
+
+impl Clipboard for SystemClipboard {
+    fn set_contents(&mut self, contents: String) -> Result<(), Box<dyn Error>> {
+        let mut clipboard_context: ClipboardContext = ClipboardProvider::new()?;
+        clipboard_context.set_contents(contents)
+    }
+}
+
+/// Copies through the terminal with the OSC 52 escape sequence, which works over SSH
+pub struct Osc52Clipboard<W: Write> {
+    pub terminal: W,
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(input: &[u8]) -> String {
+    let mut encoded = String::with_capacity(input.len().div_ceil(3) * 4);
+
+    for chunk in input.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (bits >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+impl<W: Write> Clipboard for Osc52Clipboard<W> {
+    fn set_contents(&mut self, contents: String) -> Result<(), Box<dyn Error>> {
+        write!(
+            self.terminal,
+            "\x1b]52;c;{}\x07",
+            base64(contents.as_bytes())
+        )?;
+        self.terminal.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes the copied command to a file, for sessions without any clipboard
+pub struct FileClipboard {
+    pub path: PathBuf,
+}
+
+impl Clipboard for FileClipboard {
+    fn set_contents(&mut self, contents: String) -> Result<(), Box<dyn Error>> {
+        std::fs::write(&self.path, contents)?;
+        Ok(())
+    }
+}
+
+/// Which clipboard the copied commands go to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardProviderKind {
+    /// The system clipboard when a display is available, OSC 52 otherwise
+    #[default]
+    Auto,
+    System,
+    Osc52,
+    /// The file set by `clipboard_file`
+    File,
+}
+
+/// Resolves `auto` by looking for a graphical session in the given variables
+fn resolve_provider(
+    kind: ClipboardProviderKind,
+    var: impl Fn(&str) -> Option<String>,
+) -> ClipboardProviderKind {
+    if kind != ClipboardProviderKind::Auto {
+        return kind;
+    }
+
+    let has_display = ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|name| var(name).is_some_and(|value| !value.is_empty()));
+
+    if has_display || cfg!(any(target_os = "macos", target_os = "windows")) {
+        ClipboardProviderKind::System
+    } else {
+        ClipboardProviderKind::Osc52
+    }
+}
+
+/// Where the `file` clipboard writes when no `clipboard_file` is configured
+const DEFAULT_CLIPBOARD_FILE: &str = "copied_command.txt";
+
+/// Checks the clipboard of this session can be copied to, without replacing its contents.
+/// Returns the provider `kind` resolved to.
+pub fn check_clipboard(
+    kind: ClipboardProviderKind,
+    file: Option<&Path>,
+) -> Result<ClipboardProviderKind, Box<dyn Error>> {
+    let provider = resolve_provider(kind, |name| std::env::var(name).ok());
+
+    match provider {
+        ClipboardProviderKind::Osc52 => {}
+        ClipboardProviderKind::File => {
+            let file = file.unwrap_or(Path::new(DEFAULT_CLIPBOARD_FILE));
+            let dir = match file.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            if !dir.is_dir() {
+                return Err(format!("the directory of {file:?} does not exist").into());
+            }
+        }
+        ClipboardProviderKind::Auto | ClipboardProviderKind::System => {
+            let _: ClipboardContext = ClipboardProvider::new()?;
+        }
+    }
+
+    Ok(provider)
+}
+
+/// The clipboard to copy to for this session
+pub fn clipboard_for(kind: ClipboardProviderKind, file: Option<PathBuf>) -> Box<dyn Clipboard> {
+    match resolve_provider(kind, |name| std::env::var(name).ok()) {
+        ClipboardProviderKind::Osc52 => Box::new(Osc52Clipboard {
+            terminal: std::io::stdout(),
+        }),
+        ClipboardProviderKind::File => Box::new(FileClipboard {
+            path: file.unwrap_or_else(|| PathBuf::from(DEFAULT_CLIPBOARD_FILE)),
+        }),
+        ClipboardProviderKind::Auto | ClipboardProviderKind::System => Box::new(SystemClipboard),
+    }
+}
+
+/// Keeps the copied contents around so tests can inspect them
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryClipboard {
+    pub contents: Vec<String>,
+}
+
+#[cfg(test)]
+impl Clipboard for MemoryClipboard {
+    fn set_contents(&mut self, contents: String) -> Result<(), Box<dyn Error>> {
+        self.contents.push(contents);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64, resolve_provider, Clipboard, ClipboardProviderKind, Osc52Clipboard};
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"git pull"), "Z2l0IHB1bGw=");
+    }
+
+    #[test]
+    fn test_osc52_sequence() {
+        let mut clipboard = Osc52Clipboard {
+            terminal: Vec::new(),
+        };
+
+        clipboard.set_contents("git pull".to_string()).unwrap();
+
+        assert_eq!(clipboard.terminal, b"\x1b]52;c;Z2l0IHB1bGw=\x07");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_resolve_provider() {
+        let headless = |_: &str| None;
+        let desktop = |name: &str| (name == "DISPLAY").then(|| ":0".to_string());
+
+        assert_eq!(
+            resolve_provider(ClipboardProviderKind::Auto, headless),
+            ClipboardProviderKind::Osc52
+        );
+        assert_eq!(
+            resolve_provider(ClipboardProviderKind::Auto, desktop),
+            ClipboardProviderKind::System
+        );
+        assert_eq!(
+            resolve_provider(ClipboardProviderKind::File, desktop),
+            ClipboardProviderKind::File
+        );
+    }
+}
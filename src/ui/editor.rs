@@ -0,0 +1,65 @@
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The editor the user asked for through `$VISUAL` or `$EDITOR`, `vi` otherwise
+pub fn editor_command() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// A file in the temp dir no other edit is using
+fn temp_file() -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+
+    std::env::temp_dir().join(format!(
+        "command_organiser-{}-{nanos}.sh",
+        std::process::id()
+    ))
+}
+
+/// Lets `editor` change `text` through a temp file and returns what was saved, without the
+/// trailing newline editors add. The editor may carry its own arguments, e.g. `code --wait`.
+pub fn edit_via_editor(text: &str, editor: &str) -> io::Result<String> {
+    let path = temp_file();
+    std::fs::write(&path, text)?;
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$1\""))
+        .arg("sh")
+        .arg(&path)
+        .status();
+
+    let edited = match status {
+        Ok(status) if status.success() => std::fs::read_to_string(&path),
+        Ok(status) => Err(io::Error::other(format!("{editor} exited with {status}"))),
+        Err(e) => Err(e),
+    };
+    let _ = std::fs::remove_file(&path);
+
+    Ok(edited?.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::edit_via_editor;
+
+    #[test]
+    fn test_edit_via_editor() {
+        let edited = edit_via_editor("git pull", "printf ' --rebase\\n' >>").unwrap();
+        assert_eq!(edited, "git pull --rebase");
+
+        let edited = edit_via_editor("for i in 1 2; do\n  echo $i\ndone", "true").unwrap();
+        assert_eq!(edited, "for i in 1 2; do\n  echo $i\ndone");
+
+        assert!(edit_via_editor("git pull", "false").is_err());
+    }
+}
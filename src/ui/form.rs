@@ -0,0 +1,146 @@
+use crate::model::command::{Command, ValidationError};
+use crate::service::command_service::normalized_executable;
+
+/// What submitting a form does
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormKind {
+    /// Replace the description of the command
+    EditDescription(Box<Command>),
+    /// Store a new command
+    AddCommand,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormField {
+    /// Name of the `Command` field it edits, used to point at validation errors
+    pub name: &'static str,
+    pub value: String,
+}
+
+/// Text fields edited in a popup, one of them focused
+#[derive(Clone, Debug, PartialEq)]
+pub struct Form {
+    pub kind: FormKind,
+    pub fields: Vec<FormField>,
+    pub focus: usize,
+    /// Why the last submission was rejected
+    pub error: Option<String>,
+    /// Longest alias the form accepts
+    pub max_alias_len: usize,
+}
+
+impl Form {
+    pub fn edit_description(command: Command, max_alias_len: usize) -> Form {
+        let description = command.description.clone().unwrap_or_default();
+
+        Form {
+            kind: FormKind::EditDescription(Box::new(command)),
+            fields: vec![FormField {
+                name: "description",
+                value: description,
+            }],
+            focus: 0,
+            error: None,
+            max_alias_len,
+        }
+    }
+
+    /// A form for a new command starting from the fields of `command`, the alias left empty
+    /// as it has to be unique
+    pub fn duplicate(command: &Command, max_alias_len: usize) -> Form {
+        let fields = [
+            ("command", command.command.clone()),
+            ("alias", String::new()),
+            (
+                "description",
+                command.description.clone().unwrap_or_default(),
+            ),
+        ];
+
+        Form {
+            kind: FormKind::AddCommand,
+            fields: fields
+                .into_iter()
+                .map(|(name, value)| FormField { name, value })
+                .collect(),
+            focus: 1,
+            error: None,
+            max_alias_len,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match &self.kind {
+            FormKind::EditDescription(command) => format!("Description of {}", command.alias),
+            FormKind::AddCommand => "New command".to_string(),
+        }
+    }
+
+    pub fn input(&mut self, c: char) {
+        self.fields[self.focus].value.push(c);
+        self.error = None;
+    }
+
+    pub fn delete_input(&mut self) {
+        self.fields[self.focus].value.pop();
+        self.error = None;
+    }
+
+    pub fn next_field(&mut self) {
+        self.focus = (self.focus + 1) % self.fields.len();
+    }
+
+    pub fn previous_field(&mut self) {
+        self.focus = self.focus.checked_sub(1).unwrap_or(self.fields.len() - 1);
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.value.as_str())
+    }
+
+    /// The command the form would save, checked against the limits of the db columns
+    pub fn command(&self) -> Result<Command, ValidationError> {
+        let description = self
+            .value("description")
+            .map(str::trim)
+            .filter(|description| !description.is_empty())
+            .map(str::to_string);
+
+        let command = match &self.kind {
+            FormKind::EditDescription(command) => Command {
+                description,
+                ..*command.clone()
+            },
+            FormKind::AddCommand => {
+                let command = self.value("command").unwrap_or_default().trim();
+
+                Command::new(
+                    normalized_executable(command).unwrap_or_default(),
+                    command.to_string(),
+                    self.value("alias").unwrap_or_default().trim().to_string(),
+                    description,
+                )
+            }
+        };
+        command.validate(self.max_alias_len)?;
+
+        Ok(command)
+    }
+
+    /// Validates the form, focusing the offending field and keeping the error to show it
+    pub fn submit(&mut self) -> Option<Command> {
+        match self.command() {
+            Ok(command) => Some(command),
+            Err(e) => {
+                if let Some(index) = self.fields.iter().position(|field| field.name == e.field()) {
+                    self.focus = index;
+                }
+                self.error = Some(e.to_string());
+                None
+            }
+        }
+    }
+}
@@ -1,2 +1,6 @@
+pub mod action;
 pub mod app;
+pub mod clipboard;
+pub mod editor;
+pub mod form;
 pub mod tui;
@@ -1,5 +1,5 @@
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -7,18 +7,36 @@ use ratatui::{
     backend::{Backend, CrosstermBackend},
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Span, Spans, Text},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs, Wrap},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs, Wrap},
     Frame, Terminal,
 };
 
 use anyhow::Result;
-use std::{error::Error, io};
+use std::{
+    collections::HashSet,
+    error::Error,
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 use tracing::error;
+use unicode_width::UnicodeWidthStr;
+
+use super::action::{ChordState, Mode, Outcome};
+use super::app::{App, CapturedOutput, GroupBy, ListDisplay, RELATED_LIMIT, TICK_RATE};
+use super::clipboard::clipboard_for;
+use super::editor::{edit_via_editor, editor_command};
+use super::form::Form;
+use crate::config::app_config::AppConfig;
+use crate::model::command::{Command, Label};
+use crate::model::placeholder::{expand_automatic, required_env_vars};
+use crate::service::runner::{resolve_working_dir, run_captured, RunOutcome};
 
-use super::app::App;
+pub async fn run_terminal(db_path: &str, config: AppConfig) -> Result<(), Box<dyn Error>> {
+    // Load the commands first so errors are reported on a usable terminal
+    let mut app = App::new(db_path, config).await?;
 
-pub async fn run_terminal() -> Result<(), Box<dyn Error>> {
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -26,9 +44,7 @@ pub async fn run_terminal() -> Result<(), Box<dyn Error>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // create app and run it
-    let app = App::new().await?;
-    let res = run_app(&mut terminal, app);
+    let res = run_app(&mut terminal, &mut app).await;
 
     // restore terminal
     disable_raw_mode()?;
@@ -42,55 +58,217 @@ pub async fn run_terminal() -> Result<(), Box<dyn Error>> {
     if let Err(err) = res {
         error!("{:?}", err)
     }
+    app.close().await;
 
     Ok(())
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> io::Result<()> {
+async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
+    let mut chords = ChordState::default();
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
+        terminal.draw(|f| ui(f, app))?;
 
-        if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Char('q') => return Ok(()),
-                KeyCode::Right => {
-                    app.tabs.next();
+        let action = if event::poll(TICK_RATE)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            // Any key dismisses the copy preview before its time is up
+            app.copy_preview = None;
+            chords.map_key(key.code, app.mode, &app.config.keybindings, Instant::now())
+        } else {
+            let now = Instant::now();
+            app.tick(now);
+            // A lone first key of a chord does what it is bound to once the chord timed out
+            match chords.expire(now, &app.config.keybindings) {
+                Some(action) => action,
+                None => continue,
+            }
+        };
 
-                    // If commands list state remains selected, changing tabs will crash
-                    // the app because we still have some unwraps
-                    app.commands.state = ListState::default()
+        match app.apply(action) {
+            None => {}
+            Some(Outcome::Quit) => return Ok(()),
+            Some(
+                outcome @ (Outcome::Copy { exit }
+                | Outcome::CopyWithSudo { exit }
+                | Outcome::CopyResolved { exit }),
+            ) => {
+                let mut clipboard = clipboard_for(
+                    app.config.clipboard,
+                    app.config.clipboard_file.clone().map(PathBuf::from),
+                );
+                let copied = app.command_to_copy();
+                let clip_res = match outcome {
+                    Outcome::CopyWithSudo { .. } => app.copy_with_sudo(clipboard.as_mut()),
+                    Outcome::CopyResolved { .. } => {
+                        app.copy_resolved(clipboard.as_mut(), Command::resolved_path)
+                    }
+                    _ => app.save_command_to_clipboard(clipboard.as_mut()),
+                };
+                match clip_res {
+                    Ok(copy) => {
+                        app.status = Some(if let Some(executable) = copy.unresolved {
+                            format!("{executable} was not found, copied the command as is")
+                        } else if copy.expanded {
+                            "Copied with the placeholders filled in!".to_string()
+                        } else {
+                            "Copied!".to_string()
+                        });
+                        if let Err(e) = app.record_use(&copied).await {
+                            error!("Failed to count the use of the command: {e:?}");
+                        }
+                    }
+                    Err(e) => {
+                        error!("Encountered error while copying to clipboard: {e:?}");
+                        app.status = Some("Failed to copy the command".to_string());
+                    }
                 }
-                KeyCode::Left => {
-                    app.tabs.previous();
 
-                    // If commands list state remains selected, changing tabs will crash
-                    // the app because we still have some unwraps
-                    app.commands.state = ListState::default()
+                if exit {
+                    return Ok(());
                 }
-                KeyCode::Down => {
-                    let selected_executable_tab = app.tabs.titles.get(app.tabs.index).unwrap(); // This should not fail
-                    app.commands.next(selected_executable_tab)
+            }
+            Some(Outcome::Delete) => {
+                if let Err(e) = app.delete_selected().await {
+                    error!("Failed to delete the command: {e:?}");
+                    app.status = Some("Failed to delete the command".to_string());
                 }
-                KeyCode::Up => {
-                    let selected_executable_tab = app.tabs.titles.get(app.tabs.index).unwrap(); // This should not fail
-                    app.commands.previous(selected_executable_tab);
+            }
+            Some(Outcome::SubmitForm) => {
+                if let Err(e) = app.submit_form().await {
+                    error!("Failed to save the form: {e:?}");
+                    app.status = Some("Failed to save the command".to_string());
                 }
-                KeyCode::Enter => {
-                    let clip_res = app.save_command_to_clipboard();
-                    match clip_res {
-                        Ok(()) => return Ok(()),
-                        Err(e) => {
-                            error!("Encountered error while copying to clipboard: {e:?}");
-                            return Ok(());
+            }
+            Some(Outcome::Export) => {
+                if let Err(e) = app.export_view() {
+                    error!("Failed to export the commands: {e:?}");
+                    app.status = Some("Failed to export the commands".to_string());
+                }
+            }
+            Some(Outcome::ApplyTag) => {
+                if let Err(e) = app.apply_tag().await {
+                    error!("Failed to tag the commands: {e:?}");
+                    app.status = Some("Failed to tag the commands".to_string());
+                }
+            }
+            Some(Outcome::Rename) => {
+                if let Err(e) = app.rename_selected().await {
+                    error!("Failed to rename the command: {e:?}");
+                    app.status = Some("Failed to rename the command".to_string());
+                }
+            }
+            Some(Outcome::EditInEditor) => {
+                let command = app.get_selected_command().command;
+                let edited = with_suspended_terminal(terminal, || {
+                    edit_via_editor(&command, &editor_command())
+                })?;
+
+                let updated = match edited {
+                    Ok(edited) => app.update_selected_command(&edited).await,
+                    Err(e) => {
+                        error!("Failed to run the editor: {e:?}");
+                        app.status = Some("Failed to run the editor".to_string());
+                        Ok(())
+                    }
+                };
+                if let Err(e) = updated {
+                    error!("Failed to update the command: {e:?}");
+                    app.status = Some(format!("Failed to update the command: {e}"));
+                }
+            }
+            Some(Outcome::Run) => {
+                let command = app.command_to_copy();
+                let (text, _) = expand_automatic(&command.command);
+                let timeout = app.config.run_timeout_secs.map(Duration::from_secs);
+                app.running = Some(command.alias.clone());
+                let shell = command.shell.clone();
+                let working_dir = resolve_working_dir(&command, &app.config);
+                let run = run_captured(&text, shell.as_deref(), working_dir.as_deref(), timeout);
+                tokio::pin!(run);
+                // Keep redrawing so the spinner turns while the command runs
+                let result = loop {
+                    tokio::select! {
+                        result = &mut run => break result,
+                        _ = tokio::time::sleep(TICK_RATE) => {
+                            app.tick(Instant::now());
+                            terminal.draw(|f| ui(f, app))?;
+                        }
+                    }
+                };
+                app.running = None;
+                match result {
+                    Ok((outcome, output)) => {
+                        app.status = Some(match outcome {
+                            RunOutcome::Exited(status) => format!(
+                                "{} exited with {status}, o to add its output to the description",
+                                command.alias
+                            ),
+                            RunOutcome::TimedOut => format!("{} timed out", command.alias),
+                        });
+                        if let Err(e) = app.record_use(&command).await {
+                            error!("Failed to count the use of the command: {e:?}");
                         }
+                        app.last_output = Some(CapturedOutput { command, output });
+                    }
+                    Err(e) => {
+                        error!("Failed to run {text}: {e:?}");
+                        app.status = Some("Failed to run the command".to_string());
                     }
                 }
-                _ => {}
+            }
+            Some(Outcome::AppendOutput) => {
+                if let Err(e) = app.append_last_output_to_notes().await {
+                    error!("Failed to add the output to the description: {e:?}");
+                    app.status = Some("Failed to add the output".to_string());
+                }
+            }
+            Some(Outcome::CycleLabel) => {
+                if let Err(e) = app.cycle_label().await {
+                    error!("Failed to label the command: {e:?}");
+                    app.status = Some("Failed to label the command".to_string());
+                }
+            }
+            Some(Outcome::TogglePin) => {
+                if let Err(e) = app.toggle_pin().await {
+                    error!("Failed to pin the command: {e:?}");
+                    app.status = Some("Failed to pin the command".to_string());
+                }
+            }
+            Some(Outcome::ToggleEnabled) => {
+                if let Err(e) = app.toggle_enabled().await {
+                    error!("Failed to enable or disable the command: {e:?}");
+                    app.status = Some("Failed to enable or disable the command".to_string());
+                }
+            }
+            Some(Outcome::Undo) => {
+                if let Err(e) = app.undo().await {
+                    error!("Failed to undo: {e:?}");
+                    app.status = Some("Failed to undo".to_string());
+                }
             }
         }
     }
 }
 
+/// Hands the terminal over to `f`, e.g. to run an editor, and takes it back afterwards
+fn with_suspended_terminal<B: Backend, T>(
+    terminal: &mut Terminal<B>,
+    f: impl FnOnce() -> T,
+) -> io::Result<T> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    terminal.show_cursor()?;
+
+    let result = f();
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    terminal.clear()?;
+
+    Ok(result)
+}
+
 fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let size = f.size();
     let chunks = Layout::default()
@@ -101,20 +279,316 @@ fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let block = Block::default().style(Style::default().bg(Color::Black).fg(Color::LightYellow));
     f.render_widget(block, size);
 
-    let (msg, style) = (
+    let (mut msg, _style) = (
         vec![
-            Spans::from("Press q to exit"),
-            Spans::from("Left and Right arrows to navigate through the executable tab"),
-            Spans::from("Up and Down arrows to navigate through the alias list"),
-            Spans::from("Enter to select ca command, copy it to your clipboard and close the U.i."),
+            Spans::from("Press q to exit, d or dd to delete the selected command, D to duplicate it, H to hide it and u to undo"),
+            Spans::from("Left and Right arrows to navigate through the executable tab, ' and a letter to jump to one, Tab to list the commands instead of the aliases"),
+            Spans::from("g to group the tabs by executable, tag, favorite or group, G to search them all, V to show the hidden ones"),
+            Spans::from("Up and Down arrows to navigate the alias list, gg or Home for the first one, Space to select, t to tag, c to color, R to rename"),
+            Spans::from("PageUp and PageDown to scroll through long commands, E to edit them in $EDITOR, X to export the view"),
+            Spans::from("Enter to select ca command, copy it to your clipboard and close the U.i., r to run it, o to note its output"),
+            Spans::from("S to copy the command with sudo, P with the full path of its executable, p to pin it, e to edit its description, x for examples"),
         ],
         Style::default().add_modifier(Modifier::RAPID_BLINK),
     );
 
+    if let Some(rename) = &app.rename_input {
+        msg.push(Spans::from(match &rename.error {
+            Some(error) => Span::styled(error.clone(), Style::default().fg(Color::Red)),
+            None => Span::styled(
+                "Enter to rename, Esc to cancel",
+                Style::default().fg(Color::LightGreen),
+            ),
+        }));
+    } else if app.mode == Mode::JumpToTab {
+        msg.push(Spans::from(Span::styled(
+            "Jump: type the first letter of a tab, any other key to stop",
+            Style::default().fg(Color::LightGreen),
+        )));
+    } else if let Some(alias) = &app.running {
+        msg.push(Spans::from(Span::styled(
+            format!("Running {alias} {}", app.spinner()),
+            Style::default().fg(Color::LightGreen),
+        )));
+    } else if let Some(status) = &app.status {
+        msg.push(Spans::from(Span::styled(
+            status.clone(),
+            Style::default().fg(Color::LightGreen),
+        )));
+    }
+
     let help_message = Paragraph::new(msg);
     f.render_widget(help_message, chunks[0]);
 
-    draw_executable_tab(f, app, chunks[1]);
+    // The view summary and the version sit in the bottom right corner of the help, next to
+    // the status
+    if chunks[0].height > 0 {
+        let corner = Rect {
+            y: chunks[0].bottom() - 1,
+            height: 1,
+            ..chunks[0]
+        };
+        let version = Paragraph::new(Spans::from(vec![
+            Span::styled(
+                app.view_state_summary(),
+                Style::default().fg(Color::LightCyan),
+            ),
+            Span::raw("  "),
+            Span::styled(crate::build_info(), Style::default().fg(Color::DarkGray)),
+        ]))
+        .alignment(Alignment::Right);
+        f.render_widget(version, corner);
+    }
+
+    if app.search.is_some() {
+        draw_global_search(f, app, chunks[1]);
+    } else {
+        draw_executable_tab(f, app, chunks[1]);
+    }
+
+    if app.environment_picker.is_some() {
+        draw_environment_picker(f, app, size);
+    }
+
+    if let Some(preview) = &app.copy_preview {
+        draw_copy_preview(f, &preview.text, size);
+    }
+
+    if let Some(pending) = &app.pending_copy {
+        draw_confirmation(f, &pending.command, size);
+    }
+
+    if let Some(form) = &app.form {
+        draw_form(f, form, size);
+    }
+
+    if app.mode == Mode::ConfirmDiscard {
+        draw_discard_confirmation(f, size);
+    }
+
+    if let Some(input) = &app.export_input {
+        let title = format!("Export {} commands to", input.commands.len());
+        let path = Paragraph::new(format!("{}_", input.path))
+            .block(Block::default().borders(Borders::ALL).title(title));
+
+        let area = centered_rect(40, 10, size);
+        f.render_widget(Clear, area);
+        f.render_widget(path, area);
+    }
+
+    if let Some(tag) = &app.tag_input {
+        let title = format!("Tag for {} commands", app.selected_aliases.len());
+        let input = Paragraph::new(format!("{tag}_"))
+            .block(Block::default().borders(Borders::ALL).title(title));
+
+        let area = centered_rect(40, 10, size);
+        f.render_widget(Clear, area);
+        f.render_widget(input, area);
+    }
+}
+
+/// A rectangle of the given percentages of `area`, centered in it
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(vertical[1])[1]
+}
+
+fn draw_environment_picker<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let Some(picker) = &app.environment_picker else {
+        return;
+    };
+
+    let environments: Vec<ListItem> = picker
+        .variants
+        .iter()
+        .map(|variant| {
+            let environment = variant.environment.as_deref().unwrap_or("(default)");
+            ListItem::new(Spans::from(Span::raw(environment.to_string())))
+        })
+        .collect();
+
+    let title = format!("Environment for {}", picker.selected().alias);
+    let environments = List::new(environments)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    state.select(Some(picker.index));
+
+    let area = centered_rect(40, 30, area);
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(environments, area, &mut state);
+}
+
+/// The string that just landed on the clipboard
+fn draw_copy_preview<B: Backend>(f: &mut Frame<B>, text: &str, area: Rect) {
+    let preview = Paragraph::new(text.to_string())
+        .block(Block::default().borders(Borders::ALL).title("Copied"))
+        .wrap(Wrap { trim: false });
+
+    let area = centered_rect(60, 20, area);
+    f.render_widget(Clear, area);
+    f.render_widget(preview, area);
+}
+
+/// Asks before a dangerous command is copied
+fn draw_confirmation<B: Backend>(f: &mut Frame<B>, command: &str, area: Rect) {
+    let lines = vec![
+        Spans::from(Span::styled(
+            "This command looks dangerous:",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(command.to_string()),
+        Spans::from(""),
+        Spans::from("Press y to go ahead anyway, any other key to cancel"),
+    ];
+
+    let confirmation = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Confirm"))
+        .wrap(Wrap { trim: false });
+
+    let area = centered_rect(60, 30, area);
+    f.render_widget(Clear, area);
+    f.render_widget(confirmation, area);
+}
+
+/// Asks before the edits of the open form are thrown away
+fn draw_discard_confirmation<B: Backend>(f: &mut Frame<B>, area: Rect) {
+    let confirmation = Paragraph::new("Discard unsaved changes? [y/N]")
+        .block(Block::default().borders(Borders::ALL).title("Confirm"))
+        .wrap(Wrap { trim: false });
+
+    let area = centered_rect(40, 10, area);
+    f.render_widget(Clear, area);
+    f.render_widget(confirmation, area);
+}
+
+/// The fields of the form one below the other, the focused one highlighted, and the reason
+/// the last submission was rejected
+fn draw_form<B: Backend>(f: &mut Frame<B>, form: &Form, area: Rect) {
+    let mut lines = Vec::new();
+    for (index, field) in form.fields.iter().enumerate() {
+        let style = if index == form.focus {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let cursor = if index == form.focus { "_" } else { "" };
+
+        lines.push(Spans::from(Span::styled(format!("{}:", field.name), style)));
+        lines.push(Spans::from(format!("{}{cursor}", field.value)));
+    }
+
+    if let Some(error) = &form.error {
+        lines.push(Spans::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+
+    let form = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(form.title()))
+        .wrap(Wrap { trim: false });
+
+    let area = centered_rect(60, 40, area);
+    f.render_widget(Clear, area);
+    f.render_widget(form, area);
+}
+
+/// Title of the search input, counting the matches once something was typed
+fn search_title(query: &str, matches: usize) -> String {
+    match (query.is_empty(), matches) {
+        (true, _) => "Search".to_string(),
+        (false, 0) => "Search (no matches)".to_string(),
+        (false, 1) => "Search (1 match)".to_string(),
+        (false, n) => format!("Search ({n} matches)"),
+    }
+}
+
+/// The query above a flat list of the matching commands of every executable
+fn draw_global_search<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let Some(search) = &app.search else {
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    // Only the executable tabs can be told apart by the executables of the matches
+    let matching = if app.group_by == GroupBy::Executable && !search.query.is_empty() {
+        app.matching_executables(&search.query)
+    } else {
+        HashSet::new()
+    };
+    draw_tab_bar(f, app, chunks[0], &matching);
+    let chunks = &chunks[1..];
+
+    let query = Paragraph::new(format!("{}_", search.query)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(search_title(&search.query, search.matches)),
+    );
+    f.render_widget(query, chunks[0]);
+
+    let (results, total) = app.search_results_with_total();
+    let title = if results.len() < total {
+        format!("Results (showing first {} of {total})", results.len())
+    } else {
+        "Results".to_string()
+    };
+
+    let results: Vec<ListItem> = results
+        .iter()
+        .map(|command| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(
+                    format!("{}: ", command.executable),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(format!("{}  {}", command.alias, command.command)),
+            ]))
+        })
+        .collect();
+
+    let results = List::new(results)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    state.select(Some(search.index));
+    f.render_stateful_widget(results, chunks[1], &mut state);
 }
 
 fn draw_executable_tab<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
@@ -123,25 +597,134 @@ fn draw_executable_tab<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect)
         .constraints([Constraint::Percentage(11), Constraint::Percentage(89)].as_ref())
         .split(area);
 
-    let titles = app
-        .tabs
+    draw_tab_bar(f, app, chunks[0], &HashSet::new());
+    draw_commands_pane(f, app, chunks[1])
+}
+
+/// The tabs around the selected one, those in `highlighted` tinted, e.g. the executables
+/// matching the global search
+fn draw_tab_bar<B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    area: Rect,
+    highlighted: &HashSet<String>,
+) {
+    // The block borders take one column on each side
+    let window = visible_tab_window(
+        &app.tabs.titles,
+        app.tabs.index,
+        area.width.saturating_sub(2) as usize,
+    );
+
+    let mut titles: Vec<Spans> = window
         .titles
         .iter()
-        .map(|executable| Spans::from(Span::styled(executable, Style::default().fg(Color::Cyan))))
+        .map(|title| {
+            let style = if highlighted.contains(title) {
+                Style::default()
+                    .fg(Color::LightGreen)
+                    .add_modifier(Modifier::UNDERLINED)
+            } else {
+                Style::default().fg(Color::Cyan)
+            };
+            Spans::from(Span::styled(title, style))
+        })
         .collect();
 
+    let mut selected = app.tabs.index.saturating_sub(window.start);
+    if window.overflow_left {
+        titles.insert(0, Spans::from(OVERFLOW_LEFT));
+        selected += 1;
+    }
+    if window.overflow_right {
+        titles.push(Spans::from(OVERFLOW_RIGHT));
+    }
+
     let tabs = Tabs::new(titles)
-        .block(Block::default().borders(Borders::ALL).title("Executables"))
-        .select(app.tabs.index)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(app.group_by.title()),
+        )
+        .select(selected)
         .style(Style::default().fg(Color::Rgb(255, 213, 128)))
         .highlight_style(
             Style::default()
                 .add_modifier(Modifier::BOLD)
                 .bg(Color::Black),
         );
-    f.render_widget(tabs, chunks[0]);
+    f.render_widget(tabs, area);
+}
 
-    draw_commands_pane(f, app, chunks[1])
+const OVERFLOW_LEFT: &str = "‹";
+const OVERFLOW_RIGHT: &str = "›";
+
+/// The tabs that fit in the available width, as a window over all the titles
+#[derive(Debug, PartialEq)]
+pub struct TabWindow<'a> {
+    pub titles: &'a [String],
+    pub start: usize,
+    pub overflow_left: bool,
+    pub overflow_right: bool,
+}
+
+/// Width taken by the tabs widget to render `titles`: a space on each side of every title and
+/// a divider between them.
+fn tabs_width<'a>(titles: impl Iterator<Item = &'a str>) -> usize {
+    titles
+        .map(|title| title.width() + 3)
+        .sum::<usize>()
+        .saturating_sub(1)
+}
+
+/// Computes which tabs fit in `width` columns, growing a window around the `selected` tab
+/// alternately to the right and to the left so the selection stays centered. The overflow
+/// indicators are accounted for when some tabs are hidden on either side.
+pub fn visible_tab_window(titles: &[String], selected: usize, width: usize) -> TabWindow<'_> {
+    if titles.is_empty() {
+        return TabWindow {
+            titles,
+            start: 0,
+            overflow_left: false,
+            overflow_right: false,
+        };
+    }
+
+    let selected = selected.min(titles.len() - 1);
+    let fits = |start: usize, end: usize| {
+        let mut entries: Vec<&str> = titles[start..end].iter().map(String::as_str).collect();
+        if start > 0 {
+            entries.push(OVERFLOW_LEFT);
+        }
+        if end < titles.len() {
+            entries.push(OVERFLOW_RIGHT);
+        }
+        tabs_width(entries.into_iter()) <= width
+    };
+
+    let (mut start, mut end) = (selected, selected + 1);
+    loop {
+        let grow_right = end < titles.len() && fits(start, end + 1);
+        if grow_right {
+            end += 1;
+        }
+
+        let grow_left = start > 0 && fits(start - 1, end);
+        if grow_left {
+            start -= 1;
+        }
+
+        if !grow_right && !grow_left {
+            break;
+        }
+    }
+
+    TabWindow {
+        titles: &titles[start..end],
+        start,
+        overflow_left: start > 0,
+        overflow_right: end < titles.len(),
+    }
 }
 
 fn draw_commands_pane<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
@@ -152,20 +735,36 @@ fn draw_commands_pane<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 fn draw_alias_pane<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints(vec![Constraint::Percentage(25), Constraint::Percentage(75)].as_ref())
+        .constraints([Constraint::Percentage(25), Constraint::Percentage(75)].as_ref())
         .split(area);
 
+    if app.is_empty() {
+        let empty = Paragraph::new("No commands yet, import some with --file")
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(app.list_display.title()),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty, area);
+        return;
+    }
+
     let exes = app.executables();
     let current_command_tab = exes.get(app.tabs.index).unwrap(); // This should not fail
     let commands = app.get_by_executable(current_command_tab);
 
     let aliases: Vec<ListItem> = commands
-        .into_iter()
-        .map(|command| ListItem::new(vec![Spans::from(Span::raw(command.clone().alias))]))
+        .iter()
+        .map(|command| ListItem::new(vec![Spans::from(alias_spans(app, command))]))
         .collect();
 
     let aliases = List::new(aliases)
-        .block(Block::default().borders(Borders::ALL).title("Alias list"))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(app.list_display.title()),
+        )
         .highlight_style(Style::default().add_modifier(Modifier::BOLD))
         .highlight_symbol("> ");
     f.render_stateful_widget(aliases, chunks[0], &mut app.commands.state);
@@ -173,15 +772,156 @@ fn draw_alias_pane<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     draw_description_and_command_pane(f, app, chunks[1]);
 }
 
+fn label_color(label: Label) -> Color {
+    match label {
+        Label::Red => Color::Red,
+        Label::Yellow => Color::Yellow,
+        Label::Green => Color::Green,
+        Label::Blue => Color::Blue,
+        Label::Purple => Color::Magenta,
+    }
+}
+
+/// The title, alias or command as listed, preceded by the markers of its state and its color label
+fn alias_spans<'a>(app: &App, command: &'a Command) -> Vec<Span<'a>> {
+    let mut spans = Vec::new();
+    if app.invalid_aliases.contains(&command.alias) {
+        spans.push(Span::styled("✗ ", Style::default().fg(Color::Red)));
+    }
+    if app.selected_aliases.contains(&command.alias) {
+        spans.push(Span::styled("+ ", Style::default().fg(Color::LightBlue)));
+    }
+    if command.pinned {
+        spans.push(Span::styled("* ", Style::default().fg(Color::LightGreen)));
+    }
+    if let Some(label) = command.label {
+        spans.push(Span::styled("● ", Style::default().fg(label_color(label))));
+    }
+    let expired = command.is_expired(&app.today);
+    match &app.rename_input {
+        Some(rename) if rename.alias == command.alias => spans.push(Span::styled(
+            format!("{}_", rename.input),
+            Style::default().add_modifier(Modifier::UNDERLINED),
+        )),
+        _ => spans.push(Span::styled(
+            match app.list_display {
+                ListDisplay::Alias => command.display_name(),
+                ListDisplay::Command => command.command.as_str(),
+            },
+            if expired {
+                Style::default().add_modifier(Modifier::CROSSED_OUT)
+            } else {
+                Style::default()
+            },
+        )),
+    }
+    if expired {
+        spans.push(Span::styled(" ⚠", Style::default().fg(Color::Red)));
+    }
+    if !command.enabled {
+        spans.push(Span::styled(
+            " (hidden)",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    spans
+}
+
+/// The examples section of the description pane: a header, followed by the examples when expanded
+fn example_lines(command: &Command, expanded: bool) -> Vec<Spans<'static>> {
+    if command.examples.is_empty() {
+        return Vec::new();
+    }
+
+    let (marker, hint) = if expanded {
+        ("▾", "x to collapse")
+    } else {
+        ("▸", "x to expand")
+    };
+    let mut lines = vec![Spans::from(Span::styled(
+        format!("{marker} Examples ({}), {hint}", command.examples.len()),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+
+    if expanded {
+        lines.extend(
+            command
+                .examples
+                .iter()
+                .map(|example| Spans::from(format!("  $ {example}"))),
+        );
+    }
+
+    lines
+}
+
+/// The environment variables the command expands, the unset ones in red
+fn env_var_lines(command: &Command, is_set: impl Fn(&str) -> bool) -> Vec<Spans<'static>> {
+    let names = required_env_vars(&command.command);
+    if names.is_empty() {
+        return Vec::new();
+    }
+
+    let mut spans = vec![Span::styled(
+        "Needs:",
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    for name in names {
+        let style = if is_set(&name) {
+            Style::default()
+        } else {
+            Style::default().fg(Color::Red)
+        };
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(format!("${name}"), style));
+    }
+
+    vec![Spans::from(spans)]
+}
+
 fn draw_description_and_command_pane<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints(vec![Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+        .constraints(
+            [
+                Constraint::Percentage(50),
+                Constraint::Percentage(30),
+                Constraint::Percentage(20),
+            ]
+            .as_ref(),
+        )
         .split(area);
 
     let selected_command = app.get_selected_command();
 
-    let description = selected_command.description.unwrap_or(String::new());
+    let mut description: Vec<Spans> = selected_command
+        .rendered_description()
+        .unwrap_or_default()
+        .lines()
+        .map(|line| Spans::from(line.to_string()))
+        .collect();
+    let examples = example_lines(&selected_command, app.show_examples);
+    if !examples.is_empty() {
+        description.push(Spans::from(""));
+        description.extend(examples);
+    }
+    if let Some(expires_at) = selected_command
+        .expires_at
+        .as_ref()
+        .filter(|_| selected_command.is_expired(&app.today))
+    {
+        description.push(Spans::from(""));
+        description.push(Spans::from(Span::styled(
+            format!("⚠ Expired on {expires_at}, the command may be stale"),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    let env_vars = env_var_lines(&selected_command, |name| std::env::var_os(name).is_some());
+    if !env_vars.is_empty() {
+        description.push(Spans::from(""));
+        description.extend(env_vars);
+    }
 
     let description = Paragraph::new(description)
         .block(
@@ -192,10 +932,237 @@ fn draw_description_and_command_pane<B: Backend>(f: &mut Frame<B>, app: &App, ar
         .alignment(Alignment::Left)
         .wrap(Wrap { trim: true });
 
-    let command = selected_command.command;
-    let command =
-        Paragraph::new(command).block(Block::default().borders(Borders::ALL).title("Command"));
+    // Multi-line commands keep their indentation and can be scrolled through
+    let command = Paragraph::new(selected_command.command.as_str())
+        .block(Block::default().borders(Borders::ALL).title("Command"))
+        .wrap(Wrap { trim: false })
+        .scroll((app.command_scroll, 0));
+
+    let related: Vec<ListItem> = app
+        .related_commands(&selected_command, RELATED_LIMIT)
+        .iter()
+        .map(|command| {
+            ListItem::new(Spans::from(vec![
+                Span::raw(command.display_name().to_string()),
+                Span::styled(
+                    format!(" ({})", command.executable),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+    let related = List::new(related).block(Block::default().borders(Borders::ALL).title("Related"));
 
     f.render_widget(description, chunks[0]);
     f.render_widget(command, chunks[1]);
+    f.render_widget(related, chunks[2]);
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, buffer::Buffer, Terminal};
+
+    use ratatui::style::{Color, Style};
+
+    use super::{alias_spans, env_var_lines, example_lines, search_title, ui, visible_tab_window};
+    use crate::model::command::{Command, Examples, Label};
+    use crate::ui::action::Action;
+    use crate::ui::app::{App, ListDisplay};
+
+    fn render(app: &mut App) -> Buffer {
+        let backend = TestBackend::new(80, 40);
+        let mut terminal = Terminal::new(backend).unwrap();
+        terminal.draw(|f| ui(f, app)).unwrap();
+
+        terminal.backend().buffer().clone()
+    }
+
+    fn line(buffer: &Buffer, y: u16) -> String {
+        (0..buffer.area.width)
+            .map(|x| buffer.get(x, y).symbol.clone())
+            .collect()
+    }
+
+    fn command(executable: &str, command: &str, alias: &str) -> Command {
+        Command::new(
+            executable.to_string(),
+            command.to_string(),
+            alias.to_string(),
+            Some(format!("Runs {command}")),
+        )
+    }
+
+    fn titles(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_label_bullet() {
+        let mut labelled = command("git", "git pull", "git_pull");
+        labelled.label = Some(Label::Green);
+        let app = App::from_commands(vec![labelled.clone()]);
+
+        let spans = alias_spans(&app, &labelled);
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].content, "● ");
+        assert_eq!(spans[0].style, Style::default().fg(Color::Green));
+        assert_eq!(spans[1].content, "git_pull");
+
+        let plain = command("ls", "ls -a", "ls_all");
+        assert_eq!(alias_spans(&app, &plain).len(), 1);
+    }
+
+    #[test]
+    fn test_env_var_lines() {
+        let deploy = command("deploy", "deploy $REGION ${CLUSTER}", "deploy");
+
+        let lines = env_var_lines(&deploy, |name| name == "REGION");
+        assert_eq!(lines.len(), 1);
+        let spans = &lines[0].0;
+        assert_eq!(spans[2].content, "$REGION");
+        assert_eq!(spans[2].style, Style::default());
+        assert_eq!(spans[4].content, "$CLUSTER");
+        assert_eq!(spans[4].style, Style::default().fg(Color::Red));
+
+        let plain = command("ls", "ls -a", "ls_all");
+        assert!(env_var_lines(&plain, |_| false).is_empty());
+    }
+
+    #[test]
+    fn test_list_display() {
+        let listed = command("git", "git pull", "git_pull");
+        let mut app = App::from_commands(vec![listed.clone()]);
+
+        assert_eq!(alias_spans(&app, &listed)[0].content, "git_pull");
+
+        app.apply(Action::ToggleListDisplay);
+        assert_eq!(app.list_display, ListDisplay::Command);
+        assert_eq!(alias_spans(&app, &listed)[0].content, "git pull");
+    }
+
+    #[test]
+    fn test_list_shows_title() {
+        let mut titled = command("git", "git log --graph --oneline", "glg");
+        titled.title = Some("Commit graph".to_string());
+        let untitled = command("git", "git pull", "git_pull");
+        let mut app = App::from_commands(vec![titled.clone(), untitled.clone()]);
+
+        assert_eq!(alias_spans(&app, &titled)[0].content, "Commit graph");
+        assert_eq!(alias_spans(&app, &untitled)[0].content, "git_pull");
+
+        let buffer = render(&mut app);
+        let rows: Vec<String> = (0..buffer.area.height).map(|y| line(&buffer, y)).collect();
+        assert!(rows.iter().any(|row| row.contains("Commit graph")));
+        assert!(rows.iter().any(|row| row.contains("git_pull")));
+    }
+
+    #[test]
+    fn test_search_title() {
+        assert_eq!(search_title("", 12), "Search");
+        assert_eq!(search_title("git", 3), "Search (3 matches)");
+        assert_eq!(search_title("git p", 1), "Search (1 match)");
+        assert_eq!(search_title("zzz", 0), "Search (no matches)");
+    }
+
+    #[test]
+    fn test_tab_window_no_overflow() {
+        let titles = titles(&["git", "ls", "ssh"]);
+
+        let window = visible_tab_window(&titles, 1, 80);
+
+        assert_eq!(window.titles, &titles[..]);
+        assert_eq!(window.start, 0);
+        assert!(!window.overflow_left);
+        assert!(!window.overflow_right);
+    }
+
+    #[test]
+    fn test_tab_window_overflow() {
+        let titles = titles(&["aaaa", "bbbb", "cccc", "dddd", "eeee", "ffff", "gggg"]);
+
+        // Room for three 4-wide titles plus both indicators: 3 * 7 + 2 * 4 - 1
+        let window = visible_tab_window(&titles, 3, 28);
+
+        assert_eq!(window.titles, &titles[2..5]);
+        assert_eq!(window.start, 2);
+        assert!(window.overflow_left);
+        assert!(window.overflow_right);
+    }
+
+    #[test]
+    fn test_tab_window_overflow_at_the_edges() {
+        let titles = titles(&["aaaa", "bbbb", "cccc", "dddd", "eeee"]);
+
+        let window = visible_tab_window(&titles, 0, 20);
+        assert_eq!(window.start, 0);
+        assert!(!window.overflow_left);
+        assert!(window.overflow_right);
+
+        let window = visible_tab_window(&titles, 4, 20);
+        assert_eq!(window.titles.last().unwrap(), "eeee");
+        assert!(window.overflow_left);
+        assert!(!window.overflow_right);
+    }
+
+    #[test]
+    fn test_tab_window_empty() {
+        let titles = titles(&[]);
+
+        let window = visible_tab_window(&titles, 0, 20);
+        assert!(window.titles.is_empty());
+    }
+
+    #[test]
+    fn test_render_empty() {
+        let mut app = App::from_commands(Vec::new());
+
+        let buffer = render(&mut app);
+
+        assert!(line(&buffer, 0).starts_with("Press q to exit"));
+        assert!(line(&buffer, 7).trim_end().ends_with(&crate::build_info()));
+        assert!(line(&buffer, 7).contains("group:executable sort:alias"));
+        assert!((0..40).any(|y| line(&buffer, y).contains("No commands yet")));
+    }
+
+    #[test]
+    fn test_render_populated() {
+        let mut app = App::from_commands(vec![
+            command("git", "git pull", "git_pull"),
+            command("git", "git push", "git_push"),
+            command("ls", "ls -a", "ls_all"),
+        ]);
+        app.commands.state.select(Some(1));
+
+        let buffer = render(&mut app);
+
+        // The tab bar sits right below the 8 rows of help, inside its block
+        assert!(line(&buffer, 8).starts_with("┌Executables"));
+        assert!(line(&buffer, 9).starts_with("│ git │ ls "));
+
+        // The alias list follows the tab bar, with the selected alias highlighted
+        let aliases: Vec<String> = (0..40).map(|y| line(&buffer, y)).collect();
+        let list_top = aliases
+            .iter()
+            .position(|line| line.starts_with("┌Alias list"))
+            .unwrap();
+        assert!(aliases[list_top + 1].starts_with("│  git_pull"));
+        assert!(aliases[list_top + 2].starts_with("│> git_push"));
+
+        assert!(aliases.iter().any(|line| line.contains("Runs git push")));
+    }
+
+    #[test]
+    fn test_example_lines() {
+        let mut command = Command::new(
+            "ssh".to_string(),
+            "ssh {host}".to_string(),
+            "ssh_host".to_string(),
+            None,
+        );
+        assert!(example_lines(&command, true).is_empty());
+
+        command.examples = Examples(vec!["ssh db1".to_string(), "ssh db2".to_string()]);
+        assert_eq!(example_lines(&command, false).len(), 1);
+        assert_eq!(example_lines(&command, true).len(), 3);
+    }
 }